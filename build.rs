@@ -0,0 +1,256 @@
+//! Generates `NixLicense` from `data/licenses.json`, an export of nixpkgs'
+//! `lib/licenses.nix`, and `data/spdx-licenses.json`, the SPDX license list, which
+//! tells which licenses are OSI approved. `nxpkgr licenses sync` keeps both up to date.
+
+use serde_json::{Map, Value};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    fmt::Write,
+    fs,
+    path::Path,
+};
+
+const LICENSES_JSON: &str = "data/licenses.json";
+const SPDX_LICENSES_JSON: &str = "data/spdx-licenses.json";
+
+struct License {
+    attr: String,
+    variant: String,
+    full_name: String,
+    spdx_id: Option<String>,
+    free: bool,
+    redistributable: bool,
+    deprecated: bool,
+    osi_approved: bool,
+}
+
+/// `cc-by-sa-40` becomes `CcBySa40`, `gpl3Plus` becomes `Gpl3Plus`.
+fn variant_name(attr: &str) -> String {
+    attr.split(['-', '_'])
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn license(attr: &str, meta: &Map<String, Value>, osi_approved: &BTreeSet<String>) -> License {
+    let string = |key: &str| meta.get(key).and_then(Value::as_str).map(str::to_string);
+    let flag = |key: &str| meta.get(key).and_then(Value::as_bool);
+    let free = flag("free").unwrap_or(true);
+    let spdx_id = string("spdxId");
+    License {
+        attr: attr.to_string(),
+        variant: variant_name(attr),
+        full_name: string("fullName").unwrap_or_else(|| attr.to_string()),
+        osi_approved: spdx_id.as_ref().is_some_and(|id| osi_approved.contains(id)),
+        spdx_id,
+        free,
+        redistributable: flag("redistributable").unwrap_or(free),
+        deprecated: flag("deprecated").unwrap_or(false),
+    }
+}
+
+fn generate(licenses: &[License]) -> String {
+    let mut out = String::new();
+    let mut line = |s: String| {
+        out.push_str(&s);
+        out.push('\n');
+    };
+
+    line(String::from(
+        "#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]",
+    ));
+    line(String::from("pub enum NixLicense {"));
+    for license in licenses {
+        line(format!("    /// {}", license.full_name));
+        if license.deprecated {
+            line(String::from("    ///"));
+            line(String::from("    /// Deprecated in nixpkgs"));
+        }
+        line(format!("    {},", license.variant));
+    }
+    line(String::from("}"));
+    line(String::new());
+
+    // not every accessor is used
+    line(String::from("#[allow(dead_code)]"));
+    line(String::from("impl NixLicense {"));
+    line(String::from(
+        "    pub const ALL: &'static [NixLicense] = &[",
+    ));
+    for license in licenses {
+        line(format!("        NixLicense::{},", license.variant));
+    }
+    line(String::from("    ];"));
+    line(String::new());
+
+    let mut method = |signature: &str, arm: &dyn Fn(&License) -> Option<String>, default| {
+        line(format!("    pub fn {} {{", signature));
+        line(String::from("        match self {"));
+        for license in licenses {
+            if let Some(value) = arm(license) {
+                line(format!(
+                    "            NixLicense::{} => {},",
+                    license.variant, value
+                ));
+            }
+        }
+        if let Some(default) = default {
+            line(format!("            _ => {},", default));
+        }
+        line(String::from("        }"));
+        line(String::from("    }"));
+        line(String::new());
+    };
+    method(
+        "nix_attr(self) -> &'static str",
+        &|license| Some(format!("{:?}", license.attr)),
+        None,
+    );
+    method(
+        "to_nix_meta(self) -> &'static str",
+        &|license| Some(format!("{:?}", format!("lib.licenses.{}", license.attr))),
+        None,
+    );
+    method(
+        "full_name(self) -> &'static str",
+        &|license| Some(format!("{:?}", license.full_name)),
+        None,
+    );
+    method(
+        "spdx_id(self) -> Option<&'static str>",
+        &|license| license.spdx_id.as_ref().map(|id| format!("Some({:?})", id)),
+        Some("None"),
+    );
+    let mut predicate = |name: &str, negate: bool, variants: Vec<&str>| {
+        line(format!("    pub fn {}(self) -> bool {{", name));
+        if variants.is_empty() {
+            line(format!("        {}", negate));
+        } else {
+            let variants: Vec<String> = variants
+                .iter()
+                .map(|variant| format!("NixLicense::{}", variant))
+                .collect();
+            line(format!(
+                "        {}matches!(self, {})",
+                if negate { "!" } else { "" },
+                variants.join(" | ")
+            ));
+        }
+        line(String::from("    }"));
+        line(String::new());
+    };
+    let variants = |keep: &dyn Fn(&License) -> bool| {
+        licenses
+            .iter()
+            .filter(|license| keep(license))
+            .map(|license| license.variant.as_str())
+            .collect()
+    };
+    predicate("is_free", true, variants(&|license| !license.free));
+    predicate(
+        "is_redistributable",
+        true,
+        variants(&|license| !license.redistributable),
+    );
+    predicate(
+        "is_deprecated",
+        false,
+        variants(&|license| license.deprecated),
+    );
+    predicate(
+        "is_osi_approved",
+        false,
+        variants(&|license| license.osi_approved),
+    );
+
+    let mut lookup = |name: &str, key: &dyn Fn(&License) -> Option<&str>| {
+        line(format!(
+            "    pub fn {}(s: &str) -> Option<&'static Self> {{",
+            name
+        ));
+        line(String::from("        match s {"));
+        for license in licenses {
+            if let Some(key) = key(license) {
+                line(format!(
+                    "            {:?} => Some(&NixLicense::{}),",
+                    key, license.variant
+                ));
+            }
+        }
+        line(String::from("            _ => None,"));
+        line(String::from("        }"));
+        line(String::from("    }"));
+        line(String::new());
+    };
+    lookup("from_spdx_id", &|license| license.spdx_id.as_deref());
+    lookup("from_nix_attr", &|license| Some(&license.attr));
+
+    out.truncate(out.trim_end().len());
+    out.push_str("\n}\n");
+    out
+}
+
+/// The SPDX identifiers the SPDX license list marks as OSI approved.
+fn osi_approved() -> BTreeSet<String> {
+    let json = fs::read_to_string(SPDX_LICENSES_JSON)
+        .unwrap_or_else(|e| panic!("Unable to read {}: {}", SPDX_LICENSES_JSON, e));
+    let list: Value = serde_json::from_str(&json)
+        .unwrap_or_else(|e| panic!("{} is not an SPDX license list: {}", SPDX_LICENSES_JSON, e));
+    list["licenses"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|license| license["isOsiApproved"].as_bool() == Some(true))
+        .filter_map(|license| license["licenseId"].as_str().map(str::to_string))
+        .collect()
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", LICENSES_JSON);
+    println!("cargo:rerun-if-changed={}", SPDX_LICENSES_JSON);
+
+    let json = fs::read_to_string(LICENSES_JSON)
+        .unwrap_or_else(|e| panic!("Unable to read {}: {}", LICENSES_JSON, e));
+    let exported: BTreeMap<String, Value> = serde_json::from_str(&json)
+        .unwrap_or_else(|e| panic!("{} is not a licenses export: {}", LICENSES_JSON, e));
+
+    let osi_approved = osi_approved();
+    let licenses: Vec<License> = exported
+        .iter()
+        .filter_map(|(attr, meta)| {
+            meta.as_object()
+                .map(|meta| license(attr, meta, &osi_approved))
+        })
+        .collect();
+
+    let mut variants = BTreeMap::new();
+    let mut spdx_ids = BTreeMap::new();
+    for license in &licenses {
+        if let Some(other) = variants.insert(&license.variant, &license.attr) {
+            panic!(
+                "{} and {} would both be NixLicense::{}",
+                other, license.attr, license.variant
+            );
+        }
+        if let Some(id) = &license.spdx_id {
+            if let Some(other) = spdx_ids.insert(id, &license.attr) {
+                panic!("{} and {} both have SPDX id {}", other, license.attr, id);
+            }
+        }
+    }
+
+    let mut generated = String::new();
+    writeln!(generated, "// Generated by build.rs from {}", LICENSES_JSON).unwrap();
+    generated.push('\n');
+    generated.push_str(&generate(&licenses));
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("licenses.rs");
+    fs::write(out, generated).expect("Unable to write the generated licenses");
+}
@@ -15,12 +15,11 @@
   outputs = { self, utils, rust-overlay, devshell, nixpkgs, naersk, flake-compat, ... }:
     utils.lib.eachDefaultSystem (system:
       let
-        inherit (naersk.lib.${system}) buildPackage;
         pkgs = import nixpkgs {
           inherit system;
           overlays = [ devshell.overlay rust-overlay.overlay ];
         };
-        rust-stable = pkgs.rust-bin.stable."1.54.0".default.override {
+        rust-stable = pkgs.rust-bin.stable."1.70.0".default.override {
           extensions = [
             "cargo"
             "clippy"
@@ -110,7 +109,7 @@
       {
 
         # nix build
-        defaultPackage = buildPackage {
+        defaultPackage = naersk-lib.buildPackage {
           pname = "nxpkgr";
           nativeBuildInputs = with pkgs; [ pkg-config ];
           root = ./.;
@@ -2,11 +2,11 @@ mod nix;
 mod package;
 mod sources;
 
-use color_eyre::eyre;
-use eyre::{Report, Result};
-use tracing::instrument;
+use color_eyre::{eyre, Section};
+use eyre::{eyre, Report, Result};
+use tracing::{info, instrument, warn};
 
-use std::path::{Path, PathBuf};
+use std::{fs, path::Path};
 
 /*
     for reading json AST
@@ -14,6 +14,49 @@ use std::path::{Path, PathBuf};
 */
 
 const PATH_TO_EXAMPLE_TOML: &str = "./data/example_config.toml";
+const PATH_TO_LICENSES_JSON: &str = "./data/licenses.json";
+const PATH_TO_SPDX_LICENSES_JSON: &str = "./data/spdx-licenses.json";
+
+/// What to do, as given on the command line.
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    /// `nxpkgr`: write the packages, or print them
+    Generate,
+    /// `nxpkgr check`: only resolve the packages, and fail when they break the license
+    /// policy
+    Check,
+    /// `nxpkgr licenses sync`: update the exports `NixLicense` is generated from
+    LicensesSync,
+    /// `nxpkgr licenses [--format table|json|csv]`: report the licenses of every package
+    LicensesReport(nix::ReportFormat),
+    /// `nxpkgr updates`: report the packages that changed since they were written, with
+    /// their changelogs
+    Updates,
+    /// `nxpkgr updates --commit-message <name>`: the commit message of a package's update
+    CommitMessage(String),
+}
+
+impl Command {
+    fn from_args(args: &[String]) -> Result<Self> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            [] => Ok(Command::Generate),
+            ["check"] => Ok(Command::Check),
+            ["licenses", "sync"] => Ok(Command::LicensesSync),
+            ["licenses"] => Ok(Command::LicensesReport(nix::ReportFormat::Table)),
+            ["licenses", "--format", format] => nix::ReportFormat::from_name(format)
+                .map(Command::LicensesReport)
+                .ok_or_else(|| eyre!("Unknown report format {}", format))
+                .suggestion("Use one of table, json or csv"),
+            ["updates"] => Ok(Command::Updates),
+            ["updates", "--commit-message", name] => Ok(Command::CommitMessage(name.to_string())),
+            _ => Err(eyre!("Unknown command {}", args.join(" "))).suggestion(
+                "Run nxpkgr, nxpkgr check, nxpkgr licenses [--format table|json|csv], \
+                 nxpkgr licenses sync or nxpkgr updates [--commit-message <name>]",
+            ),
+        }
+    }
+}
 
 #[instrument]
 #[tokio::main]
@@ -29,9 +72,134 @@ async fn main() -> Result<(), Report> {
         })
         .install()?;
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = Command::from_args(&args)?;
+
+    if command == Command::LicensesSync {
+        let count = nix::sync_licenses(Path::new(PATH_TO_LICENSES_JSON))?;
+        info!("Wrote {} licenses to {}", count, PATH_TO_LICENSES_JSON);
+        let count = nix::sync_spdx_licenses(Path::new(PATH_TO_SPDX_LICENSES_JSON)).await?;
+        info!("Wrote {} licenses to {}", count, PATH_TO_SPDX_LICENSES_JSON);
+        info!("Rebuild to update NixLicense");
+        return Ok(());
+    }
+
     let manifest = package::TomlManifest::from_file(Path::new(PATH_TO_EXAMPLE_TOML).to_path_buf())?;
-    let openvsx_packages = manifest.get_openvsx_nixpkgs().await?;
-    println!("{:#?}", openvsx_packages);
+    let settings = manifest.settings.clone().unwrap_or_default();
+    sources::github::authenticate(&manifest.github_hosts()?, &manifest.github_token_files()?)?;
+
+    let (mut packages, mut unpublished) = manifest.get_openvsx_nixpkgs().await?;
+    let (vsmarketplace, vsmarketplace_unpublished) = manifest.get_vsmarketplace_nixpkgs().await?;
+    packages.extend(vsmarketplace);
+    unpublished.extend(vsmarketplace_unpublished);
+    packages.extend(manifest.get_crates_nixpkgs().await?);
+    packages.extend(manifest.get_pypi_nixpkgs().await?);
+    packages.extend(manifest.get_npm_nixpkgs().await?);
+    packages.extend(manifest.get_amo_nixpkgs().await?);
+    packages.extend(manifest.get_jetbrains_nixpkgs().await?);
+    packages.extend(manifest.get_github_nixpkgs().await?);
+    packages.extend(manifest.get_gitlab_nixpkgs().await?);
+    packages.extend(manifest.get_git_nixpkgs().await?);
+
+    if settings.mark_deprecated_broken {
+        for package in &mut packages {
+            if package.meta.status.is_some() {
+                package.meta.broken = Some(true);
+            }
+        }
+    }
+
+    for package in &packages {
+        if let Some(status) = &package.meta.status {
+            warn!("{}: {}", package.name, status);
+        }
+        match package.meta.license_confidence {
+            Some(nix::LicenseConfidence::Spdx) => (),
+            Some(confidence) => info!(
+                "{}: license recognised by its {}, check it is right",
+                package.name, confidence
+            ),
+            None => warn!("{}: no license recognised", package.name),
+        }
+        if let Some(native) = package.builder.native_binaries() {
+            warn!(
+                "{}: {}, check it runs with autoPatchelfHook",
+                package.name,
+                native.summary()
+            );
+        }
+    }
+    for name in &unpublished {
+        warn!("{}: {}", name, nix::PackageStatus::Unpublished);
+    }
+
+    let violations = settings
+        .license_policy
+        .as_ref()
+        .map(|policy| policy.check(&packages))
+        .unwrap_or_default();
+    for violation in &violations {
+        warn!("{}", violation);
+    }
+    let updates = nix::updates(&packages, &settings.output);
+    match &command {
+        Command::LicensesReport(format) => {
+            print!("{}", nix::license_report(&packages, *format));
+            return Ok(());
+        }
+        Command::Updates => {
+            print!("{}", nix::update_report(&updates));
+            return Ok(());
+        }
+        Command::CommitMessage(name) => {
+            return match updates.iter().find(|update| &update.package.name == name) {
+                Some(update) => {
+                    print!("{}", update.commit_message());
+                    Ok(())
+                }
+                None => Err(eyre!("{} has no update", name)).note(format!(
+                    "Packages are compared with those written to {}",
+                    settings.output.display()
+                )),
+            };
+        }
+        _ => (),
+    }
+    if command == Command::Check {
+        if !violations.is_empty() {
+            return Err(eyre!("{} license policy violations", violations.len()))
+                .suggestion("Change `settings.license_policy` or remove the packages");
+        }
+        let flagged = packages
+            .iter()
+            .filter(|package| package.meta.status.is_some())
+            .count();
+        return match flagged + unpublished.len() {
+            0 => Ok(()),
+            count => Err(eyre!(
+                "{} packages are deprecated or no longer published",
+                count
+            ))
+            .suggestion("Switch to their replacements or remove them from the manifest"),
+        };
+    }
+
+    if settings.create_package || settings.create_overlay {
+        for update in &updates {
+            info!("{}", update.title());
+        }
+    }
+    for package in &packages {
+        if settings.create_package || settings.create_overlay {
+            package.write(&settings.output)?;
+        } else {
+            println!("{}", package.to_nix());
+        }
+    }
+
+    if settings.create_overlay {
+        fs::write(settings.output.join("overlay.nix"), nix::overlay(&packages))?;
+    }
 
     Ok(())
 }
@@ -52,3 +220,38 @@ fn install_tracing() {
         .with(ErrorLayer::default())
         .init();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> Result<Command> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Command::from_args(&args)
+    }
+
+    #[test]
+    fn test_command() {
+        assert_eq!(command(&[]).unwrap(), Command::Generate);
+        assert_eq!(command(&["check"]).unwrap(), Command::Check);
+        assert_eq!(
+            command(&["licenses", "sync"]).unwrap(),
+            Command::LicensesSync
+        );
+        assert_eq!(
+            command(&["licenses"]).unwrap(),
+            Command::LicensesReport(nix::ReportFormat::Table)
+        );
+        assert_eq!(
+            command(&["licenses", "--format", "csv"]).unwrap(),
+            Command::LicensesReport(nix::ReportFormat::Csv)
+        );
+        assert!(command(&["licenses", "--format", "xml"]).is_err());
+        assert_eq!(command(&["updates"]).unwrap(), Command::Updates);
+        assert_eq!(
+            command(&["updates", "--commit-message", "ripgrep"]).unwrap(),
+            Command::CommitMessage(String::from("ripgrep"))
+        );
+        assert!(command(&["build"]).is_err());
+    }
+}
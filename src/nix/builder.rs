@@ -0,0 +1,242 @@
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+
+use super::{
+    native::NativeBinaries,
+    string::{attr_name, quote},
+};
+
+/// The nixpkgs function used to build a [`super::NixPackage`].
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+pub enum NixBuilder {
+    /// `stdenv.mkDerivation`
+    #[default]
+    MkDerivation,
+    /// `vscode-utils.buildVscodeExtension`, propagating the `vscode-extensions` named by
+    /// the unique ids in `dependencies`, and patching the `native` binaries it ships
+    VSCodeExtension {
+        publisher: String,
+        name: String,
+        dependencies: Vec<String>,
+        #[serde(default)]
+        native: NativeBinaries,
+    },
+    /// `rustPlatform.buildRustPackage`
+    RustPackage { cargo_lock: CargoLock },
+    /// `buildPythonPackage`, from a python package set
+    PythonPackage { wheel: bool },
+    /// `stdenv.mkDerivation` copying an npm tarball to `lib/node_modules/<package_name>`
+    NodePackage { package_name: String },
+    /// `buildFirefoxXpiAddon`, as used by NUR's firefox-addons
+    FirefoxXpiAddon { addon_id: String },
+    /// `stdenv.mkDerivation` laying out a plugin directory for `jetbrains.plugins.addPlugins`,
+    /// from a zip or, when `jar` is set, a single jar named `jar`
+    JetBrainsPlugin { jar: Option<String> },
+    /// `vimUtils.buildVimPlugin`, with the `vimPlugins` it depends on
+    VimPlugin { dependencies: Vec<String> },
+    /// `stdenv.mkDerivation` installing the executables of a prebuilt release asset to `bin`
+    PrebuiltBinary { format: AssetFormat },
+}
+
+/// What a prebuilt release asset is, going by its file name.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum AssetFormat {
+    Tarball,
+    Zip,
+    /// A bare executable, installed as `bin/<pname>`
+    Executable,
+}
+
+impl AssetFormat {
+    pub fn of(file_name: &str) -> Self {
+        let file_name = file_name.to_lowercase();
+        if file_name.ends_with(".zip") {
+            AssetFormat::Zip
+        } else if [
+            ".tar", ".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.bz2", ".tar.zst",
+        ]
+        .iter()
+        .any(|extension| file_name.ends_with(extension))
+        {
+            AssetFormat::Tarball
+        } else {
+            AssetFormat::Executable
+        }
+    }
+}
+
+/// The `cargoLock` attribute of `rustPlatform.buildRustPackage`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct CargoLock {
+    /// Contents of the crate's `Cargo.lock`, written next to the expression.
+    pub lock_file: String,
+    /// `name-version` of every git dependency, with the hash of its repository.
+    pub output_hashes: BTreeMap<String, String>,
+}
+
+/// Attribute path of an extension under `vscode-extensions`, which nixpkgs keys by
+/// lowercased publisher and name.
+pub(super) fn vscode_extension_attr(publisher: &str, name: &str) -> String {
+    format!(
+        "{}.{}",
+        attr_name(&publisher.to_lowercase()),
+        attr_name(&name.to_lowercase())
+    )
+}
+
+impl NixBuilder {
+    /// Arguments the builder is taken from in a `callPackage`-able expression.
+    pub fn function_args(&self) -> &'static [&'static str] {
+        match self {
+            NixBuilder::MkDerivation | NixBuilder::NodePackage { .. } => &["stdenv"],
+            NixBuilder::VSCodeExtension { dependencies, .. } if dependencies.is_empty() => {
+                &["vscode-utils"]
+            }
+            NixBuilder::VSCodeExtension { .. } => &["vscode-extensions", "vscode-utils"],
+            NixBuilder::RustPackage { .. } => &["rustPlatform"],
+            NixBuilder::PythonPackage { .. } => &["buildPythonPackage"],
+            NixBuilder::FirefoxXpiAddon { .. } => &["buildFirefoxXpiAddon"],
+            NixBuilder::JetBrainsPlugin { jar: None } => &["stdenv", "unzip"],
+            NixBuilder::JetBrainsPlugin { jar: Some(_) } => &["stdenv"],
+            NixBuilder::VimPlugin { dependencies } if dependencies.is_empty() => &["vimUtils"],
+            NixBuilder::VimPlugin { .. } => &["vimPlugins", "vimUtils"],
+            NixBuilder::PrebuiltBinary {
+                format: AssetFormat::Zip,
+            } => &["stdenv", "unzip"],
+            NixBuilder::PrebuiltBinary { .. } => &["stdenv"],
+        }
+    }
+
+    /// The native binaries the package ships, when they need patching.
+    pub fn native_binaries(&self) -> Option<&NativeBinaries> {
+        match self {
+            NixBuilder::VSCodeExtension { native, .. } if !native.is_empty() => Some(native),
+            _ => None,
+        }
+    }
+
+    /// The function applied to the derivation's attribute set.
+    pub fn function(&self) -> &'static str {
+        match self {
+            NixBuilder::MkDerivation
+            | NixBuilder::NodePackage { .. }
+            | NixBuilder::JetBrainsPlugin { .. }
+            | NixBuilder::PrebuiltBinary { .. } => "stdenv.mkDerivation",
+            NixBuilder::VSCodeExtension { .. } => "vscode-utils.buildVscodeExtension",
+            NixBuilder::RustPackage { .. } => "rustPlatform.buildRustPackage",
+            NixBuilder::PythonPackage { .. } => "buildPythonPackage",
+            NixBuilder::FirefoxXpiAddon { .. } => "buildFirefoxXpiAddon",
+            NixBuilder::VimPlugin { .. } => "vimUtils.buildVimPlugin",
+        }
+    }
+
+    /// Whether the builder takes `url` and `sha256` itself rather than a `src` from a fetcher.
+    pub fn fetches_src(&self) -> bool {
+        matches!(self, NixBuilder::FirefoxXpiAddon { .. })
+    }
+
+    /// Attributes specific to the builder, rendered at the top level of the derivation.
+    pub fn attrs(&self) -> String {
+        match self {
+            NixBuilder::MkDerivation => String::new(),
+            NixBuilder::VSCodeExtension {
+                publisher,
+                name,
+                dependencies,
+                native,
+            } => {
+                let mut attrs = format!(
+                    "  name = \"${{pname}}-${{version}}\";\n  vscodeExtPublisher = {};\n  vscodeExtName = {};\n  vscodeExtUniqueId = {};\n",
+                    quote(publisher),
+                    quote(name),
+                    quote(&format!("{}.{}", publisher, name))
+                );
+                // vscode-with-extensions and home-manager gather extensions with buildEnv,
+                // which follows propagatedUserEnvPkgs
+                let dependencies: Vec<String> = dependencies
+                    .iter()
+                    .filter_map(|unique_id| unique_id.split_once('.'))
+                    .map(|(publisher, name)| {
+                        format!("vscode-extensions.{}", vscode_extension_attr(publisher, name))
+                    })
+                    .collect();
+                if !dependencies.is_empty() {
+                    attrs.push_str(&format!(
+                        "\n  propagatedUserEnvPkgs = [ {} ];\n",
+                        dependencies.join(" ")
+                    ));
+                }
+                if !native.is_empty() {
+                    attrs.push_str(&format!("\n{}", native.attrs()));
+                }
+                attrs
+            }
+            NixBuilder::RustPackage { cargo_lock } => {
+                let mut attrs = String::from("  cargoLock = {\n    lockFile = ./Cargo.lock;\n");
+                if !cargo_lock.output_hashes.is_empty() {
+                    attrs.push_str("    outputHashes = {\n");
+                    for (dependency, hash) in &cargo_lock.output_hashes {
+                        attrs.push_str(&format!(
+                            "      {} = {};\n",
+                            quote(dependency),
+                            quote(hash)
+                        ));
+                    }
+                    attrs.push_str("    };\n");
+                }
+                attrs.push_str("  };\n");
+                attrs
+            }
+            NixBuilder::PythonPackage { wheel: true } => String::from("  format = \"wheel\";\n"),
+            NixBuilder::PythonPackage { wheel: false } => String::from("  format = \"setuptools\";\n"),
+            NixBuilder::NodePackage { package_name } => {
+                let install_dir = format!("$out/lib/node_modules/{}", package_name);
+                format!(
+                    "  dontBuild = true;\n\n  installPhase = ''\n    runHook preInstall\n    mkdir -p \"{dir}\"\n    cp -r . \"{dir}\"\n    runHook postInstall\n  '';\n",
+                    dir = install_dir.replace("''", "'''").replace("${", "''${")
+                )
+            }
+            NixBuilder::FirefoxXpiAddon { addon_id } => {
+                format!("  addonId = {};\n", quote(addon_id))
+            }
+            NixBuilder::JetBrainsPlugin { jar: None } => String::from(
+                "  nativeBuildInputs = [ unzip ];\n\n  installPhase = ''\n    runHook preInstall\n    mkdir -p $out\n    cp -r . $out\n    runHook postInstall\n  '';\n",
+            ),
+            NixBuilder::JetBrainsPlugin { jar: Some(jar) } => format!(
+                "  dontUnpack = true;\n\n  installPhase = ''\n    runHook preInstall\n    install -Dm644 $src \"$out/lib/{}\"\n    runHook postInstall\n  '';\n",
+                jar.replace("''", "'''").replace("${", "''${")
+            ),
+            NixBuilder::VimPlugin { dependencies } if dependencies.is_empty() => String::new(),
+            NixBuilder::VimPlugin { dependencies } => format!(
+                "  dependencies = [ {} ];\n",
+                dependencies
+                    .iter()
+                    .map(|dependency| format!("vimPlugins.{}", attr_name(dependency)))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
+            NixBuilder::PrebuiltBinary {
+                format: AssetFormat::Executable,
+            } => String::from(
+                "  dontUnpack = true;\n\n  installPhase = ''\n    runHook preInstall\n    install -Dm755 $src \"$out/bin/${pname}\"\n    runHook postInstall\n  '';\n",
+            ),
+            NixBuilder::PrebuiltBinary { format } => {
+                let mut attrs = String::new();
+                if *format == AssetFormat::Zip {
+                    attrs.push_str("  nativeBuildInputs = [ unzip ];\n\n");
+                }
+                attrs.push_str("  sourceRoot = \".\";\n\n  installPhase = ''\n    runHook preInstall\n    find . -type f -perm -u+x -exec install -Dm755 -t $out/bin {} +\n    runHook postInstall\n  '';\n");
+                attrs
+            }
+        }
+    }
+
+    /// Extra files that have to be written next to the expression, as `(file name, contents)`.
+    pub fn extra_files(&self) -> Vec<(&'static str, &str)> {
+        match self {
+            NixBuilder::RustPackage { cargo_lock } => vec![("Cargo.lock", &cargo_lock.lock_file)],
+            _ => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use regex::Regex;
+
+use std::cmp::Ordering;
+
+/// The notes of one version in a changelog.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangelogEntry {
+    pub version: String,
+    pub notes: String,
+}
+
+/// The version a changelog heading is about, as in `## [1.2.0] - 2024-01-31`,
+/// `## v1.2.0 (2024-01-31)`, `### Version 1.2.0` or `## 1.2.0`.
+fn heading_version(heading: &str) -> Option<String> {
+    let version = Regex::new(r"\bv?(\d+(?:\.\d+)+(?:-[0-9A-Za-z][0-9A-Za-z.]*)?)").unwrap();
+    version
+        .captures(heading)
+        .map(|captures| captures[1].to_string())
+}
+
+/// The level and text of the heading at `lines[i]`, written with `#`s or underlined with
+/// `=`s or `-`s on the next line.
+fn heading(lines: &[&str], i: usize, atx: &Regex) -> Option<(usize, String)> {
+    if let Some(captures) = atx.captures(lines[i]) {
+        return Some((captures[1].len(), captures[2].to_string()));
+    }
+
+    let text = lines[i].trim();
+    let underline = lines.get(i + 1)?.trim();
+    if text.is_empty() || text.starts_with(['-', '*', '>', '|']) || underline.is_empty() {
+        None
+    } else if underline.chars().all(|c| c == '=') {
+        Some((1, text.to_string()))
+    } else if underline.chars().all(|c| c == '-') {
+        Some((2, text.to_string()))
+    } else {
+        None
+    }
+}
+
+/// Every version of a Keep a Changelog style changelog, as they are listed. Versions are
+/// headings with a version number in them; the first one sets their level, so deeper
+/// headings like `### Added` stay in the notes and shallower ones, or headings like
+/// `## Unreleased`, end them.
+pub fn changelog_entries(changelog: &str) -> Vec<ChangelogEntry> {
+    let lines: Vec<&str> = changelog.lines().collect();
+    let mut entries = Vec::new();
+    let mut current: Option<(String, Vec<&str>)> = None;
+    let mut version_level = None;
+    let mut fenced = false;
+
+    let atx = Regex::new(r"^ {0,3}(#{1,6})\s+(.*?)(?:\s+#+)?\s*$").unwrap();
+    // link definitions, like the compare links at the end of Keep a Changelog files
+    let link = Regex::new(r"^ {0,3}\[[^\]]+\]:\s").unwrap();
+    let mut finish = |current: Option<(String, Vec<&str>)>| {
+        if let Some((version, notes)) = current {
+            let notes: Vec<&str> = notes
+                .into_iter()
+                .filter(|line| !link.is_match(line))
+                .collect();
+            entries.push(ChangelogEntry {
+                version,
+                notes: notes.join("\n").trim().to_string(),
+            });
+        }
+    };
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+            fenced = !fenced;
+        }
+        let found = if fenced {
+            None
+        } else {
+            heading(&lines, i, &atx)
+        };
+        match found {
+            Some((level, text))
+                if version_level.map_or(true, |version_level| level <= version_level) =>
+            {
+                finish(current.take());
+                if let Some(version) = heading_version(&text) {
+                    version_level = Some(level);
+                    current = Some((version, Vec::new()));
+                }
+                // skip the underline of the heading
+                if !line.trim_start().starts_with('#') {
+                    i += 1;
+                }
+            }
+            _ => {
+                if let Some((_, notes)) = current.as_mut() {
+                    notes.push(line);
+                }
+            }
+        }
+        i += 1;
+    }
+    finish(current);
+    entries
+}
+
+/// The numbers of a version and its pre-release, `v1.2.0-beta.1` being `[1, 2, 0]` and
+/// `beta.1`.
+fn version_parts(version: &str) -> Option<(Vec<u64>, Option<&str>)> {
+    let version = version.trim_start_matches('v');
+    let (numbers, pre) = match version.split_once('-') {
+        Some((numbers, pre)) => (numbers, Some(pre)),
+        None => (version, None),
+    };
+    let numbers = numbers
+        .split('.')
+        .map(|number| number.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    Some((numbers, pre))
+}
+
+/// Order versions by their numbers, missing numbers counting as 0, then pre-releases
+/// before releases.
+fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    let (a_numbers, a_pre) = version_parts(a)?;
+    let (b_numbers, b_pre) = version_parts(b)?;
+    let len = a_numbers.len().max(b_numbers.len());
+    let number = |numbers: &[u64], i: usize| numbers.get(i).copied().unwrap_or(0);
+    let numbers = (0..len)
+        .map(|i| number(&a_numbers, i).cmp(&number(&b_numbers, i)))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal);
+    Some(numbers.then(match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => a.cmp(b),
+    }))
+}
+
+/// The notes of `to`, and of every version after `from` up to it, from a changelog.
+/// Without `from`, or when it is not a version, only the notes of `to` are taken; without
+/// notes for `to` there are none.
+pub fn notes_between(changelog: &str, from: Option<&str>, to: &str) -> Option<String> {
+    let entries = changelog_entries(changelog);
+    let is = |version: &str, other: &str| compare_versions(version, other) == Some(Ordering::Equal);
+    if !entries.iter().any(|entry| is(&entry.version, to)) {
+        return None;
+    }
+
+    let since = from.filter(|from| version_parts(from).is_some());
+    let entries: Vec<&ChangelogEntry> = entries
+        .iter()
+        .filter(|entry| match since {
+            Some(from) => {
+                compare_versions(&entry.version, from) == Some(Ordering::Greater)
+                    && compare_versions(&entry.version, to).is_some_and(Ordering::is_le)
+            }
+            None => is(&entry.version, to),
+        })
+        .collect();
+
+    match entries.as_slice() {
+        // going down, or staying at the same version, brings no notes
+        [] => None,
+        [entry] => Some(entry.notes.clone()).filter(|notes| !notes.is_empty()),
+        entries => Some(
+            entries
+                .iter()
+                .map(|entry| format!("### {}\n\n{}", entry.version, entry.notes))
+                .map(|section| section.trim_end().to_string())
+                .collect::<Vec<String>>()
+                .join("\n\n"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEEP_A_CHANGELOG: &str = r#"# Changelog
+
+All notable changes to this project will be documented in this file.
+
+## [Unreleased]
+
+- Something in progress
+
+## [1.2.0] - 2024-03-02
+
+### Added
+
+- `alt+up` moves lines up
+
+```md
+## 9.9.9 is not a heading in a code block
+```
+
+## [1.1.1] - 2024-02-01
+
+### Fixed
+
+- Crash on empty files
+
+## [1.1.0] - 2024-01-05
+
+- First release with keybindings
+
+[1.2.0]: https://github.com/o/r/compare/v1.1.1...v1.2.0
+"#;
+
+    #[test]
+    fn test_changelog_entries() {
+        let entries = changelog_entries(KEEP_A_CHANGELOG);
+        let versions: Vec<&str> = entries.iter().map(|e| e.version.as_str()).collect();
+        assert_eq!(versions, ["1.2.0", "1.1.1", "1.1.0"]);
+        assert!(entries[0]
+            .notes
+            .starts_with("### Added\n\n- `alt+up` moves lines up"));
+        assert!(entries[0].notes.contains("## 9.9.9 is not a heading"));
+        assert_eq!(entries[1].notes, "### Fixed\n\n- Crash on empty files");
+
+        let setext =
+            "0.3.0\n=====\n\n* Faster\n\nv0.2.6 (2023-12-01)\n===================\n\n* Initial\n";
+        assert_eq!(
+            changelog_entries(setext),
+            vec![
+                ChangelogEntry {
+                    version: String::from("0.3.0"),
+                    notes: String::from("* Faster"),
+                },
+                ChangelogEntry {
+                    version: String::from("0.2.6"),
+                    notes: String::from("* Initial"),
+                },
+            ]
+        );
+
+        let grouped = "# 2.x\n\n### Version 2.0.1\n\nFix\n\n### Version 2.0.0\n\nBreak\n\n# 1.x\n\n### Version 1.0.0\n\nInit\n";
+        let versions: Vec<String> = changelog_entries(grouped)
+            .into_iter()
+            .map(|e| e.version)
+            .collect();
+        assert_eq!(versions, ["2.0.1", "2.0.0", "1.0.0"]);
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.2.0", "v1.2"), Some(Ordering::Equal));
+        assert_eq!(compare_versions("1.10.0", "1.9.9"), Some(Ordering::Greater));
+        assert_eq!(
+            compare_versions("1.2.0-beta.1", "1.2.0"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(compare_versions("unstable-2024-01-01", "1.0.0"), None);
+    }
+
+    #[test]
+    fn test_notes_between() {
+        assert_eq!(
+            notes_between(KEEP_A_CHANGELOG, Some("1.1.0"), "1.2.0").unwrap(),
+            "### 1.2.0\n\n### Added\n\n- `alt+up` moves lines up\n\n```md\n## 9.9.9 is not a heading in a code block\n```\n\n### 1.1.1\n\n### Fixed\n\n- Crash on empty files"
+        );
+        assert_eq!(
+            notes_between(KEEP_A_CHANGELOG, Some("1.1.0"), "1.1.1").unwrap(),
+            "### Fixed\n\n- Crash on empty files"
+        );
+        assert_eq!(
+            notes_between(KEEP_A_CHANGELOG, None, "1.1.0").unwrap(),
+            "- First release with keybindings"
+        );
+        assert_eq!(
+            notes_between(KEEP_A_CHANGELOG, Some("unstable-2024-01-01"), "1.1.0").unwrap(),
+            "- First release with keybindings"
+        );
+        assert_eq!(
+            notes_between(KEEP_A_CHANGELOG, Some("1.1.0"), "1.3.0"),
+            None
+        );
+        assert_eq!(
+            notes_between(KEEP_A_CHANGELOG, Some("1.2.0"), "1.1.1"),
+            None
+        );
+        assert_eq!(
+            notes_between(KEEP_A_CHANGELOG, Some("1.1.1"), "1.1.1"),
+            None
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+
+use super::{
+    hash::is_sri,
+    string::{attr_name, quote},
+};
+
+/// The nixpkgs fetcher used to build the `src` of a [`super::NixPackage`].
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub enum NixFetcher {
+    /// `fetchurl { url = ...; sha256 = ...; }`, optionally with a store path `name`
+    /// (VSIX files need a `.zip` name to be unpacked).
+    FetchUrl { name: Option<String> },
+    /// `fetchCrate { inherit pname version; sha256 = ...; }`
+    FetchCrate { crate_name: String },
+    /// `fetchPypi { inherit pname version; sha256 = ...; }`, for an sdist
+    /// (with its archive `extension`) or a wheel.
+    FetchPypi {
+        pypi_name: String,
+        extension: Option<String>,
+        wheel: Option<WheelTag>,
+    },
+    /// `fetchFromGitHub { owner = ...; repo = ...; rev = ...; sha256 = ...; }`, with
+    /// `githubBase` for GitHub Enterprise Server and `private` for private repositories
+    FetchFromGitHub {
+        github_base: Option<String>,
+        owner: String,
+        repo: String,
+        rev: String,
+        private: bool,
+    },
+    /// `fetchFromGitLab { owner = ...; repo = ...; rev = ...; sha256 = ...; }`, with `domain`
+    /// for instances other than gitlab.com
+    FetchFromGitLab {
+        domain: Option<String>,
+        owner: String,
+        repo: String,
+        rev: String,
+    },
+    /// `fetchgit { url = ...; rev = ...; sha256 = ...; }`
+    FetchGit { rev: String },
+    /// A `fetchurl` for every system, picked by `stdenv.hostPlatform.system`, all with
+    /// the store path `name` if there is one
+    FetchUrlBySystem {
+        name: Option<String>,
+        sources: BTreeMap<String, SystemSource>,
+    },
+}
+
+/// The `fetchurl` of one system, for [`NixFetcher::FetchUrlBySystem`].
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct SystemSource {
+    pub url: String,
+    pub sha256: String,
+}
+
+/// The compatibility tag of a wheel, `{python}-{abi}-{platform}`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct WheelTag {
+    pub python: String,
+    pub abi: String,
+    pub platform: String,
+}
+
+/// The fetcher's hash attribute, `hash` for SRI hashes and `sha256` otherwise.
+fn hash_attr(hash: &str) -> String {
+    if is_sri(hash) {
+        format!("    hash = {};\n", quote(hash))
+    } else {
+        format!("    sha256 = {};\n", quote(hash))
+    }
+}
+
+impl Default for NixFetcher {
+    fn default() -> Self {
+        NixFetcher::FetchUrl { name: None }
+    }
+}
+
+impl NixFetcher {
+    /// Names of the arguments the fetcher is taken from in a `callPackage`-able expression.
+    pub fn function_args(&self) -> &'static [&'static str] {
+        match self {
+            NixFetcher::FetchUrl { .. } => &["fetchurl"],
+            NixFetcher::FetchCrate { .. } => &["fetchCrate"],
+            NixFetcher::FetchPypi { .. } => &["fetchPypi"],
+            NixFetcher::FetchFromGitHub { .. } => &["fetchFromGitHub"],
+            NixFetcher::FetchFromGitLab { .. } => &["fetchFromGitLab"],
+            NixFetcher::FetchGit { .. } => &["fetchgit"],
+            NixFetcher::FetchUrlBySystem { .. } => &["fetchurl", "stdenv"],
+        }
+    }
+
+    /// Render the fetcher call, indented for an attribute at the top level of a derivation.
+    pub fn to_nix(&self, pname: &str, url: &str, sha256: &str) -> String {
+        match self {
+            NixFetcher::FetchUrl { name } => {
+                let mut fetcher = String::from("fetchurl {\n");
+                if let Some(name) = name {
+                    fetcher.push_str(&format!("    name = {};\n", quote(name)));
+                }
+                fetcher.push_str(&format!("    url = {};\n", quote(url)));
+                fetcher.push_str(&hash_attr(sha256));
+                fetcher.push_str("  }");
+                fetcher
+            }
+            NixFetcher::FetchCrate { crate_name } => {
+                let mut fetcher = String::from("fetchCrate {\n    inherit pname version;\n");
+                if crate_name != pname {
+                    fetcher.push_str(&format!("    crateName = {};\n", quote(crate_name)));
+                }
+                fetcher.push_str(&hash_attr(sha256));
+                fetcher.push_str("  }");
+                fetcher
+            }
+            NixFetcher::FetchPypi {
+                pypi_name,
+                extension,
+                wheel,
+            } => {
+                let mut fetcher = if pypi_name != pname {
+                    format!(
+                        "fetchPypi {{\n    pname = {};\n    inherit version;\n",
+                        quote(pypi_name)
+                    )
+                } else {
+                    String::from("fetchPypi {\n    inherit pname version;\n")
+                };
+                if let Some(wheel) = wheel {
+                    fetcher.push_str("    format = \"wheel\";\n");
+                    fetcher.push_str(&format!("    dist = {};\n", quote(&wheel.python)));
+                    fetcher.push_str(&format!("    python = {};\n", quote(&wheel.python)));
+                    fetcher.push_str(&format!("    abi = {};\n", quote(&wheel.abi)));
+                    fetcher.push_str(&format!("    platform = {};\n", quote(&wheel.platform)));
+                } else if let Some(extension) = extension {
+                    fetcher.push_str(&format!("    extension = {};\n", quote(extension)));
+                }
+                fetcher.push_str(&hash_attr(sha256));
+                fetcher.push_str("  }");
+                fetcher
+            }
+            NixFetcher::FetchFromGitHub {
+                github_base,
+                owner,
+                repo,
+                rev,
+                private,
+            } => {
+                let mut fetcher = String::from("fetchFromGitHub {\n");
+                if let Some(github_base) = github_base {
+                    fetcher.push_str(&format!("    githubBase = {};\n", quote(github_base)));
+                }
+                fetcher.push_str(&format!("    owner = {};\n", quote(owner)));
+                fetcher.push_str(&format!("    repo = {};\n", quote(repo)));
+                fetcher.push_str(&format!("    rev = {};\n", quote(rev)));
+                if *private {
+                    fetcher.push_str("    private = true;\n");
+                }
+                fetcher.push_str(&hash_attr(sha256));
+                fetcher.push_str("  }");
+                fetcher
+            }
+            NixFetcher::FetchFromGitLab {
+                domain,
+                owner,
+                repo,
+                rev,
+            } => {
+                let mut fetcher = String::from("fetchFromGitLab {\n");
+                if let Some(domain) = domain {
+                    fetcher.push_str(&format!("    domain = {};\n", quote(domain)));
+                }
+                fetcher.push_str(&format!("    owner = {};\n", quote(owner)));
+                fetcher.push_str(&format!("    repo = {};\n", quote(repo)));
+                fetcher.push_str(&format!("    rev = {};\n", quote(rev)));
+                fetcher.push_str(&hash_attr(sha256));
+                fetcher.push_str("  }");
+                fetcher
+            }
+            NixFetcher::FetchGit { rev } => {
+                let mut fetcher = String::from("fetchgit {\n");
+                fetcher.push_str(&format!("    url = {};\n", quote(url)));
+                fetcher.push_str(&format!("    rev = {};\n", quote(rev)));
+                fetcher.push_str(&hash_attr(sha256));
+                fetcher.push_str("  }");
+                fetcher
+            }
+            NixFetcher::FetchUrlBySystem { name, sources } => {
+                let mut fetcher = String::from("{\n");
+                for (system, source) in sources {
+                    fetcher.push_str(&format!("    {} = fetchurl {{\n", attr_name(system)));
+                    if let Some(name) = name {
+                        fetcher.push_str(&format!("      name = {};\n", quote(name)));
+                    }
+                    fetcher.push_str(&format!(
+                        "      url = {};\n  {}    }};\n",
+                        quote(&source.url),
+                        hash_attr(&source.sha256)
+                    ));
+                }
+                fetcher.push_str(
+                    "  }.${stdenv.hostPlatform.system} or (throw \"Unsupported system: ${stdenv.hostPlatform.system}\")",
+                );
+                fetcher
+            }
+        }
+    }
+}
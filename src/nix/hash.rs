@@ -0,0 +1,112 @@
+/*
+    Nix's base32 encoding, taken from
+    https://github.com/NixOS/nix/blob/master/src/libutil/hash.cc
+*/
+
+const NIX_BASE32_CHARS: &[u8; 32] = b"0123456789abcdfghijklmnpqrsvwxyz";
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const SRI_ALGORITHMS: &[&str] = &["sha1", "sha256", "sha512"];
+
+/// Encode `bytes` the way `nix-hash --base32` prints them.
+pub fn to_nix_base32(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return String::new();
+    }
+    let len = (bytes.len() * 8 - 1) / 5 + 1;
+
+    (0..len)
+        .rev()
+        .map(|n| {
+            let b = n * 5;
+            let i = b / 8;
+            let j = b % 8;
+            let low = bytes[i] >> j;
+            let high = if i + 1 < bytes.len() {
+                bytes[i + 1].checked_shl(8 - j as u32).unwrap_or(0)
+            } else {
+                0
+            };
+            NIX_BASE32_CHARS[((low | high) & 0x1f) as usize] as char
+        })
+        .collect()
+}
+
+fn to_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_CHARS[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn hex_to_bytes(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+        })
+        .collect()
+}
+
+/// Convert a hex digest, like the ones package registries publish, to nix's base32.
+pub fn hex_to_nix_base32(hex: &str) -> Option<String> {
+    hex_to_bytes(hex).map(|b| to_nix_base32(&b))
+}
+
+/// Convert a hex digest to an SRI hash, eg. `sha1-<base64>`.
+pub fn hex_to_sri(algorithm: &str, hex: &str) -> Option<String> {
+    hex_to_bytes(hex).map(|b| format!("{}-{}", algorithm, to_base64(&b)))
+}
+
+/// Whether `hash` is an SRI hash (`sha512-<base64>`), which fetchers take as `hash`
+/// rather than `sha256`.
+pub fn is_sri(hash: &str) -> bool {
+    match hash.split_once('-') {
+        Some((algorithm, digest)) => SRI_ALGORITHMS.contains(&algorithm) && !digest.is_empty(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_to_nix_base32() {
+        // sha256 of the empty string
+        assert_eq!(
+            hex_to_nix_base32("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+                .unwrap(),
+            "0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73"
+        );
+        assert!(hex_to_nix_base32("e3b").is_none());
+        assert!(hex_to_nix_base32("zz").is_none());
+    }
+
+    #[test]
+    fn test_sri() {
+        // sha1 of the empty string
+        assert_eq!(
+            hex_to_sri("sha1", "da39a3ee5e6b4b0d3255bfef95601890afd80709").unwrap(),
+            "sha1-2jmj7l5rSw0yVb/vlWAYkK/YBwk="
+        );
+        assert!(is_sri("sha512-z4PhNX7vuL3xVChQ1m2AB9Yg5AULVxXcg/SpIdNs6c5H0NE8XYXysP+DGNKHfuwvY7kxvUdBeoGlODJ6+SfaPg=="));
+        assert!(!is_sri(
+            "0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73"
+        ));
+    }
+}
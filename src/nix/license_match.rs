@@ -0,0 +1,387 @@
+use serde::Deserialize;
+
+use std::{collections::BTreeSet, fmt};
+
+use super::license::NixLicense;
+
+/// How a license was recognised, from the most to the least certain.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LicenseConfidence {
+    /// An SPDX identifier or expression
+    Spdx,
+    /// The full name of a license, eg. `Apache License 2.0`
+    Name,
+    /// A common alias, eg. `GPLv3`
+    Alias,
+    /// A license file close enough to a known license text
+    Text,
+}
+
+impl fmt::Display for LicenseConfidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LicenseConfidence::Spdx => "SPDX identifier",
+            LicenseConfidence::Name => "license name",
+            LicenseConfidence::Alias => "alias",
+            LicenseConfidence::Text => "license text",
+        })
+    }
+}
+
+/// A nix license, and how sure the match is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LicenseMatch {
+    pub license: NixLicense,
+    pub confidence: LicenseConfidence,
+}
+
+/// Full names of licenses, as GitHub and most registries spell them.
+const LICENSE_NAMES: &[(&str, NixLicense)] = &[
+    ("Academic Free License v3.0", NixLicense::Afl3),
+    ("Apache License 2.0", NixLicense::Asl20),
+    ("Apache License, Version 2.0", NixLicense::Asl20),
+    ("Artistic License 2.0", NixLicense::Artistic2),
+    ("BSD 2-Clause \"Simplified\" License", NixLicense::Bsd2),
+    (
+        "BSD 3-Clause \"New\" or \"Revised\" License",
+        NixLicense::Bsd3,
+    ),
+    (
+        "BSD 4-Clause \"Original\" or \"Old\" License",
+        NixLicense::BsdOriginal,
+    ),
+    ("BSD Zero Clause License", NixLicense::Bsd0),
+    ("Boost Software License 1.0", NixLicense::Boost),
+    ("Creative Commons Zero v1.0 Universal", NixLicense::Cc0),
+    (
+        "Creative Commons Attribution 4.0 International",
+        NixLicense::CcBy40,
+    ),
+    (
+        "Creative Commons Attribution Share Alike 4.0 International",
+        NixLicense::CcBySa40,
+    ),
+    ("Eclipse Public License 1.0", NixLicense::Epl10),
+    ("Eclipse Public License 2.0", NixLicense::Epl20),
+    ("European Union Public License 1.1", NixLicense::Eupl11),
+    ("European Union Public License 1.2", NixLicense::Eupl12),
+    (
+        "GNU Affero General Public License v3.0",
+        NixLicense::Agpl3Only,
+    ),
+    ("GNU Free Documentation License v1.3", NixLicense::Fdl13Only),
+    ("GNU General Public License v2.0", NixLicense::Gpl2Only),
+    ("GNU General Public License v3.0", NixLicense::Gpl3Only),
+    (
+        "GNU Lesser General Public License v2.1",
+        NixLicense::Lgpl21Only,
+    ),
+    (
+        "GNU Lesser General Public License v3.0",
+        NixLicense::Lgpl3Only,
+    ),
+    ("ISC License", NixLicense::Isc),
+    ("MIT License", NixLicense::Mit),
+    ("Microsoft Public License", NixLicense::Mspl),
+    ("Mozilla Public License 2.0", NixLicense::Mpl20),
+    ("Open Software License 3.0", NixLicense::Osl3),
+    ("PostgreSQL License", NixLicense::Postgresql),
+    ("Python Software Foundation License", NixLicense::Psfl),
+    ("The Unlicense", NixLicense::Unlicense),
+    (
+        "University of Illinois/NCSA Open Source License",
+        NixLicense::Ncsa,
+    ),
+    ("Universal Permissive License v1.0", NixLicense::Upl),
+    ("Vim License", NixLicense::Vim),
+    ("zlib License", NixLicense::Zlib),
+    (
+        "Do What The F*ck You Want To Public License",
+        NixLicense::Wtfpl,
+    ),
+];
+
+/// Shorthands people write instead of a license's name or SPDX identifier.
+const LICENSE_ALIASES: &[(&str, NixLicense)] = &[
+    ("AGPLv3", NixLicense::Agpl3Only),
+    ("AGPLv3+", NixLicense::Agpl3Plus),
+    ("Apache", NixLicense::Asl20),
+    ("Apache 2", NixLicense::Asl20),
+    ("Apache2", NixLicense::Asl20),
+    ("ASL 2.0", NixLicense::Asl20),
+    ("BSD", NixLicense::Bsd3),
+    ("New BSD", NixLicense::Bsd3),
+    ("Modified BSD", NixLicense::Bsd3),
+    ("Simplified BSD", NixLicense::Bsd2),
+    ("FreeBSD", NixLicense::Bsd2),
+    ("CC0", NixLicense::Cc0),
+    ("Expat", NixLicense::Mit),
+    ("GPLv2", NixLicense::Gpl2Only),
+    ("GPL2", NixLicense::Gpl2Only),
+    ("GPL 2", NixLicense::Gpl2Only),
+    ("GPLv2+", NixLicense::Gpl2Plus),
+    ("GPLv3", NixLicense::Gpl3Only),
+    ("GPL3", NixLicense::Gpl3Only),
+    ("GPL 3", NixLicense::Gpl3Only),
+    ("GPLv3+", NixLicense::Gpl3Plus),
+    ("LGPLv2.1", NixLicense::Lgpl21Only),
+    ("LGPL2.1", NixLicense::Lgpl21Only),
+    ("LGPLv2.1+", NixLicense::Lgpl21Plus),
+    ("LGPLv3", NixLicense::Lgpl3Only),
+    ("LGPL3", NixLicense::Lgpl3Only),
+    ("LGPLv3+", NixLicense::Lgpl3Plus),
+    ("MPL2", NixLicense::Mpl20),
+    ("MPL 2", NixLicense::Mpl20),
+    ("Public Domain", NixLicense::PublicDomain),
+    ("Proprietary", NixLicense::Unfree),
+    ("Commercial", NixLicense::Unfree),
+    // npm's way of saying a package may not be used at all
+    ("UNLICENSED", NixLicense::Unfree),
+];
+
+/// Opening passages of licenses, which a license file has to contain nearly word for
+/// word to match.
+const LICENSE_TEXTS: &[(&str, NixLicense)] = &[
+    (include_str!("license_texts/0BSD.txt"), NixLicense::Bsd0),
+    (
+        include_str!("license_texts/AGPL-3.0.txt"),
+        NixLicense::Agpl3Only,
+    ),
+    (
+        include_str!("license_texts/Apache-2.0.txt"),
+        NixLicense::Asl20,
+    ),
+    (
+        include_str!("license_texts/BSD-2-Clause.txt"),
+        NixLicense::Bsd2,
+    ),
+    (
+        include_str!("license_texts/BSD-3-Clause.txt"),
+        NixLicense::Bsd3,
+    ),
+    (include_str!("license_texts/BSL-1.0.txt"), NixLicense::Boost),
+    (
+        include_str!("license_texts/GPL-2.0.txt"),
+        NixLicense::Gpl2Only,
+    ),
+    (
+        include_str!("license_texts/GPL-3.0.txt"),
+        NixLicense::Gpl3Only,
+    ),
+    (include_str!("license_texts/ISC.txt"), NixLicense::Isc),
+    (
+        include_str!("license_texts/LGPL-2.1.txt"),
+        NixLicense::Lgpl21Only,
+    ),
+    (
+        include_str!("license_texts/LGPL-3.0.txt"),
+        NixLicense::Lgpl3Only,
+    ),
+    (include_str!("license_texts/MIT.txt"), NixLicense::Mit),
+    (include_str!("license_texts/MPL-2.0.txt"), NixLicense::Mpl20),
+    (
+        include_str!("license_texts/Unlicense.txt"),
+        NixLicense::Unlicense,
+    ),
+    (include_str!("license_texts/WTFPL.txt"), NixLicense::Wtfpl),
+    (include_str!("license_texts/Zlib.txt"), NixLicense::Zlib),
+];
+
+/// Share of a license text's word pairs a file needs to match it.
+const TEXT_THRESHOLD: f64 = 0.9;
+
+/// Headings of proprietary terms, like those of Microsoft's own marketplace extensions.
+const PROPRIETARY_TERMS: &[&str] = &[
+    "MICROSOFT SOFTWARE LICENSE TERMS",
+    "MICROSOFT PRE-RELEASE SOFTWARE LICENSE TERMS",
+];
+
+/// Lowercased words of a license name, without punctuation, `the`, `license` or the `v`
+/// of version numbers, so `The MIT License` and `MIT license` are the same.
+fn normalize_name(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !(c.is_alphanumeric() || c == '.' || c == '+'))
+        .filter(|word| !word.is_empty())
+        .filter(|word| !matches!(*word, "the" | "license" | "licence" | "version"))
+        .map(|word| match word.strip_prefix('v') {
+            Some(number) if number.starts_with(|c: char| c.is_ascii_digit()) => number,
+            _ => word,
+        })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Pairs of consecutive lowercased words, ignoring punctuation and layout.
+fn word_pairs(text: &str) -> BTreeSet<(String, String)> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    words
+        .windows(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect()
+}
+
+impl NixLicense {
+    /// Match a license by its SPDX identifier, full name or a common alias.
+    pub fn from_name(name: &str) -> Option<LicenseMatch> {
+        if let Some(license) = NixLicense::from_str(name.trim()) {
+            return Some(LicenseMatch {
+                license: *license,
+                confidence: LicenseConfidence::Spdx,
+            });
+        }
+        let name = normalize_name(name);
+        let find = |names: &[(&str, NixLicense)], confidence| {
+            names
+                .iter()
+                .find(|(known, _)| normalize_name(known) == name)
+                .map(|(_, license)| LicenseMatch {
+                    license: *license,
+                    confidence,
+                })
+        };
+        find(LICENSE_NAMES, LicenseConfidence::Name)
+            .or_else(|| find(LICENSE_ALIASES, LicenseConfidence::Alias))
+    }
+
+    /// Match a license file against the bundled license texts. When several match, as
+    /// BSD-2-Clause does within a BSD-3-Clause file, the longest text wins. Proprietary
+    /// terms are unfree.
+    pub fn from_text(text: &str) -> Option<LicenseMatch> {
+        let upper = text.to_uppercase();
+        if PROPRIETARY_TERMS.iter().any(|terms| upper.contains(terms)) {
+            return Some(LicenseMatch {
+                license: NixLicense::Unfree,
+                confidence: LicenseConfidence::Text,
+            });
+        }
+        let pairs = word_pairs(text);
+        LICENSE_TEXTS
+            .iter()
+            .filter_map(|(known, license)| {
+                let known = word_pairs(known);
+                let found = known.iter().filter(|pair| pairs.contains(pair)).count();
+                let score = found as f64 / known.len() as f64;
+                (score >= TEXT_THRESHOLD).then_some((score, known.len(), license))
+            })
+            .max_by(|(a_score, a_len, _), (b_score, b_len, _)| {
+                // scores this close are the same text with small edits
+                if (a_score - b_score).abs() < 0.02 {
+                    a_len.cmp(b_len)
+                } else {
+                    a_score.total_cmp(b_score)
+                }
+            })
+            .map(|(_, _, license)| LicenseMatch {
+                license: *license,
+                confidence: LicenseConfidence::Text,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn license(name: &str) -> Option<(NixLicense, LicenseConfidence)> {
+        NixLicense::from_name(name).map(|m| (m.license, m.confidence))
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(
+            license("Apache-2.0"),
+            Some((NixLicense::Asl20, LicenseConfidence::Spdx))
+        );
+        assert_eq!(
+            license("MIT License"),
+            Some((NixLicense::Mit, LicenseConfidence::Name))
+        );
+        assert_eq!(
+            license("The MIT license"),
+            Some((NixLicense::Mit, LicenseConfidence::Name))
+        );
+        assert_eq!(
+            license("Apache License 2.0"),
+            Some((NixLicense::Asl20, LicenseConfidence::Name))
+        );
+        assert_eq!(
+            license("GNU General Public License v3.0"),
+            Some((NixLicense::Gpl3Only, LicenseConfidence::Name))
+        );
+        assert_eq!(
+            license("GPLv3"),
+            Some((NixLicense::Gpl3Only, LicenseConfidence::Alias))
+        );
+        assert_eq!(
+            license("GPLv3+"),
+            Some((NixLicense::Gpl3Plus, LicenseConfidence::Alias))
+        );
+        assert_eq!(
+            license("GPL v2"),
+            Some((NixLicense::Gpl2Only, LicenseConfidence::Alias))
+        );
+        assert_eq!(
+            license("BSD"),
+            Some((NixLicense::Bsd3, LicenseConfidence::Alias))
+        );
+        assert_eq!(
+            license("UNLICENSED"),
+            Some((NixLicense::Unfree, LicenseConfidence::Alias))
+        );
+        assert_eq!(license("Other"), None);
+    }
+
+    #[test]
+    fn test_no_deprecated_licenses() {
+        for (_, license) in LICENSE_NAMES
+            .iter()
+            .chain(LICENSE_ALIASES)
+            .chain(LICENSE_TEXTS)
+        {
+            assert!(!license.is_deprecated(), "{:?} is deprecated", license);
+        }
+    }
+
+    #[test]
+    fn test_from_text() {
+        let text = |text: &str| NixLicense::from_text(text).map(|m| m.license);
+
+        let mit = format!(
+            "MIT License\n\nCopyright (c) 2021 Jane Doe\n\n{}",
+            include_str!("license_texts/MIT.txt")
+        );
+        assert_eq!(text(&mit), Some(NixLicense::Mit));
+
+        // reflowed, as READMEs and package.json files often have them
+        let isc = include_str!("license_texts/ISC.txt").replace('\n', " ");
+        assert_eq!(text(&isc), Some(NixLicense::Isc));
+
+        let bsd3 = format!(
+            "Copyright (c) 2018, The Authors\nAll rights reserved.\n\n{}",
+            include_str!("license_texts/BSD-3-Clause.txt")
+        );
+        assert_eq!(text(&bsd3), Some(NixLicense::Bsd3));
+        assert_eq!(
+            text(include_str!("license_texts/BSD-2-Clause.txt")),
+            Some(NixLicense::Bsd2)
+        );
+        assert_eq!(
+            text(include_str!("license_texts/0BSD.txt")),
+            Some(NixLicense::Bsd0)
+        );
+
+        for (known, license) in LICENSE_TEXTS {
+            assert_eq!(text(known), Some(*license));
+        }
+
+        assert_eq!(
+            text("MICROSOFT SOFTWARE LICENSE TERMS\n\nMICROSOFT C/C++ EXTENSION FOR VISUAL STUDIO CODE"),
+            Some(NixLicense::Unfree)
+        );
+        assert_eq!(text("All rights reserved. Do not redistribute."), None);
+    }
+}
@@ -0,0 +1,245 @@
+use serde::Serialize;
+
+use super::{license::NixLicense, package::NixPackage};
+
+/// How `nxpkgr licenses` prints its report.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl ReportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "table" => Some(ReportFormat::Table),
+            "json" => Some(ReportFormat::Json),
+            "csv" => Some(ReportFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// One license of one package; packages without a recognised license get a row
+/// without one.
+#[derive(Debug, Serialize, PartialEq)]
+struct LicenseRow<'a> {
+    package: &'a str,
+    version: &'a str,
+    license: Option<&'static str>,
+    spdx_id: Option<&'static str>,
+    full_name: Option<&'static str>,
+    free: Option<bool>,
+    redistributable: Option<bool>,
+    osi_approved: Option<bool>,
+    deprecated: Option<bool>,
+    confidence: Option<String>,
+}
+
+const COLUMNS: [&str; 9] = [
+    "PACKAGE",
+    "VERSION",
+    "LICENSE",
+    "SPDX",
+    "FREE",
+    "REDISTRIBUTABLE",
+    "OSI APPROVED",
+    "DEPRECATED",
+    "CONFIDENCE",
+];
+
+fn rows(packages: &[NixPackage]) -> Vec<LicenseRow<'_>> {
+    let mut rows = Vec::new();
+    for package in packages {
+        let confidence = package
+            .meta
+            .license_confidence
+            .map(|confidence| confidence.to_string());
+        let row = |license: Option<NixLicense>| LicenseRow {
+            package: &package.name,
+            version: &package.version,
+            license: license.map(NixLicense::nix_attr),
+            spdx_id: license.and_then(NixLicense::spdx_id),
+            full_name: license.map(NixLicense::full_name),
+            free: license.map(NixLicense::is_free),
+            redistributable: license.map(NixLicense::is_redistributable),
+            osi_approved: license.map(NixLicense::is_osi_approved),
+            deprecated: license.map(NixLicense::is_deprecated),
+            confidence: confidence.clone(),
+        };
+        match package.meta.license.as_deref() {
+            Some(licenses) if !licenses.is_empty() => {
+                rows.extend(licenses.iter().map(|license| row(Some(*license))))
+            }
+            _ => rows.push(row(None)),
+        }
+    }
+    rows
+}
+
+impl LicenseRow<'_> {
+    fn cells(&self) -> [String; 9] {
+        let text = |value: Option<&str>| value.unwrap_or("").to_string();
+        let flag = |value: Option<bool>| match value {
+            Some(true) => String::from("yes"),
+            Some(false) => String::from("no"),
+            None => String::new(),
+        };
+        [
+            self.package.to_string(),
+            self.version.to_string(),
+            text(self.license),
+            text(self.spdx_id),
+            flag(self.free),
+            flag(self.redistributable),
+            flag(self.osi_approved),
+            flag(self.deprecated),
+            text(self.confidence.as_deref()),
+        ]
+    }
+}
+
+fn table(rows: &[LicenseRow<'_>]) -> String {
+    let cells: Vec<[String; 9]> = rows.iter().map(LicenseRow::cells).collect();
+    let mut widths = COLUMNS.map(str::len);
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |row: &[String]| {
+        let padded: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        format!("{}\n", padded.join("  ").trim_end())
+    };
+    let mut out = line(&COLUMNS.map(String::from));
+    for row in &cells {
+        out.push_str(&line(row));
+    }
+    out
+}
+
+/// Quote a CSV field when it holds a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn csv(rows: &[LicenseRow<'_>]) -> String {
+    let header: Vec<String> = COLUMNS
+        .iter()
+        .map(|column| column.to_lowercase().replace(' ', "_"))
+        .collect();
+    let mut out = format!("{}\n", header.join(","));
+    for row in rows {
+        let fields: Vec<String> = row.cells().iter().map(|cell| csv_field(cell)).collect();
+        out.push_str(&format!("{}\n", fields.join(",")));
+    }
+    out
+}
+
+/// Render every package with its licenses and what nixpkgs and SPDX know about them,
+/// for license reviews.
+pub fn license_report(packages: &[NixPackage], format: ReportFormat) -> String {
+    let rows = rows(packages);
+    match format {
+        ReportFormat::Table => table(&rows),
+        ReportFormat::Csv => csv(&rows),
+        ReportFormat::Json => {
+            let mut json = serde_json::to_string_pretty(&rows).unwrap();
+            json.push('\n');
+            json
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::{LicenseConfidence, NixBuilder, NixFetcher, NixPackageMeta};
+
+    fn package(
+        name: &str,
+        license: Option<Vec<NixLicense>>,
+        license_confidence: Option<LicenseConfidence>,
+    ) -> NixPackage {
+        NixPackage {
+            name: String::from(name),
+            pname: String::from(name),
+            src: String::new(),
+            version: String::from("1.0.0"),
+            sha256: String::new(),
+            meta: NixPackageMeta {
+                license,
+                license_confidence,
+                ..Default::default()
+            },
+            fetcher: NixFetcher::FetchUrl { name: None },
+            builder: NixBuilder::MkDerivation,
+        }
+    }
+
+    fn packages() -> Vec<NixPackage> {
+        vec![
+            package(
+                "ripgrep",
+                Some(vec![NixLicense::Unlicense, NixLicense::Mit]),
+                Some(LicenseConfidence::Spdx),
+            ),
+            package(
+                "cpptools",
+                Some(vec![NixLicense::Unfree]),
+                Some(LicenseConfidence::Text),
+            ),
+            package("mystery", None, None),
+        ]
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(
+            license_report(&packages(), ReportFormat::Table),
+            "\
+PACKAGE   VERSION  LICENSE    SPDX       FREE  REDISTRIBUTABLE  OSI APPROVED  DEPRECATED  CONFIDENCE
+ripgrep   1.0.0    unlicense  Unlicense  yes   yes              yes           no          SPDX identifier
+ripgrep   1.0.0    mit        MIT        yes   yes              yes           no          SPDX identifier
+cpptools  1.0.0    unfree                no    no               no            no          license text
+mystery   1.0.0
+"
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(
+            license_report(&packages(), ReportFormat::Csv),
+            "\
+package,version,license,spdx,free,redistributable,osi_approved,deprecated,confidence
+ripgrep,1.0.0,unlicense,Unlicense,yes,yes,yes,no,SPDX identifier
+ripgrep,1.0.0,mit,MIT,yes,yes,yes,no,SPDX identifier
+cpptools,1.0.0,unfree,,no,no,no,no,license text
+mystery,1.0.0,,,,,,,
+"
+        );
+        assert_eq!(csv_field("a, \"b\""), "\"a, \"\"b\"\"\"");
+    }
+
+    #[test]
+    fn test_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&license_report(&packages(), ReportFormat::Json)).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 4);
+        assert_eq!(json[1]["license"], "mit");
+        assert_eq!(json[1]["full_name"], "MIT License");
+        assert_eq!(json[2]["free"], false);
+        assert_eq!(json[3]["license"], serde_json::Value::Null);
+    }
+}
@@ -1,7 +1,26 @@
+mod builder;
+mod changelog;
+mod fetcher;
+mod hash;
 mod license;
+mod license_match;
+mod license_report;
+mod native;
+mod overlay;
 mod package;
 mod platforms;
+mod spdx;
+mod string;
+mod update;
 
-pub use license::NixLicense;
-pub use package::{NixPackage, NixPackageMeta};
+pub use builder::{AssetFormat, CargoLock, NixBuilder};
+pub use fetcher::{NixFetcher, SystemSource, WheelTag};
+pub use hash::{hex_to_nix_base32, hex_to_sri};
+pub use license::{sync_licenses, sync_spdx_licenses, NixLicense};
+pub use license_match::LicenseConfidence;
+pub use license_report::{license_report, ReportFormat};
+pub use native::{ElfFile, NativeBinaries};
+pub use overlay::overlay;
+pub use package::{NixPackage, NixPackageMeta, PackageLinks, PackageStatus};
 pub use platforms::NixPlatforms;
+pub use update::{update_report, updates};
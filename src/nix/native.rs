@@ -0,0 +1,252 @@
+use serde::Deserialize;
+
+use std::collections::BTreeSet;
+
+/// An ELF executable or shared library found in a package.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct ElfFile {
+    /// Path of the file in the package
+    pub path: String,
+    /// CPU it is built for, as in nix system names, eg. `x86_64` or `aarch64`
+    pub arch: String,
+    /// Shared libraries it is linked against, by soname
+    pub needed: Vec<String>,
+}
+
+/// Sonames glibc provides, which every stdenv has.
+const GLIBC: &[&str] = &[
+    "ld-linux",
+    "libanl.so",
+    "libc.so",
+    "libdl.so",
+    "libm.so",
+    "libpthread.so",
+    "libresolv.so",
+    "librt.so",
+    "libutil.so",
+];
+
+/// nixpkgs attributes providing shared libraries, by the start of their soname; more
+/// specific prefixes come first.
+const LIBRARIES: &[(&str, &str)] = &[
+    ("libstdc++.so", "stdenv.cc.cc.lib"),
+    ("libgcc_s.so", "stdenv.cc.cc.lib"),
+    ("libz.so", "zlib"),
+    ("libzstd.so", "zstd"),
+    ("liblzma.so", "xz"),
+    ("libbz2.so", "bzip2"),
+    ("libssl.so.1.1", "openssl_1_1"),
+    ("libcrypto.so.1.1", "openssl_1_1"),
+    ("libssl.so", "openssl"),
+    ("libcrypto.so", "openssl"),
+    ("libicu", "icu"),
+    ("libkrb5.so", "krb5"),
+    ("libgssapi_krb5.so", "krb5"),
+    ("libcurl.so", "curl"),
+    ("libuuid.so", "libuuid"),
+    ("libunwind.so", "libunwind"),
+    ("libffi.so", "libffi"),
+    ("libxml2.so", "libxml2"),
+    ("libsqlite3.so", "sqlite"),
+    ("libexpat.so", "expat"),
+    ("libtinfo.so.5", "ncurses5"),
+    ("libncurses.so.5", "ncurses5"),
+    ("libtinfo.so", "ncurses"),
+    ("libncurses", "ncurses"),
+    ("libsecret-1.so", "libsecret"),
+    ("libglib-2.0.so", "glib"),
+    ("libgobject-2.0.so", "glib"),
+    ("libgio-2.0.so", "glib"),
+    ("libgtk-3.so", "gtk3"),
+    ("libdbus-1.so", "dbus"),
+    ("libudev.so", "systemd"),
+    ("libnss3.so", "nss"),
+    ("libasound.so", "alsa-lib"),
+    ("libGL.so", "libGL"),
+    ("libX11.so", "xorg.libX11"),
+    ("libXext.so", "xorg.libXext"),
+    ("libxkbfile.so", "xorg.libxkbfile"),
+];
+
+fn is_glibc(soname: &str) -> bool {
+    GLIBC.iter().any(|prefix| soname.starts_with(prefix))
+}
+
+/// The nixpkgs attribute providing the library `soname`, if it is a known one.
+fn library_attr(soname: &str) -> Option<&'static str> {
+    LIBRARIES
+        .iter()
+        .find(|(prefix, _)| soname.starts_with(prefix))
+        .map(|(_, attr)| *attr)
+}
+
+/// The ELF files of a package, which do not run on NixOS until `autoPatchelfHook` points
+/// them at libraries in the store.
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+pub struct NativeBinaries {
+    pub files: Vec<ElfFile>,
+}
+
+impl NativeBinaries {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn archs(&self) -> BTreeSet<&str> {
+        self.files.iter().map(|file| file.arch.as_str()).collect()
+    }
+
+    /// Every library the files are linked against, besides glibc's.
+    pub fn libraries(&self) -> BTreeSet<&str> {
+        self.files
+            .iter()
+            .flat_map(|file| file.needed.iter().map(String::as_str))
+            .filter(|soname| !is_glibc(soname))
+            .collect()
+    }
+
+    /// Suggested `buildInputs`, from the libraries that are known.
+    pub fn build_inputs(&self) -> BTreeSet<&'static str> {
+        self.libraries()
+            .into_iter()
+            .filter_map(library_attr)
+            .collect()
+    }
+
+    /// Libraries no attribute is known for, which have to be found by hand, unless the
+    /// package ships them itself.
+    pub fn unknown_libraries(&self) -> BTreeSet<&str> {
+        let shipped: BTreeSet<&str> = self
+            .files
+            .iter()
+            .filter_map(|file| file.path.rsplit('/').next())
+            .collect();
+        self.libraries()
+            .into_iter()
+            .filter(|soname| library_attr(soname).is_none() && !shipped.contains(soname))
+            .collect()
+    }
+
+    /// Arguments the patching attributes are taken from in a `callPackage`-able expression.
+    pub fn function_args(&self) -> BTreeSet<&'static str> {
+        let mut args: BTreeSet<&'static str> = self
+            .build_inputs()
+            .into_iter()
+            .filter_map(|attr| attr.split('.').next())
+            .collect();
+        args.insert("autoPatchelfHook");
+        args.insert("lib");
+        args.insert("stdenv");
+        args
+    }
+
+    /// `nativeBuildInputs` with `autoPatchelfHook`, and the suggested `buildInputs`, on Linux
+    /// where the files can run.
+    pub fn attrs(&self) -> String {
+        let mut attrs = String::from(
+            "  nativeBuildInputs = lib.optionals stdenv.hostPlatform.isLinux [ autoPatchelfHook ];\n",
+        );
+        let build_inputs: Vec<&str> = self.build_inputs().into_iter().collect();
+        if !build_inputs.is_empty() {
+            attrs.push_str(&format!(
+                "  buildInputs = lib.optionals stdenv.hostPlatform.isLinux [ {} ];\n",
+                build_inputs.join(" ")
+            ));
+        }
+        let unknown: Vec<&str> = self.unknown_libraries().into_iter().collect();
+        if !unknown.is_empty() {
+            attrs.push_str(&format!(
+                "  # also needs {}, which has to be added by hand\n",
+                unknown.join(", ")
+            ));
+        }
+        attrs
+    }
+
+    /// What was found, to review: the files, their architectures and their libraries.
+    pub fn summary(&self) -> String {
+        let paths: Vec<&str> = self.files.iter().map(|file| file.path.as_str()).collect();
+        let archs: Vec<&str> = self.archs().into_iter().collect();
+        let mut summary = format!(
+            "ships native binaries for {}: {}",
+            archs.join(", "),
+            paths.join(", ")
+        );
+        let unknown: Vec<&str> = self.unknown_libraries().into_iter().collect();
+        if !unknown.is_empty() {
+            summary.push_str(&format!(
+                "; no buildInputs known for {}",
+                unknown.join(", ")
+            ));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elf(path: &str, arch: &str, needed: &[&str]) -> ElfFile {
+        ElfFile {
+            path: String::from(path),
+            arch: String::from(arch),
+            needed: needed.iter().map(|soname| soname.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_native_binaries() {
+        let native = NativeBinaries {
+            files: vec![
+                elf(
+                    "extension/server/rust-analyzer",
+                    "x86_64",
+                    &[
+                        "libgcc_s.so.1",
+                        "libm.so.6",
+                        "libc.so.6",
+                        "ld-linux-x86-64.so.2",
+                    ],
+                ),
+                elf(
+                    "extension/bin/addon.node",
+                    "x86_64",
+                    &[
+                        "libstdc++.so.6",
+                        "libz.so.1",
+                        "libX11.so.6",
+                        "libvendored.so",
+                        "libweird.so.2",
+                    ],
+                ),
+                elf("extension/bin/libvendored.so", "x86_64", &["libc.so.6"]),
+            ],
+        };
+
+        assert_eq!(native.archs().into_iter().collect::<Vec<_>>(), ["x86_64"]);
+        assert_eq!(
+            native.build_inputs().into_iter().collect::<Vec<_>>(),
+            ["stdenv.cc.cc.lib", "xorg.libX11", "zlib"]
+        );
+        assert_eq!(
+            native.unknown_libraries().into_iter().collect::<Vec<_>>(),
+            ["libweird.so.2"]
+        );
+        assert_eq!(
+            native.function_args().into_iter().collect::<Vec<_>>(),
+            ["autoPatchelfHook", "lib", "stdenv", "xorg", "zlib"]
+        );
+        assert_eq!(
+            native.attrs(),
+            "  nativeBuildInputs = lib.optionals stdenv.hostPlatform.isLinux [ autoPatchelfHook ];
+  buildInputs = lib.optionals stdenv.hostPlatform.isLinux [ stdenv.cc.cc.lib xorg.libX11 zlib ];
+  # also needs libweird.so.2, which has to be added by hand
+"
+        );
+        assert!(native
+            .summary()
+            .starts_with("ships native binaries for x86_64: "));
+        assert!(NativeBinaries::default().is_empty());
+    }
+}
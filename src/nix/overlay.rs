@@ -0,0 +1,126 @@
+use super::{
+    builder::{vscode_extension_attr, NixBuilder},
+    package::NixPackage,
+    string::{attr_name, quote},
+};
+
+/// Path of the directory a package is written to, relative to the overlay.
+fn package_path(name: &str) -> String {
+    if name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+'))
+    {
+        format!("./{}", name)
+    } else {
+        format!("(./. + {})", quote(&format!("/{}", name)))
+    }
+}
+
+/// Render an overlay adding every package, as written by [`NixPackage::write`], to nixpkgs.
+///
+/// Vim plugins land in `vimPlugins` and VS Code extensions in
+/// `vscode-extensions.<publisher>.<name>`, everything else at the top level.
+pub fn overlay(packages: &[NixPackage]) -> String {
+    let mut top_level = Vec::new();
+    let mut vim_plugins = Vec::new();
+    let mut vscode_extensions = Vec::new();
+
+    for package in packages {
+        let call_package = format!("final.callPackage {} {{ }}", package_path(&package.name));
+        match &package.builder {
+            NixBuilder::VimPlugin { .. } => vim_plugins.push(format!(
+                "    {} = {};\n",
+                attr_name(&package.name),
+                call_package
+            )),
+            NixBuilder::VSCodeExtension {
+                publisher, name, ..
+            } => vscode_extensions.push(format!(
+                "    {} = {};\n",
+                vscode_extension_attr(publisher, name),
+                call_package
+            )),
+            _ => top_level.push(format!(
+                "  {} = {};\n",
+                attr_name(&package.name),
+                call_package
+            )),
+        }
+    }
+
+    let mut sections = Vec::new();
+    if !top_level.is_empty() {
+        sections.push(top_level.concat());
+    }
+    if !vim_plugins.is_empty() {
+        sections.push(format!(
+            "  vimPlugins = prev.vimPlugins // {{\n{}  }};\n",
+            vim_plugins.concat()
+        ));
+    }
+    if !vscode_extensions.is_empty() {
+        sections.push(format!(
+            "  vscode-extensions = prev.lib.recursiveUpdate prev.vscode-extensions {{\n{}  }};\n",
+            vscode_extensions.concat()
+        ));
+    }
+
+    format!("final: prev: {{\n{}}}\n", sections.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::{NativeBinaries, NixFetcher, NixPackageMeta};
+
+    fn package(name: &str, builder: NixBuilder) -> NixPackage {
+        NixPackage {
+            name: String::from(name),
+            pname: String::from(name),
+            src: String::new(),
+            version: String::from("1.0.0"),
+            sha256: String::new(),
+            meta: NixPackageMeta::default(),
+            fetcher: NixFetcher::default(),
+            builder,
+        }
+    }
+
+    #[test]
+    fn test_overlay() {
+        let packages = vec![
+            package("eww", NixBuilder::MkDerivation),
+            package(
+                "vim-fugitive",
+                NixBuilder::VimPlugin {
+                    dependencies: Vec::new(),
+                },
+            ),
+            package(
+                "Dart-Code.dart-code",
+                NixBuilder::VSCodeExtension {
+                    publisher: String::from("Dart-Code"),
+                    name: String::from("dart-code"),
+                    dependencies: Vec::new(),
+                    native: NativeBinaries::default(),
+                },
+            ),
+        ];
+
+        assert_eq!(
+            overlay(&packages),
+            r#"final: prev: {
+  eww = final.callPackage ./eww { };
+
+  vimPlugins = prev.vimPlugins // {
+    vim-fugitive = final.callPackage ./vim-fugitive { };
+  };
+
+  vscode-extensions = prev.lib.recursiveUpdate prev.vscode-extensions {
+    dart-code.dart-code = final.callPackage ./Dart-Code.dart-code { };
+  };
+}
+"#
+        );
+    }
+}
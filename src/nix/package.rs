@@ -1,16 +1,20 @@
 use color_eyre::{
-    eyre::{eyre, Report, WrapErr},
+    eyre::{Report, WrapErr},
     Section,
 };
-use rayon::prelude::*;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use tracing::instrument;
 
-use std::path::{Path, PathBuf};
+use std::{collections::BTreeSet, fmt, fs, path::Path};
 
-use crate::package::TomlManifest;
-
-use super::{license::NixLicense, platforms::NixPlatforms};
+use super::{
+    builder::NixBuilder,
+    fetcher::NixFetcher,
+    license::NixLicense,
+    license_match::LicenseConfidence,
+    platforms::NixPlatforms,
+    string::{indented, quote},
+};
 
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct NixPackage {
@@ -18,8 +22,11 @@ pub struct NixPackage {
     pub pname: String,
     pub src: String,
     pub version: String,
+    /// Nix base32 sha256 of `src`, or an SRI hash (`sha512-...`) which is rendered as `hash`.
     pub sha256: String,
     pub meta: NixPackageMeta,
+    pub fetcher: NixFetcher,
+    pub builder: NixBuilder,
 }
 
 #[derive(Debug, Deserialize, Clone, PartialEq)]
@@ -29,8 +36,17 @@ pub struct NixPackageMeta {
     pub branch: Option<String>,
     pub homepage: Option<String>,
     pub download_page: Option<String>,
+    /// Source repository, for tooling; nixpkgs has no such attribute, so it is not rendered
+    pub repository: Option<String>,
     pub changelog: Option<Vec<String>>,
+    /// The changelog itself, in markdown; not rendered, only used to describe updates
+    pub changelog_text: Option<String>,
     pub license: Option<Vec<NixLicense>>,
+    /// How `license` was recognised; not rendered, only reported
+    pub license_confidence: Option<LicenseConfidence>,
+    /// Whether the package is still maintained where it is published; not rendered, only
+    /// reported
+    pub status: Option<PackageStatus>,
     pub priority: Option<String>,
     pub maintainers: Option<String>,
     pub platforms: Option<NixPlatforms>,
@@ -49,11 +65,15 @@ impl Default for NixPackageMeta {
             branch: None,
             homepage: None,
             download_page: None,
+            repository: None,
             changelog: None,
+            changelog_text: None,
             license: None,
+            license_confidence: None,
+            status: None,
             priority: None,
             maintainers: Some(String::from("th3whit3wolf")),
-            platforms: Some(NixPlatforms::None),
+            platforms: None,
             tests: None,
             timeout: None,
             hydra_platforms: None,
@@ -62,3 +82,540 @@ impl Default for NixPackageMeta {
         }
     }
 }
+
+impl NixPackage {
+    /// Render the package as a `callPackage`-able Nix expression.
+    pub fn to_nix(&self) -> String {
+        let mut args: BTreeSet<&str> = BTreeSet::new();
+        args.insert("lib");
+        args.extend(self.builder.function_args());
+        if let Some(native) = self.builder.native_binaries() {
+            args.extend(native.function_args());
+        }
+
+        let src = if self.builder.fetches_src() {
+            format!(
+                "  url = {};\n  sha256 = {};\n",
+                quote(&self.src),
+                quote(&self.sha256)
+            )
+        } else {
+            args.extend(self.fetcher.function_args());
+            format!(
+                "  src = {};\n",
+                self.fetcher.to_nix(&self.pname, &self.src, &self.sha256)
+            )
+        };
+        let args: Vec<&str> = args.into_iter().collect();
+
+        format!(
+            "{{ {args} }}:\n\n{builder} rec {{\n  pname = {pname};\n  version = {version};\n\n{src}\n{attrs}{separator}  meta = {meta};\n}}\n",
+            args = args.join(", "),
+            builder = self.builder.function(),
+            pname = quote(&self.pname),
+            version = quote(&self.version),
+            src = src,
+            attrs = self.builder.attrs(),
+            separator = if self.builder.attrs().is_empty() { "" } else { "\n" },
+            meta = self.meta.to_nix(),
+        )
+    }
+
+    /// Write the expression, and any file it references, to `<dir>/<name>/`.
+    #[instrument(skip(self))]
+    pub fn write(&self, dir: &Path) -> Result<(), Report> {
+        let package_dir = dir.join(&self.name);
+        fs::create_dir_all(&package_dir)
+            .wrap_err_with(|| format!("Unable to create {}", package_dir.display()))
+            .suggestion("Make sure you have permisions to the output directory")?;
+
+        fs::write(package_dir.join("default.nix"), self.to_nix())
+            .wrap_err_with(|| format!("Unable to write the expression for {}", self.name))?;
+        for (file_name, contents) in self.builder.extra_files() {
+            fs::write(package_dir.join(file_name), contents)
+                .wrap_err_with(|| format!("Unable to write {} for {}", file_name, self.name))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The pages a source knows of a package, which every backend turns into `meta` the same way.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PackageLinks {
+    /// The homepage the package declares itself
+    pub homepage: Option<String>,
+    /// Its source repository, as a URL or a shorthand like `github:owner/repo`
+    pub repository: Option<String>,
+    /// Its listing on the registry or store it is published to
+    pub store_page: Option<String>,
+}
+
+/// Why a package should not be relied on any more, as the registry it is published to says.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub enum PackageStatus {
+    /// Deprecated by its publisher, in favour of `replacement` when they name one
+    Deprecated { replacement: Option<String> },
+    /// Removed from the registry; only versions written before are left
+    Unpublished,
+}
+
+impl fmt::Display for PackageStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackageStatus::Deprecated {
+                replacement: Some(replacement),
+            } => write!(f, "deprecated, replaced by {}", replacement),
+            PackageStatus::Deprecated { replacement: None } => f.write_str("deprecated"),
+            PackageStatus::Unpublished => f.write_str("no longer published"),
+        }
+    }
+}
+
+/// The web page of a repository, from the ways manifests write it:
+/// `git+https://github.com/owner/repo.git`, `git@github.com:owner/repo.git`,
+/// `github:owner/repo` or just `owner/repo`.
+pub fn repository_url(repository: &str) -> Option<String> {
+    let url = repository.trim().trim_start_matches("git+");
+    let url = url
+        .strip_suffix(".git")
+        .unwrap_or(url)
+        .trim_end_matches('/');
+
+    let shorthand = |host: &str, path: &str| Some(format!("https://{}/{}", host, path));
+    if url.starts_with("https://") || url.starts_with("http://") {
+        Some(url.to_string())
+    } else if let Some(path) = url.strip_prefix("github:") {
+        shorthand("github.com", path)
+    } else if let Some(path) = url.strip_prefix("gitlab:") {
+        shorthand("gitlab.com", path)
+    } else if let Some(path) = url.strip_prefix("bitbucket:") {
+        shorthand("bitbucket.org", path)
+    } else if let Some(path) = url
+        .strip_prefix("git://")
+        .or_else(|| url.strip_prefix("ssh://git@"))
+    {
+        Some(format!("https://{}", path))
+    } else if let Some((host, path)) = url.strip_prefix("git@").and_then(|u| u.split_once(':')) {
+        shorthand(host, path)
+    } else {
+        match url.split_once('/') {
+            Some((owner, repo))
+                if !owner.is_empty()
+                    && !owner.starts_with('.')
+                    && !repo.is_empty()
+                    && !url.contains(':')
+                    && !repo.contains('/') =>
+            {
+                shorthand("github.com", url)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl NixPackageMeta {
+    /// `homepage` is the package's own homepage, or else its repository, or else its store
+    /// page, and `downloadPage` is the store page.
+    pub fn from_links(links: PackageLinks) -> Self {
+        let link = |url: Option<String>| {
+            url.map(|url| url.trim().to_string())
+                .filter(|url| url.starts_with("https://") || url.starts_with("http://"))
+        };
+        let repository = links.repository.as_deref().and_then(repository_url);
+        let store_page = link(links.store_page);
+        NixPackageMeta {
+            homepage: link(links.homepage)
+                .or_else(|| repository.clone())
+                .or_else(|| store_page.clone()),
+            download_page: store_page,
+            repository,
+            ..Default::default()
+        }
+    }
+
+    /// Render the `meta` attribute set, indented for the top level of a derivation.
+    pub fn to_nix(&self) -> String {
+        let mut meta = String::from("{\n");
+        if let Some(description) = &self.description {
+            meta.push_str(&format!("    description = {};\n", quote(description)));
+        }
+        if let Some(long_description) = &self.long_description {
+            meta.push_str(&format!(
+                "    longDescription = {};\n",
+                indented(long_description, "    ")
+            ));
+        }
+        if let Some(branch) = &self.branch {
+            meta.push_str(&format!("    branch = {};\n", quote(branch)));
+        }
+        if let Some(homepage) = &self.homepage {
+            meta.push_str(&format!("    homepage = {};\n", quote(homepage)));
+        }
+        if let Some(download_page) = &self.download_page {
+            meta.push_str(&format!("    downloadPage = {};\n", quote(download_page)));
+        }
+        if let Some(changelog) = self.changelog.as_ref().and_then(|c| c.first()) {
+            meta.push_str(&format!("    changelog = {};\n", quote(changelog)));
+        }
+        match self.license.as_deref() {
+            Some([license]) => {
+                meta.push_str(&format!("    license = {};\n", license.to_nix_meta()))
+            }
+            Some(licenses) if !licenses.is_empty() => {
+                let licenses: Vec<&str> = licenses.iter().map(|lic| lic.to_nix_meta()).collect();
+                meta.push_str(&format!("    license = [ {} ];\n", licenses.join(" ")));
+            }
+            _ => (),
+        }
+        if let Some(priority) = &self.priority {
+            meta.push_str(&format!("    priority = {};\n", priority));
+        }
+        if let Some(maintainers) = &self.maintainers {
+            meta.push_str(&format!(
+                "    maintainers = [ lib.maintainers.{} ];\n",
+                maintainers
+            ));
+        }
+        if let Some(platforms) = &self.platforms {
+            meta.push_str(&format!("    platforms = {};\n", platforms.to_nix_meta()));
+        }
+        if let Some(hydra_platforms) = &self.hydra_platforms {
+            meta.push_str(&format!("    hydraPlatforms = {};\n", hydra_platforms));
+        }
+        if let Some(timeout) = self.timeout {
+            meta.push_str(&format!("    timeout = {};\n", timeout));
+        }
+        if let Some(broken) = self.broken {
+            meta.push_str(&format!("    broken = {};\n", broken));
+        }
+        meta.push_str("  }");
+        meta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::{AssetFormat, CargoLock, ElfFile, NativeBinaries, SystemSource};
+
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_to_nix() {
+        let ripgrep = NixPackage {
+            name: String::from("ripgrep"),
+            pname: String::from("ripgrep"),
+            src: String::from("https://crates.io/api/v1/crates/ripgrep/13.0.0/download"),
+            version: String::from("13.0.0"),
+            sha256: String::from("1gv4imhjgxmyxaa996yshcjlakmrjw9pf4rycp90pq675cn9sz7k"),
+            meta: NixPackageMeta {
+                description: Some(String::from(
+                    "ripgrep is a line-oriented search tool that recursively searches your current directory for a regex pattern",
+                )),
+                homepage: Some(String::from("https://github.com/BurntSushi/ripgrep")),
+                license: Some(vec![NixLicense::Unlicense, NixLicense::Mit]),
+                ..Default::default()
+            },
+            fetcher: NixFetcher::FetchCrate {
+                crate_name: String::from("ripgrep"),
+            },
+            builder: NixBuilder::RustPackage {
+                cargo_lock: CargoLock {
+                    lock_file: String::from("# This file is automatically @generated by Cargo.\n"),
+                    output_hashes: BTreeMap::new(),
+                },
+            },
+        };
+
+        assert_eq!(
+            ripgrep.to_nix(),
+            r#"{ fetchCrate, lib, rustPlatform }:
+
+rustPlatform.buildRustPackage rec {
+  pname = "ripgrep";
+  version = "13.0.0";
+
+  src = fetchCrate {
+    inherit pname version;
+    sha256 = "1gv4imhjgxmyxaa996yshcjlakmrjw9pf4rycp90pq675cn9sz7k";
+  };
+
+  cargoLock = {
+    lockFile = ./Cargo.lock;
+  };
+
+  meta = {
+    description = "ripgrep is a line-oriented search tool that recursively searches your current directory for a regex pattern";
+    homepage = "https://github.com/BurntSushi/ripgrep";
+    license = [ lib.licenses.unlicense lib.licenses.mit ];
+    maintainers = [ lib.maintainers.th3whit3wolf ];
+  };
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_to_nix_vscode_dependencies() {
+        let java_pack = NixPackage {
+            name: String::from("vscjava.vscode-java-pack"),
+            pname: String::from("vscjava.vscode-java-pack"),
+            src: String::from("https://open-vsx.org/api/vscjava/vscode-java-pack/0.27.0/file/vscjava.vscode-java-pack-0.27.0.vsix"),
+            version: String::from("0.27.0"),
+            sha256: String::from("0lzj2yfbkhq9vh4pd2ib4yr5nhm4qdgw1s4ysvrkn42d6fjq2jpx"),
+            meta: NixPackageMeta {
+                description: Some(String::from("Popular extensions for Java development")),
+                maintainers: None,
+                ..Default::default()
+            },
+            fetcher: NixFetcher::FetchUrl {
+                name: Some(String::from("vscjava-vscode-java-pack.zip")),
+            },
+            builder: NixBuilder::VSCodeExtension {
+                publisher: String::from("vscjava"),
+                name: String::from("vscode-java-pack"),
+                dependencies: vec![
+                    String::from("redhat.java"),
+                    String::from("vscjava.vscode-java-debug"),
+                ],
+                native: NativeBinaries::default(),
+            },
+        };
+
+        assert_eq!(
+            java_pack.to_nix(),
+            r#"{ fetchurl, lib, vscode-extensions, vscode-utils }:
+
+vscode-utils.buildVscodeExtension rec {
+  pname = "vscjava.vscode-java-pack";
+  version = "0.27.0";
+
+  src = fetchurl {
+    name = "vscjava-vscode-java-pack.zip";
+    url = "https://open-vsx.org/api/vscjava/vscode-java-pack/0.27.0/file/vscjava.vscode-java-pack-0.27.0.vsix";
+    sha256 = "0lzj2yfbkhq9vh4pd2ib4yr5nhm4qdgw1s4ysvrkn42d6fjq2jpx";
+  };
+
+  name = "${pname}-${version}";
+  vscodeExtPublisher = "vscjava";
+  vscodeExtName = "vscode-java-pack";
+  vscodeExtUniqueId = "vscjava.vscode-java-pack";
+
+  propagatedUserEnvPkgs = [ vscode-extensions.redhat.java vscode-extensions.vscjava.vscode-java-debug ];
+
+  meta = {
+    description = "Popular extensions for Java development";
+  };
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_to_nix_vscode_native_binaries() {
+        let cpptools = NixPackage {
+            name: String::from("ms-vscode.cpptools"),
+            pname: String::from("ms-vscode.cpptools"),
+            src: String::from("https://open-vsx.org/api/ms-vscode/cpptools/1.9.8/file/ms-vscode.cpptools-1.9.8.vsix"),
+            version: String::from("1.9.8"),
+            sha256: String::from("1vw8i8vvqsk9pn4ipg1yb3d1gk1d7g4vhnnbl5s44p1dfm2v7x4b"),
+            meta: NixPackageMeta {
+                description: Some(String::from("C/C++ IntelliSense, debugging, and code browsing")),
+                maintainers: None,
+                ..Default::default()
+            },
+            fetcher: NixFetcher::FetchUrl { name: None },
+            builder: NixBuilder::VSCodeExtension {
+                publisher: String::from("ms-vscode"),
+                name: String::from("cpptools"),
+                dependencies: Vec::new(),
+                native: NativeBinaries {
+                    files: vec![ElfFile {
+                        path: String::from("extension/bin/cpptools"),
+                        arch: String::from("x86_64"),
+                        needed: vec![
+                            String::from("libstdc++.so.6"),
+                            String::from("libz.so.1"),
+                            String::from("libc.so.6"),
+                        ],
+                    }],
+                },
+            },
+        };
+
+        assert_eq!(
+            cpptools.to_nix(),
+            r#"{ autoPatchelfHook, fetchurl, lib, stdenv, vscode-utils, zlib }:
+
+vscode-utils.buildVscodeExtension rec {
+  pname = "ms-vscode.cpptools";
+  version = "1.9.8";
+
+  src = fetchurl {
+    url = "https://open-vsx.org/api/ms-vscode/cpptools/1.9.8/file/ms-vscode.cpptools-1.9.8.vsix";
+    sha256 = "1vw8i8vvqsk9pn4ipg1yb3d1gk1d7g4vhnnbl5s44p1dfm2v7x4b";
+  };
+
+  name = "${pname}-${version}";
+  vscodeExtPublisher = "ms-vscode";
+  vscodeExtName = "cpptools";
+  vscodeExtUniqueId = "ms-vscode.cpptools";
+
+  nativeBuildInputs = lib.optionals stdenv.hostPlatform.isLinux [ autoPatchelfHook ];
+  buildInputs = lib.optionals stdenv.hostPlatform.isLinux [ stdenv.cc.cc.lib zlib ];
+
+  meta = {
+    description = "C/C++ IntelliSense, debugging, and code browsing";
+  };
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_to_nix_per_system() {
+        let mut sources = BTreeMap::new();
+        sources.insert(
+            String::from("aarch64-darwin"),
+            SystemSource {
+                url: String::from("https://github.com/elkowar/eww/releases/download/v0.4.0/eww-aarch64-apple-darwin.zip"),
+                sha256: String::from("0m5b0xbq5mvx4lqc8vjn9k7f4kxbyhmhhxrfw7m2kqfqxqp3hgg8"),
+            },
+        );
+        sources.insert(
+            String::from("x86_64-linux"),
+            SystemSource {
+                url: String::from(
+                    "https://github.com/elkowar/eww/releases/download/v0.4.0/eww-x86_64-linux.zip",
+                ),
+                sha256: String::from("1kbg8bq0ghq1z9k3drs1x4h8ys6xqd5h5w6cd5mqg1h2iq2mxhs2"),
+            },
+        );
+        let eww = NixPackage {
+            name: String::from("eww"),
+            pname: String::from("eww"),
+            src: String::from("https://github.com/elkowar/eww/releases/tag/v0.4.0"),
+            version: String::from("0.4.0"),
+            sha256: String::new(),
+            meta: NixPackageMeta {
+                platforms: Some(NixPlatforms::Systems(sources.keys().cloned().collect())),
+                maintainers: None,
+                ..Default::default()
+            },
+            fetcher: NixFetcher::FetchUrlBySystem {
+                name: None,
+                sources,
+            },
+            builder: NixBuilder::PrebuiltBinary {
+                format: AssetFormat::Zip,
+            },
+        };
+
+        assert_eq!(
+            eww.to_nix(),
+            r#"{ fetchurl, lib, stdenv, unzip }:
+
+stdenv.mkDerivation rec {
+  pname = "eww";
+  version = "0.4.0";
+
+  src = {
+    aarch64-darwin = fetchurl {
+      url = "https://github.com/elkowar/eww/releases/download/v0.4.0/eww-aarch64-apple-darwin.zip";
+      sha256 = "0m5b0xbq5mvx4lqc8vjn9k7f4kxbyhmhhxrfw7m2kqfqxqp3hgg8";
+    };
+    x86_64-linux = fetchurl {
+      url = "https://github.com/elkowar/eww/releases/download/v0.4.0/eww-x86_64-linux.zip";
+      sha256 = "1kbg8bq0ghq1z9k3drs1x4h8ys6xqd5h5w6cd5mqg1h2iq2mxhs2";
+    };
+  }.${stdenv.hostPlatform.system} or (throw "Unsupported system: ${stdenv.hostPlatform.system}");
+
+  nativeBuildInputs = [ unzip ];
+
+  sourceRoot = ".";
+
+  installPhase = ''
+    runHook preInstall
+    find . -type f -perm -u+x -exec install -Dm755 -t $out/bin {} +
+    runHook postInstall
+  '';
+
+  meta = {
+    platforms = [ "aarch64-darwin" "x86_64-linux" ];
+  };
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_package_status() {
+        assert_eq!(
+            PackageStatus::Deprecated {
+                replacement: Some(String::from("ms-python.black-formatter"))
+            }
+            .to_string(),
+            "deprecated, replaced by ms-python.black-formatter"
+        );
+        assert_eq!(
+            PackageStatus::Deprecated { replacement: None }.to_string(),
+            "deprecated"
+        );
+        assert_eq!(
+            PackageStatus::Unpublished.to_string(),
+            "no longer published"
+        );
+    }
+
+    #[test]
+    fn test_from_links() {
+        for repository in [
+            "git+https://github.com/owner/repo.git",
+            "git@github.com:owner/repo.git",
+            "git://github.com/owner/repo.git",
+            "ssh://git@github.com/owner/repo",
+            "github:owner/repo",
+            "owner/repo",
+            "https://github.com/owner/repo/",
+        ] {
+            assert_eq!(
+                repository_url(repository).as_deref(),
+                Some("https://github.com/owner/repo"),
+                "{}",
+                repository
+            );
+        }
+        assert_eq!(repository_url("./local"), None);
+
+        let links = |homepage: Option<&str>, repository: Option<&str>| PackageLinks {
+            homepage: homepage.map(String::from),
+            repository: repository.map(String::from),
+            store_page: Some(String::from("https://www.npmjs.com/package/left-pad")),
+        };
+        let meta = NixPackageMeta::from_links(links(
+            Some("https://left-pad.io"),
+            Some("github:stevemao/left-pad"),
+        ));
+        assert_eq!(meta.homepage.as_deref(), Some("https://left-pad.io"));
+        assert_eq!(
+            meta.repository.as_deref(),
+            Some("https://github.com/stevemao/left-pad")
+        );
+        assert_eq!(
+            meta.download_page.as_deref(),
+            Some("https://www.npmjs.com/package/left-pad")
+        );
+
+        let meta = NixPackageMeta::from_links(links(Some(""), Some("github:stevemao/left-pad")));
+        assert_eq!(
+            meta.homepage.as_deref(),
+            Some("https://github.com/stevemao/left-pad")
+        );
+        let meta = NixPackageMeta::from_links(links(None, None));
+        assert_eq!(
+            meta.homepage.as_deref(),
+            Some("https://www.npmjs.com/package/left-pad")
+        );
+    }
+}
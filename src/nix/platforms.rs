@@ -1,4 +1,6 @@
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
+
+use super::string::quote;
 
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 pub enum NixPlatforms {
@@ -94,10 +96,12 @@ pub enum NixPlatforms {
     X86_64Redox,
     X86_64Solaris,
     X86_64Windows,
+    /// Exactly these systems, eg. `x86_64-linux`
+    Systems(Vec<String>),
 }
 
 impl NixPlatforms {
-    fn to_nix_meta(&self) -> String {
+    pub fn to_nix_meta(&self) -> String {
         match self {
             NixPlatforms::Aarch64 => String::from("lib.platforms.aarch64"),
             NixPlatforms::Aarch64Darwin => String::from("lib.platforms.aarch64-darwin"),
@@ -191,6 +195,14 @@ impl NixPlatforms {
             NixPlatforms::X86_64Redox => String::from("lib.platforms.x86-redox"),
             NixPlatforms::X86_64Solaris => String::from("lib.platforms.x86-solaris"),
             NixPlatforms::X86_64Windows => String::from("lib.platforms.x86-windows"),
+            NixPlatforms::Systems(systems) => format!(
+                "[ {} ]",
+                systems
+                    .iter()
+                    .map(|system| quote(system))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
         }
     }
 }
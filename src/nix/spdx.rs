@@ -0,0 +1,354 @@
+use thiserror::Error;
+use tracing::warn;
+
+use std::iter::Peekable;
+
+use super::license::NixLicense;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SpdxError {
+    #[error("Empty license expression")]
+    Empty,
+    #[error("Unexpected {0} in license expression")]
+    Unexpected(String),
+    #[error("Unexpected end of license expression")]
+    UnexpectedEnd,
+}
+
+/// A parsed SPDX license expression, eg. `(MIT OR Apache-2.0) AND BSD-3-Clause`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpdxExpression {
+    /// A license identifier, with the exception it is granted `WITH`
+    License {
+        id: String,
+        exception: Option<String>,
+    },
+    And(Vec<SpdxExpression>),
+    Or(Vec<SpdxExpression>),
+}
+
+/// The nix licenses an expression maps to, and the terms that map to none.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SpdxLicenses {
+    pub licenses: Vec<NixLicense>,
+    pub unrecognised: Vec<String>,
+}
+
+impl SpdxLicenses {
+    fn push(&mut self, license: NixLicense) {
+        if !self.licenses.contains(&license) {
+            self.licenses.push(license);
+        }
+    }
+
+    fn push_id(&mut self, id: &str) {
+        match NixLicense::from_str(id) {
+            Some(license) => self.push(*license),
+            // npm packages that may not be used without permission
+            None if id == "UNLICENSED" => self.push(NixLicense::Unfree),
+            None => self.unrecognised.push(id.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Open,
+    Close,
+    And,
+    Or,
+    With,
+    Id(&'a str),
+}
+
+impl std::fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Open => f.write_str("`(`"),
+            Token::Close => f.write_str("`)`"),
+            Token::And => f.write_str("`AND`"),
+            Token::Or => f.write_str("`OR`"),
+            Token::With => f.write_str("`WITH`"),
+            Token::Id(id) => write!(f, "`{}`", id),
+        }
+    }
+}
+
+/// Split an expression into tokens; operators may be upper or lower case, and the
+/// `/` older crates use stands for `OR`.
+fn tokenize(expression: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for word in expression.split_whitespace() {
+        let mut rest = word;
+        while !rest.is_empty() {
+            let end = rest.find(['(', ')', '/']).unwrap_or(rest.len());
+            if end == 0 {
+                tokens.push(match &rest[..1] {
+                    "(" => Token::Open,
+                    ")" => Token::Close,
+                    _ => Token::Or,
+                });
+                rest = &rest[1..];
+                continue;
+            }
+            tokens.push(match &rest[..end] {
+                "AND" | "and" => Token::And,
+                "OR" | "or" => Token::Or,
+                "WITH" | "with" => Token::With,
+                id => Token::Id(id),
+            });
+            rest = &rest[end..];
+        }
+    }
+    tokens
+}
+
+type Tokens<'a> = Peekable<std::vec::IntoIter<Token<'a>>>;
+
+impl SpdxExpression {
+    pub fn parse(expression: &str) -> Result<Self, SpdxError> {
+        let mut tokens = tokenize(expression).into_iter().peekable();
+        if tokens.peek().is_none() {
+            return Err(SpdxError::Empty);
+        }
+        let parsed = Self::parse_or(&mut tokens)?;
+        match tokens.next() {
+            Some(token) => Err(SpdxError::Unexpected(token.to_string())),
+            None => Ok(parsed),
+        }
+    }
+
+    fn parse_or(tokens: &mut Tokens<'_>) -> Result<Self, SpdxError> {
+        let mut operands = vec![Self::parse_and(tokens)?];
+        while tokens.next_if_eq(&Token::Or).is_some() {
+            operands.push(Self::parse_and(tokens)?);
+        }
+        Ok(match operands.len() {
+            1 => operands.remove(0),
+            _ => SpdxExpression::Or(operands),
+        })
+    }
+
+    fn parse_and(tokens: &mut Tokens<'_>) -> Result<Self, SpdxError> {
+        let mut operands = vec![Self::parse_with(tokens)?];
+        while tokens.next_if_eq(&Token::And).is_some() {
+            operands.push(Self::parse_with(tokens)?);
+        }
+        Ok(match operands.len() {
+            1 => operands.remove(0),
+            _ => SpdxExpression::And(operands),
+        })
+    }
+
+    fn parse_with(tokens: &mut Tokens<'_>) -> Result<Self, SpdxError> {
+        match tokens.next() {
+            Some(Token::Open) => {
+                let inner = Self::parse_or(tokens)?;
+                match tokens.next() {
+                    Some(Token::Close) => Ok(inner),
+                    Some(token) => Err(SpdxError::Unexpected(token.to_string())),
+                    None => Err(SpdxError::UnexpectedEnd),
+                }
+            }
+            Some(Token::Id(id)) => {
+                let exception = match tokens.next_if_eq(&Token::With) {
+                    Some(_) => match tokens.next() {
+                        Some(Token::Id(exception)) => Some(exception.to_string()),
+                        Some(token) => return Err(SpdxError::Unexpected(token.to_string())),
+                        None => return Err(SpdxError::UnexpectedEnd),
+                    },
+                    None => None,
+                };
+                Ok(SpdxExpression::License {
+                    id: id.to_string(),
+                    exception,
+                })
+            }
+            Some(token) => Err(SpdxError::Unexpected(token.to_string())),
+            None => Err(SpdxError::UnexpectedEnd),
+        }
+    }
+
+    /// Every license the expression names, as nixpkgs lists the licenses of a package
+    /// whether they apply together or as alternatives.
+    pub fn to_nix_licenses(&self) -> SpdxLicenses {
+        let mut licenses = SpdxLicenses::default();
+        self.collect_licenses(&mut licenses);
+        licenses
+    }
+
+    fn collect_licenses(&self, licenses: &mut SpdxLicenses) {
+        match self {
+            SpdxExpression::License {
+                id,
+                exception: Some(exception),
+            } => match with_exception(id, exception) {
+                Some(license) => licenses.push(license),
+                // nixpkgs has some exceptions, like LLVM-exception, as licenses of their own
+                None => {
+                    licenses.push_id(id);
+                    licenses.push_id(exception);
+                }
+            },
+            SpdxExpression::License {
+                id,
+                exception: None,
+            } => licenses.push_id(id),
+            SpdxExpression::And(operands) | SpdxExpression::Or(operands) => {
+                for operand in operands {
+                    operand.collect_licenses(licenses);
+                }
+            }
+        }
+    }
+}
+
+/// The nixpkgs license for a license granted with an exception, where nixpkgs has one.
+fn with_exception(id: &str, exception: &str) -> Option<NixLicense> {
+    match (id, exception) {
+        ("GPL-2.0" | "GPL-2.0-only", "Classpath-exception-2.0") => Some(NixLicense::Gpl2Classpath),
+        ("GPL-2.0+" | "GPL-2.0-or-later", "Classpath-exception-2.0") => {
+            Some(NixLicense::Gpl2ClasspathPlus)
+        }
+        ("GPL-3.0+" | "GPL-3.0-or-later", "Classpath-exception-2.0") => {
+            Some(NixLicense::Gpl3ClasspathPlus)
+        }
+        _ => None,
+    }
+}
+
+impl NixLicense {
+    /// Map an SPDX license expression to nix licenses, reporting the parts that map to none.
+    pub fn from_spdx(expression: &str) -> Vec<NixLicense> {
+        if expression.trim().is_empty() {
+            return Vec::new();
+        }
+        match SpdxExpression::parse(expression) {
+            Ok(parsed) => {
+                let SpdxLicenses {
+                    licenses,
+                    unrecognised,
+                } = parsed.to_nix_licenses();
+                if !unrecognised.is_empty() {
+                    warn!(
+                        "Unrecognised licenses in {}: {}",
+                        expression,
+                        unrecognised.join(", ")
+                    );
+                }
+                licenses
+            }
+            Err(e) => {
+                warn!("Unable to parse license {}: {}", expression, e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn license(id: &str) -> SpdxExpression {
+        SpdxExpression::License {
+            id: String::from(id),
+            exception: None,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(SpdxExpression::parse("MIT"), Ok(license("MIT")));
+        assert_eq!(
+            SpdxExpression::parse("MIT OR Apache-2.0"),
+            Ok(SpdxExpression::Or(vec![
+                license("MIT"),
+                license("Apache-2.0")
+            ]))
+        );
+        assert_eq!(
+            SpdxExpression::parse("(MIT AND BSD-3-Clause)"),
+            Ok(SpdxExpression::And(vec![
+                license("MIT"),
+                license("BSD-3-Clause")
+            ]))
+        );
+        assert_eq!(
+            SpdxExpression::parse("MIT AND (Apache-2.0 OR ISC) or Zlib"),
+            Ok(SpdxExpression::Or(vec![
+                SpdxExpression::And(vec![
+                    license("MIT"),
+                    SpdxExpression::Or(vec![license("Apache-2.0"), license("ISC")])
+                ]),
+                license("Zlib")
+            ]))
+        );
+        assert_eq!(
+            SpdxExpression::parse("Unlicense/MIT"),
+            Ok(SpdxExpression::Or(vec![
+                license("Unlicense"),
+                license("MIT")
+            ]))
+        );
+        assert_eq!(
+            SpdxExpression::parse("GPL-2.0-or-later WITH Classpath-exception-2.0"),
+            Ok(SpdxExpression::License {
+                id: String::from("GPL-2.0-or-later"),
+                exception: Some(String::from("Classpath-exception-2.0")),
+            })
+        );
+
+        assert_eq!(SpdxExpression::parse("  "), Err(SpdxError::Empty));
+        assert_eq!(
+            SpdxExpression::parse("(MIT OR"),
+            Err(SpdxError::UnexpectedEnd)
+        );
+        assert_eq!(
+            SpdxExpression::parse("MIT Apache-2.0"),
+            Err(SpdxError::Unexpected(String::from("`Apache-2.0`")))
+        );
+        assert_eq!(
+            SpdxExpression::parse("MIT WITH OR"),
+            Err(SpdxError::Unexpected(String::from("`OR`")))
+        );
+    }
+
+    #[test]
+    fn test_to_nix_licenses() {
+        let licenses =
+            |expression: &str| SpdxExpression::parse(expression).unwrap().to_nix_licenses();
+
+        assert_eq!(
+            licenses("(MIT AND BSD-3-Clause) OR MIT").licenses,
+            vec![NixLicense::Mit, NixLicense::Bsd3]
+        );
+        assert_eq!(
+            licenses("GPL-2.0-or-later WITH Classpath-exception-2.0").licenses,
+            vec![NixLicense::Gpl2ClasspathPlus]
+        );
+        assert_eq!(
+            licenses("Apache-2.0 WITH LLVM-exception").licenses,
+            vec![NixLicense::Asl20, NixLicense::LlvmException]
+        );
+
+        let unknown = licenses("MIT OR LicenseRef-Proprietary");
+        assert_eq!(unknown.licenses, vec![NixLicense::Mit]);
+        assert_eq!(unknown.unrecognised, vec!["LicenseRef-Proprietary"]);
+
+        let unknown = licenses("GPL-3.0-only WITH Bison-exception-2.2");
+        assert_eq!(unknown.licenses, vec![NixLicense::Gpl3Only]);
+        assert_eq!(unknown.unrecognised, vec!["Bison-exception-2.2"]);
+
+        assert_eq!(
+            NixLicense::from_spdx("MIT OR Apache-2.0"),
+            vec![NixLicense::Mit, NixLicense::Asl20]
+        );
+        assert_eq!(
+            NixLicense::from_spdx("UNLICENSED"),
+            vec![NixLicense::Unfree]
+        );
+        assert!(NixLicense::from_spdx("").is_empty());
+        assert!(NixLicense::from_spdx("(MIT").is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+/*
+    Escaping rules taken from
+    https://nixos.org/manual/nix/stable/expressions/language-values.html
+*/
+
+/// Quote `s` as a double-quoted Nix string.
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '$' if chars.peek() == Some(&'{') => quoted.push_str("\\$"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Render `s` as an attribute name, quoting it unless it is a valid identifier.
+pub fn attr_name(s: &str) -> String {
+    let mut chars = s.chars();
+    let is_identifier = match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '\'')
+        }
+        _ => false,
+    };
+    if is_identifier {
+        s.to_string()
+    } else {
+        quote(s)
+    }
+}
+
+/// Render `s` as an indented (`''`) Nix string, with every line indented by `indent`.
+pub fn indented(s: &str, indent: &str) -> String {
+    let mut rendered = String::from("''\n");
+    for line in s.trim().lines() {
+        let line = line.replace("''", "'''").replace("${", "''${");
+        if line.trim().is_empty() {
+            rendered.push('\n');
+        } else {
+            rendered.push_str(indent);
+            rendered.push_str("  ");
+            rendered.push_str(&line);
+            rendered.push('\n');
+        }
+    }
+    rendered.push_str(indent);
+    rendered.push_str("''");
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attr_name() {
+        assert_eq!(attr_name("plenary-nvim"), "plenary-nvim");
+        assert_eq!(attr_name("nvim-treesitter.lua"), "\"nvim-treesitter.lua\"");
+        assert_eq!(attr_name("1password"), "\"1password\"");
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("ripgrep"), r#""ripgrep""#);
+        assert_eq!(quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote("${pkgs}"), r#""\${pkgs}""#);
+        assert_eq!(quote("$HOME"), r#""$HOME""#);
+        assert_eq!(quote("a\nb"), r#""a\nb""#);
+    }
+
+    #[test]
+    fn test_indented() {
+        assert_eq!(
+            indented("first\n\nsecond ''${x}", "  "),
+            "''\n    first\n\n    second '''''${x}\n  ''"
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use regex::Regex;
+
+use std::{fs, path::Path};
+
+use super::{changelog::notes_between, package::NixPackage};
+
+/// The version of the package `name` as [`NixPackage::write`] last wrote it to `dir`.
+pub fn written_version(dir: &Path, name: &str) -> Option<String> {
+    let expression = fs::read_to_string(dir.join(name).join("default.nix")).ok()?;
+    let version = Regex::new(r#"(?m)^  version = "((?:[^"\\]|\\.)*)";$"#).unwrap();
+    let captures = version.captures(&expression)?;
+    Some(
+        captures[1]
+            .replace("\\\"", "\"")
+            .replace("\\$", "$")
+            .replace("\\\\", "\\"),
+    )
+}
+
+/// A package that is new, or at another version than the one written before.
+#[derive(Debug, PartialEq)]
+pub struct PackageUpdate<'a> {
+    pub package: &'a NixPackage,
+    /// The version written before, if the package was
+    pub from: Option<String>,
+}
+
+impl PackageUpdate<'_> {
+    /// `name: 1.0.0 -> 1.1.0`, or `name: init at 1.1.0`, as nixpkgs titles its commits.
+    pub fn title(&self) -> String {
+        match &self.from {
+            Some(from) => format!(
+                "{}: {} -> {}",
+                self.package.name, from, self.package.version
+            ),
+            None => format!("{}: init at {}", self.package.name, self.package.version),
+        }
+    }
+
+    /// The changelog of every version since the one written before.
+    pub fn notes(&self) -> Option<String> {
+        let changelog = self.package.meta.changelog_text.as_deref()?;
+        notes_between(changelog, self.from.as_deref(), &self.package.version)
+    }
+
+    fn changelog_url(&self) -> Option<&str> {
+        self.package
+            .meta
+            .changelog
+            .as_ref()
+            .and_then(|changelog| changelog.first())
+            .map(String::as_str)
+    }
+
+    pub fn commit_message(&self) -> String {
+        let mut message = format!("{}\n", self.title());
+        if let Some(notes) = self.notes() {
+            message.push_str(&format!("\n{}\n", notes));
+        }
+        if let Some(url) = self.changelog_url() {
+            message.push_str(&format!("\nChangelog: {}\n", url));
+        }
+        message
+    }
+}
+
+/// The packages that are new or updated since they were written to `dir`.
+pub fn updates<'a>(packages: &'a [NixPackage], dir: &Path) -> Vec<PackageUpdate<'a>> {
+    packages
+        .iter()
+        .map(|package| PackageUpdate {
+            package,
+            from: written_version(dir, &package.name),
+        })
+        .filter(|update| update.from.as_deref() != Some(update.package.version.as_str()))
+        .collect()
+}
+
+/// A markdown report of the updates, with what changed in each, eg. for a pull request.
+pub fn update_report(updates: &[PackageUpdate<'_>]) -> String {
+    if updates.is_empty() {
+        return String::from("Everything is up to date.\n");
+    }
+
+    let mut report = String::from("# Updates\n");
+    for update in updates {
+        report.push_str(&format!("\n## {}\n\n", update.title()));
+        match update.notes() {
+            Some(notes) => report.push_str(&format!("{}\n", notes)),
+            None => report.push_str(&format!(
+                "No changelog entry for {}.\n",
+                update.package.version
+            )),
+        }
+        if let Some(url) = update.changelog_url() {
+            report.push_str(&format!("\n[Changelog]({})\n", url));
+        }
+        if let Some(status) = &update.package.meta.status {
+            report.push_str(&format!("\n**Review:** {}.\n", status));
+        }
+        if let Some(native) = update.package.builder.native_binaries() {
+            report.push_str(&format!(
+                "\n**Review:** {}; check it runs with `autoPatchelfHook`.\n",
+                native.summary()
+            ));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::{
+        ElfFile, NativeBinaries, NixBuilder, NixFetcher, NixPackageMeta, PackageStatus,
+    };
+
+    fn package(name: &str, version: &str, changelog_text: Option<&str>) -> NixPackage {
+        NixPackage {
+            name: String::from(name),
+            pname: String::from(name),
+            src: format!("https://example.com/{}-{}.tar.gz", name, version),
+            version: String::from(version),
+            sha256: String::new(),
+            meta: NixPackageMeta {
+                changelog: Some(vec![format!("https://example.com/{}/releases", name)]),
+                changelog_text: changelog_text.map(String::from),
+                ..Default::default()
+            },
+            fetcher: NixFetcher::FetchUrl { name: None },
+            builder: NixBuilder::MkDerivation,
+        }
+    }
+
+    const RELEASES: &str =
+        "## v0.9.0\n\nFaster startup\n\n## v0.8.1\n\nFix a crash\n\n## v0.8.0\n\nFirst release\n";
+
+    #[test]
+    fn test_updates() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = package("tool", "0.8.0", None);
+        written.write(dir.path()).unwrap();
+        package("same", "1.0.0", None).write(dir.path()).unwrap();
+        assert_eq!(
+            written_version(dir.path(), "tool").as_deref(),
+            Some("0.8.0")
+        );
+        assert_eq!(written_version(dir.path(), "missing"), None);
+
+        let packages = vec![
+            package("tool", "0.9.0", Some(RELEASES)),
+            package("same", "1.0.0", None),
+            package("fresh", "2.0.0", None),
+        ];
+        let updates = updates(&packages, dir.path());
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].title(), "tool: 0.8.0 -> 0.9.0");
+        assert_eq!(updates[1].title(), "fresh: init at 2.0.0");
+
+        assert_eq!(
+            updates[0].commit_message(),
+            "\
+tool: 0.8.0 -> 0.9.0
+
+### 0.9.0
+
+Faster startup
+
+### 0.8.1
+
+Fix a crash
+
+Changelog: https://example.com/tool/releases
+"
+        );
+        assert_eq!(
+            update_report(&updates),
+            "\
+# Updates
+
+## tool: 0.8.0 -> 0.9.0
+
+### 0.9.0
+
+Faster startup
+
+### 0.8.1
+
+Fix a crash
+
+[Changelog](https://example.com/tool/releases)
+
+## fresh: init at 2.0.0
+
+No changelog entry for 2.0.0.
+
+[Changelog](https://example.com/fresh/releases)
+"
+        );
+        assert_eq!(update_report(&[]), "Everything is up to date.\n");
+    }
+
+    #[test]
+    fn test_update_report_review() {
+        let mut extension = package("rust-lang.rust-analyzer", "0.3.0", None);
+        extension.builder = NixBuilder::VSCodeExtension {
+            publisher: String::from("rust-lang"),
+            name: String::from("rust-analyzer"),
+            dependencies: Vec::new(),
+            native: NativeBinaries {
+                files: vec![ElfFile {
+                    path: String::from("extension/server/rust-analyzer"),
+                    arch: String::from("x86_64"),
+                    needed: vec![String::from("libgcc_s.so.1"), String::from("libc.so.6")],
+                }],
+            },
+        };
+        extension.meta.status = Some(PackageStatus::Deprecated {
+            replacement: Some(String::from("rust-lang.rust-analyzer-next")),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let packages = vec![extension];
+
+        assert!(update_report(&updates(&packages, dir.path())).ends_with(
+            "\n**Review:** deprecated, replaced by rust-lang.rust-analyzer-next.\n\n**Review:** ships native binaries for x86_64: extension/server/rust-analyzer; check it runs with `autoPatchelfHook`.\n"
+        ));
+    }
+}
@@ -0,0 +1,94 @@
+use serde::de;
+use serde::{Deserialize, Serialize};
+use toml::value::Table;
+
+use std::fmt;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DetailedTomlAmoPackage {
+    /// The add-on's slug or GUID
+    pub src: String,
+    pub pin: Option<String>,
+    pub passthru: Option<Table>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum TomlAmoPackage {
+    /// In the simple format, only a slug or GUID is specified, eg.
+    /// `package = "<slug>"`
+    Simple(String),
+    /// The simple format is equivalent to a detailed dependency
+    /// specifying only a slug or GUID, eg.
+    /// `package = { src = "<slug>" }`
+    Detailed(DetailedTomlAmoPackage),
+}
+
+impl<'de> de::Deserialize<'de> for TomlAmoPackage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct TomlAmoPackageVisitor;
+
+        impl<'de> de::Visitor<'de> for TomlAmoPackageVisitor {
+            type Value = TomlAmoPackage;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str(
+                    "an add-on slug like \"ublock-origin\" or a \
+                     detailed dependency like { src = \"uBlock0@raymondhill.net\", pin = \"1.52.2\" }",
+                )
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(TomlAmoPackage::Simple(s.to_owned()))
+            }
+
+            fn visit_map<V>(self, map: V) -> Result<Self::Value, V::Error>
+            where
+                V: de::MapAccess<'de>,
+            {
+                let mvd = de::value::MapAccessDeserializer::new(map);
+                DetailedTomlAmoPackage::deserialize(mvd).map(TomlAmoPackage::Detailed)
+            }
+        }
+
+        deserializer.deserialize_any(TomlAmoPackageVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_amo() {
+        let test_str = r#"
+		[amo]
+        ublock-origin = "ublock-origin"
+        bitwarden = { src = "{446900e4-71c2-419f-a6a7-df9c091e268b}", pin = "2023.10.1" }
+		"#;
+
+        let manifest_amo = crate::package::TomlManifest::from_str(test_str)
+            .unwrap()
+            .amo
+            .unwrap();
+
+        match manifest_amo.get("ublock-origin").unwrap() {
+            TomlAmoPackage::Simple(s) => assert_eq!(s, "ublock-origin"),
+            TomlAmoPackage::Detailed(_) => unreachable!(),
+        }
+
+        match manifest_amo.get("bitwarden").unwrap() {
+            TomlAmoPackage::Simple(_) => unreachable!(),
+            TomlAmoPackage::Detailed(detailed) => {
+                assert_eq!(&detailed.src, "{446900e4-71c2-419f-a6a7-df9c091e268b}");
+                assert_eq!(detailed.pin.as_deref(), Some("2023.10.1"));
+                assert!(&detailed.passthru.is_none());
+            }
+        }
+    }
+}
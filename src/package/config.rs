@@ -1,33 +1,48 @@
 use color_eyre::{
-    eyre::{eyre, Report, Result, WrapErr},
+    eyre::{eyre, Report, Result},
     Section,
 };
 
+use semver::Version;
 use serde::{Deserialize, Serialize};
 
 use toml::Value;
-use tracing::instrument;
+use tracing::{instrument, warn};
 
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet, VecDeque},
     fs,
     path::{Path, PathBuf},
 };
 
-use crate::nix::NixPackage;
+use crate::nix::{NixBuilder, NixPackage};
 
 use super::{
-    error::PackageError, github::TomlGitHubPackage, openvsx::TomlOpenVSXPackage,
-    settings::TomlSettings, vsmarketplace::TomlVSCodeMarketPlacePackage,
+    amo::TomlAmoPackage, crates::TomlCratesPackage, error::PackageError, github::TomlGitHubPackage,
+    jetbrains::TomlJetBrainsPackage, npm::TomlNpmPackage, openvsx::TomlOpenVSXPackage,
+    pypi::TomlPypiPackage, settings::TomlSettings, vsmarketplace::TomlVSCodeMarketPlacePackage,
 };
 
-use crate::sources::openvsx::OpenVSXExtension;
+use crate::sources::{
+    amo::AmoAddon,
+    crates::CratesIoCrate,
+    error::SourceError,
+    git::{GitRef, GitRepository},
+    github::{GitHubBatch, GitHubHost, GitHubReleaseSource, GitHubSource},
+    gitlab::GitLabSource,
+    jetbrains::JetBrainsPlugin,
+    npm::NpmVersion,
+    openvsx::OpenVSXExtension,
+    pypi::PyPIProject,
+    vscode::{nixpkgs_vscode_version, parse_vscode_version},
+    vscodemarketplace::VSMarketPlaceExtensionRefined,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum Overlay {
     #[serde(rename(deserialize = "vscode"))]
     VSCode,
-    #[serde(rename(deserialize = "Vim"))]
+    #[serde(rename(deserialize = "vim"), alias = "Vim")]
     Vim,
     #[serde(rename(deserialize = "none"))]
     None,
@@ -37,8 +52,15 @@ pub enum Overlay {
 pub struct TomlManifest {
     pub settings: Option<Box<TomlSettings>>,
     pub github: Option<BTreeMap<String, TomlGitHubPackage>>,
+    pub gitlab: Option<BTreeMap<String, TomlGitHubPackage>>,
+    pub git: Option<BTreeMap<String, TomlGitHubPackage>>,
     pub openvsx: Option<BTreeMap<String, TomlOpenVSXPackage>>,
     pub vsmarketplace: Option<BTreeMap<String, TomlVSCodeMarketPlacePackage>>,
+    pub crates: Option<BTreeMap<String, TomlCratesPackage>>,
+    pub pypi: Option<BTreeMap<String, TomlPypiPackage>>,
+    pub npm: Option<BTreeMap<String, TomlNpmPackage>>,
+    pub amo: Option<BTreeMap<String, TomlAmoPackage>>,
+    pub jetbrains: Option<BTreeMap<String, TomlJetBrainsPackage>>,
     #[serde(flatten)]
     pub other: BTreeMap<String, Value>,
 }
@@ -51,13 +73,6 @@ fn config_path_to_string(config_toml: PathBuf) -> String {
     }
 }
 
-/*
- * Github Rate Limit
- * - Unauthenticated =>     60 requests per hour
- * - Authenticated   =>  5,000 requests per hour
- * - Enterprise      => 15,000 requests per hour
- */
-
 impl TomlManifest {
     /// Read config file and all of its packages
     #[instrument]
@@ -113,45 +128,505 @@ impl TomlManifest {
         passthru: Option<Table>
     }
         */
-    pub async fn get_openvsx_nixpkgs(self) -> Result<Vec<NixPackage>> {
+    /// The VS Code version extensions have to be compatible with: `settings.vscode_version`,
+    /// or else the one in nixpkgs at `settings.vscode_nixpkgs`.
+    pub async fn vscode_version(&self) -> Result<Option<Version>> {
+        let settings = match self.settings.as_deref() {
+            Some(settings) => settings,
+            None => return Ok(None),
+        };
+        match (&settings.vscode_version, &settings.vscode_nixpkgs) {
+            (Some(version), _) => parse_vscode_version(version).map(Some),
+            (None, Some(rev)) => nixpkgs_vscode_version(rev).await.map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// The Open VSX extensions of the manifest, and the names of those that are no longer
+    /// published, which have nothing left to package.
+    pub async fn get_openvsx_nixpkgs(&self) -> Result<(Vec<NixPackage>, Vec<String>)> {
         let mut openvsx: Vec<NixPackage> = Vec::new();
-        if let Some(map) = self.openvsx {
-            for (pname, openvsx_package) in map {
-                match openvsx_package {
-                    TomlOpenVSXPackage::Simple(unique_id) => {
-                        match OpenVSXExtension::get(unique_id).await {
-                            Ok(pkg) => openvsx.push(pkg.to_nixpkg(pname, None)),
-                            Err(_) => {
-                                return Err(eyre!("Unable to get package for {}", pname)
-                                    .error(PackageError::DownloadPackageError))
+        let mut unpublished: Vec<String> = Vec::new();
+        if let Some(map) = &self.openvsx {
+            let mut extensions: VecDeque<(String, OpenVSXExtension, Option<String>)> =
+                VecDeque::new();
+            let default_vscode = self.vscode_version().await?;
+            if default_vscode.is_none() {
+                warn!("No VS Code version configured, extensions will not be checked for compatibility");
+            }
+            for (pname, openvsx_package) in map.clone() {
+                let (pkg, extract) = match openvsx_package {
+                    TomlOpenVSXPackage::Simple(unique_id) => (
+                        OpenVSXExtension::get_compatible(unique_id, default_vscode.as_ref(), false)
+                            .await,
+                        None,
+                    ),
+                    TomlOpenVSXPackage::Detailed(details) => {
+                        let vscode = match &details.vscode_version {
+                            Some(version) => Some(parse_vscode_version(version)?),
+                            None => default_vscode.clone(),
+                        };
+                        let pkg = match details.pin {
+                            Some(version) => {
+                                OpenVSXExtension::get_with_version(details.src, version)
+                                    .await
+                                    .map(|extension| {
+                                        if let Some(vscode) =
+                                            vscode.as_ref().filter(|v| !extension.accepts(v))
+                                        {
+                                            warn!(
+                                                "{}.{} {} is not compatible with VS Code {}",
+                                                extension.namespace,
+                                                extension.name,
+                                                extension.version,
+                                                vscode
+                                            );
+                                        }
+                                        extension
+                                    })
+                            }
+                            None => {
+                                OpenVSXExtension::get_compatible(
+                                    details.src,
+                                    vscode.as_ref(),
+                                    details.prerelease,
+                                )
+                                .await
                             }
+                        };
+                        (pkg, details.extract)
+                    }
+                };
+                match pkg {
+                    Ok(pkg) => extensions.push_back((pname, pkg, extract)),
+                    Err(e) if matches!(e.downcast_ref(), Some(SourceError::Unpublished(_))) => {
+                        unpublished.push(pname)
+                    }
+                    Err(e) => {
+                        return Err(eyre!("Unable to get package for {}", pname)
+                            .error(PackageError::DownloadPackageError))
+                        .note(e)
+                    }
+                }
+            }
+
+            // Walk extensionDependencies and extensionPack, so installing an extension
+            // installs everything it needs
+            let add_dependencies = self
+                .settings
+                .as_deref()
+                .is_some_and(|settings| settings.add_extension_dependencies);
+            let mut known: BTreeSet<String> = extensions
+                .iter()
+                .map(|(_, extension, _)| {
+                    format!("{}.{}", extension.namespace, extension.name).to_lowercase()
+                })
+                .collect();
+            while let Some((pname, extension, extract)) = extensions.pop_front() {
+                let dependencies = match extension.manifest().await {
+                    Ok(manifest) => manifest.requires(),
+                    Err(e) => {
+                        warn!("Unable to read the package.json of {}: {}", pname, e);
+                        Vec::new()
+                    }
+                };
+                for unique_id in &dependencies {
+                    if !known.insert(unique_id.to_lowercase()) {
+                        continue;
+                    }
+                    if !add_dependencies {
+                        warn!(
+                            "{} needs {}, which is not in the manifest and has to come from nixpkgs",
+                            pname, unique_id
+                        );
+                        continue;
+                    }
+                    match OpenVSXExtension::get_compatible(
+                        unique_id.clone(),
+                        default_vscode.as_ref(),
+                        false,
+                    )
+                    .await
+                    {
+                        Ok(dependency) => {
+                            extensions.push_back((unique_id.clone(), dependency, None))
+                        }
+                        Err(e) => {
+                            return Err(eyre!(
+                                "Unable to get package for {}, which {} depends on",
+                                unique_id,
+                                pname
+                            )
+                            .error(PackageError::DownloadPackageError))
+                            .note(e)
                         }
                     }
-                    TomlOpenVSXPackage::Detailed(details) => {
-                        let pkg = if let Some(version) = details.pin {
-                            match OpenVSXExtension::get_with_version(details.src, version).await {
-                                Ok(p) => p,
-                                Err(_) => {
-                                    return Err(eyre!("Unable to get package for {}", pname)
-                                        .error(PackageError::DownloadPackageError))
+                }
+                match extension.to_nixpkg(pname.clone(), extract, dependencies) {
+                    Ok(pkg) => openvsx.push(pkg),
+                    Err(e) => {
+                        return Err(eyre!("Unable to get package for {}", pname)
+                            .error(PackageError::DownloadPackageError))
+                        .note(e)
+                    }
+                }
+            }
+        }
+        Ok((openvsx, unpublished))
+    }
+
+    /// The VS Code Marketplace extensions of the manifest, and the names of those that are
+    /// no longer published.
+    pub async fn get_vsmarketplace_nixpkgs(&self) -> Result<(Vec<NixPackage>, Vec<String>)> {
+        let mut vsmarketplace: Vec<NixPackage> = Vec::new();
+        let mut unpublished: Vec<String> = Vec::new();
+        if let Some(map) = &self.vsmarketplace {
+            let default_vscode = self.vscode_version().await?;
+            if default_vscode.is_none() {
+                warn!("No VS Code version configured, extensions will not be checked for compatibility");
+            }
+            for (pname, vsmarketplace_package) in map.clone() {
+                let pkg = match vsmarketplace_package {
+                    TomlVSCodeMarketPlacePackage::Simple(unique_id) => {
+                        VSMarketPlaceExtensionRefined::get_compatible(
+                            unique_id,
+                            default_vscode.as_ref(),
+                            false,
+                        )
+                        .await
+                    }
+                    TomlVSCodeMarketPlacePackage::Detailed(details) => {
+                        let vscode = match &details.vscode_version {
+                            Some(version) => Some(parse_vscode_version(version)?),
+                            None => default_vscode.clone(),
+                        };
+                        match details.pin {
+                            Some(version) => VSMarketPlaceExtensionRefined::get_with_version(
+                                details.src,
+                                version,
+                            )
+                            .await
+                            .map(|extension| {
+                                if let Some(vscode) =
+                                    vscode.as_ref().filter(|v| !extension.accepts(v))
+                                {
+                                    warn!(
+                                        "{}.{} {} is not compatible with VS Code {}",
+                                        extension.publisher,
+                                        extension.extension_name,
+                                        extension.version,
+                                        vscode
+                                    );
                                 }
+                                extension
+                            }),
+                            None => {
+                                VSMarketPlaceExtensionRefined::get_compatible(
+                                    details.src,
+                                    vscode.as_ref(),
+                                    details.prerelease,
+                                )
+                                .await
                             }
-                        } else {
-                            match OpenVSXExtension::get(details.src).await {
-                                Ok(p) => p,
-                                Err(_) => {
-                                    return Err(eyre!("Unable to get package for {}", pname)
-                                        .error(PackageError::DownloadPackageError))
-                                }
+                        }
+                    }
+                };
+                match pkg.and_then(|pkg| pkg.to_nixpkg(pname.clone())) {
+                    Ok(pkg) => vsmarketplace.push(pkg),
+                    Err(e) if matches!(e.downcast_ref(), Some(SourceError::Unpublished(_))) => {
+                        unpublished.push(pname)
+                    }
+                    Err(e) => {
+                        return Err(eyre!("Unable to get package for {}", pname)
+                            .error(PackageError::DownloadPackageError))
+                        .note(e)
+                    }
+                }
+            }
+        }
+        Ok((vsmarketplace, unpublished))
+    }
+
+    pub async fn get_crates_nixpkgs(&self) -> Result<Vec<NixPackage>> {
+        let mut crates: Vec<NixPackage> = Vec::new();
+        if let Some(map) = &self.crates {
+            for (pname, crates_package) in map.clone() {
+                let pkg = match crates_package {
+                    TomlCratesPackage::Simple(crate_name) => CratesIoCrate::get(crate_name).await,
+                    TomlCratesPackage::Detailed(details) => match details.pin {
+                        Some(version) => {
+                            CratesIoCrate::get_with_version(details.src, version).await
+                        }
+                        None => CratesIoCrate::get(details.src).await,
+                    },
+                };
+                match pkg.and_then(|pkg| pkg.to_nixpkg(pname.clone())) {
+                    Ok(pkg) => crates.push(pkg),
+                    Err(e) => {
+                        return Err(eyre!("Unable to get package for {}", pname)
+                            .error(PackageError::DownloadPackageError))
+                        .note(e)
+                    }
+                }
+            }
+        }
+        Ok(crates)
+    }
+
+    pub async fn get_pypi_nixpkgs(&self) -> Result<Vec<NixPackage>> {
+        let mut pypi: Vec<NixPackage> = Vec::new();
+        if let Some(map) = &self.pypi {
+            for (pname, pypi_package) in map.clone() {
+                let pkg = match pypi_package {
+                    TomlPypiPackage::Simple(project) => PyPIProject::get(project, None).await,
+                    TomlPypiPackage::Detailed(details) => match details.pin {
+                        Some(version) => {
+                            PyPIProject::get_with_version(details.src, version, details.wheel).await
+                        }
+                        None => PyPIProject::get(details.src, details.wheel).await,
+                    },
+                };
+                match pkg.and_then(|pkg| pkg.to_nixpkg(pname.clone())) {
+                    Ok(pkg) => pypi.push(pkg),
+                    Err(e) => {
+                        return Err(eyre!("Unable to get package for {}", pname)
+                            .error(PackageError::DownloadPackageError))
+                        .note(e)
+                    }
+                }
+            }
+        }
+        Ok(pypi)
+    }
+
+    pub async fn get_npm_nixpkgs(&self) -> Result<Vec<NixPackage>> {
+        let mut npm: Vec<NixPackage> = Vec::new();
+        if let Some(map) = &self.npm {
+            for (pname, npm_package) in map.clone() {
+                let pkg = match npm_package {
+                    TomlNpmPackage::Simple(package) => NpmVersion::get(package, None).await,
+                    TomlNpmPackage::Detailed(details) => match details.pin {
+                        Some(version) => NpmVersion::get_with_version(details.src, version).await,
+                        None => NpmVersion::get(details.src, details.tag).await,
+                    },
+                };
+                match pkg.and_then(|pkg| pkg.to_nixpkg(pname.clone())) {
+                    Ok(pkg) => npm.push(pkg),
+                    Err(e) => {
+                        return Err(eyre!("Unable to get package for {}", pname)
+                            .error(PackageError::DownloadPackageError))
+                        .note(e)
+                    }
+                }
+            }
+        }
+        Ok(npm)
+    }
+
+    pub async fn get_amo_nixpkgs(&self) -> Result<Vec<NixPackage>> {
+        let mut amo: Vec<NixPackage> = Vec::new();
+        if let Some(map) = &self.amo {
+            for (pname, amo_package) in map.clone() {
+                let pkg = match amo_package {
+                    TomlAmoPackage::Simple(addon) => AmoAddon::get(addon).await,
+                    TomlAmoPackage::Detailed(details) => match details.pin {
+                        Some(version) => AmoAddon::get_with_version(details.src, version).await,
+                        None => AmoAddon::get(details.src).await,
+                    },
+                };
+                match pkg.and_then(|pkg| pkg.to_nixpkg(pname.clone())) {
+                    Ok(pkg) => amo.push(pkg),
+                    Err(e) => {
+                        return Err(eyre!("Unable to get package for {}", pname)
+                            .error(PackageError::DownloadPackageError))
+                        .note(e)
+                    }
+                }
+            }
+        }
+        Ok(amo)
+    }
+
+    pub async fn get_jetbrains_nixpkgs(&self) -> Result<Vec<NixPackage>> {
+        let mut jetbrains: Vec<NixPackage> = Vec::new();
+        let default_build = self
+            .settings
+            .as_ref()
+            .and_then(|settings| settings.jetbrains_build.clone());
+        if let Some(map) = &self.jetbrains {
+            for (pname, jetbrains_package) in map.clone() {
+                let pkg = match jetbrains_package {
+                    TomlJetBrainsPackage::Simple(plugin) => {
+                        JetBrainsPlugin::get(plugin, default_build.clone()).await
+                    }
+                    TomlJetBrainsPackage::Detailed(details) => {
+                        let build = details.build.or_else(|| default_build.clone());
+                        match details.pin {
+                            Some(version) => {
+                                JetBrainsPlugin::get_with_version(details.src, version, build).await
                             }
-                        };
-                        openvsx.push(pkg.to_nixpkg(pname, details.extract))
+                            None => JetBrainsPlugin::get(details.src, build).await,
+                        }
+                    }
+                };
+                match pkg.and_then(|pkg| pkg.to_nixpkg(pname.clone())) {
+                    Ok(pkg) => jetbrains.push(pkg),
+                    Err(e) => {
+                        return Err(eyre!("Unable to get package for {}", pname)
+                            .error(PackageError::DownloadPackageError))
+                        .note(e)
                     }
                 }
             }
-            Ok(openvsx)
-        } else {
-            Err(eyre!("There are not openvsx packages"))
+        }
+        Ok(jetbrains)
+    }
+
+    /// The GitHub instance of a package: its own `github_host`/`github_api_url`, or else
+    /// the settings', or else github.com.
+    pub fn github_host(&self, package: &TomlGitHubPackage) -> Result<GitHubHost> {
+        match package {
+            TomlGitHubPackage::Detailed(details)
+                if details.github_host.is_some() || details.github_api_url.is_some() =>
+            {
+                GitHubHost::new(
+                    details.github_host.as_deref(),
+                    details.github_api_url.as_deref(),
+                )
+            }
+            _ => match self.settings.as_deref() {
+                Some(settings) => GitHubHost::new(
+                    settings.github_host.as_deref(),
+                    settings.github_api_url.as_deref(),
+                ),
+                None => Ok(GitHubHost::default()),
+            },
+        }
+    }
+
+    /// Every GitHub instance packages are taken from.
+    pub fn github_hosts(&self) -> Result<Vec<GitHubHost>> {
+        let mut hosts = Vec::new();
+        for package in self.github.iter().flat_map(BTreeMap::values) {
+            let host = self.github_host(package)?;
+            if !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+        Ok(hosts)
+    }
+
+    /// Token files by GitHub host, `github_token_file` being the one of the settings' host.
+    pub fn github_token_files(&self) -> Result<BTreeMap<String, PathBuf>> {
+        let settings = self.settings.as_deref().cloned().unwrap_or_default();
+        let mut token_files = settings.github_token_files.clone();
+        if let Some(token_file) = settings.github_token_file {
+            let host = GitHubHost::new(
+                settings.github_host.as_deref(),
+                settings.github_api_url.as_deref(),
+            )?;
+            token_files.entry(host.host).or_insert(token_file);
+        }
+        Ok(token_files)
+    }
+
+    pub async fn get_github_nixpkgs(&self) -> Result<Vec<NixPackage>> {
+        let mut github: Vec<NixPackage> = Vec::new();
+        if let Some(map) = &self.github {
+            let mut hosts = BTreeMap::new();
+            for (pname, github_package) in map {
+                hosts.insert(pname.clone(), self.github_host(github_package)?);
+            }
+            let batch = GitHubBatch::get(map.iter().map(|(pname, github_package)| {
+                let src = match github_package {
+                    TomlGitHubPackage::Simple(src) => src.clone(),
+                    TomlGitHubPackage::Detailed(details) => details.src.clone(),
+                };
+                (hosts[pname].clone(), src)
+            }))
+            .await;
+            for (pname, github_package) in map.clone() {
+                let assets = match &github_package {
+                    TomlGitHubPackage::Detailed(details) => details.asset_selection(),
+                    TomlGitHubPackage::Simple(_) => None,
+                };
+                let (src, git_ref, builder) = git_source(github_package);
+                let pkg = match (batch.repo(&hosts[&pname], &src).await, assets) {
+                    (Ok(repo), Some(assets)) => GitHubReleaseSource::get(repo, git_ref, assets)
+                        .await
+                        .and_then(|pkg| pkg.to_nixpkg(pname.clone())),
+                    (Ok(repo), None) => GitHubSource::get(repo, git_ref)
+                        .await
+                        .and_then(|pkg| pkg.to_nixpkg(pname.clone(), builder)),
+                    (Err(e), _) => Err(e),
+                };
+                match pkg {
+                    Ok(pkg) => github.push(pkg),
+                    Err(e) => {
+                        return Err(eyre!("Unable to get package for {}", pname)
+                            .error(PackageError::DownloadPackageError))
+                        .note(e)
+                    }
+                }
+            }
+        }
+        Ok(github)
+    }
+
+    pub async fn get_gitlab_nixpkgs(&self) -> Result<Vec<NixPackage>> {
+        let mut gitlab: Vec<NixPackage> = Vec::new();
+        if let Some(map) = &self.gitlab {
+            for (pname, gitlab_package) in map.clone() {
+                let (src, git_ref, builder) = git_source(gitlab_package);
+                match GitLabSource::get(src, git_ref)
+                    .await
+                    .and_then(|pkg| pkg.to_nixpkg(pname.clone(), builder))
+                {
+                    Ok(pkg) => gitlab.push(pkg),
+                    Err(e) => {
+                        return Err(eyre!("Unable to get package for {}", pname)
+                            .error(PackageError::DownloadPackageError))
+                        .note(e)
+                    }
+                }
+            }
+        }
+        Ok(gitlab)
+    }
+
+    pub async fn get_git_nixpkgs(&self) -> Result<Vec<NixPackage>> {
+        let mut git: Vec<NixPackage> = Vec::new();
+        if let Some(map) = &self.git {
+            for (pname, git_package) in map.clone() {
+                let (src, git_ref, builder) = git_source(git_package);
+                match GitRepository::get(src, git_ref).await {
+                    Ok(pkg) => git.push(pkg.to_nixpkg(pname, builder)),
+                    Err(e) => {
+                        return Err(eyre!("Unable to get package for {}", pname)
+                            .error(PackageError::DownloadPackageError))
+                        .note(e)
+                    }
+                }
+            }
+        }
+        Ok(git)
+    }
+}
+
+/// The repository, ref and builder of a `[github]`, `[gitlab]` or `[git]` entry.
+fn git_source(package: TomlGitHubPackage) -> (String, GitRef, NixBuilder) {
+    match package {
+        TomlGitHubPackage::Simple(src) => (
+            src,
+            GitRef::LatestRelease {
+                prefix: None,
+                prerelease: false,
+            },
+            NixBuilder::default(),
+        ),
+        TomlGitHubPackage::Detailed(details) => {
+            let (git_ref, builder) = (details.git_ref(), details.builder());
+            (details.src, git_ref, builder)
         }
     }
 }
@@ -0,0 +1,93 @@
+use serde::de;
+use serde::{Deserialize, Serialize};
+use toml::value::Table;
+
+use std::fmt;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DetailedTomlCratesPackage {
+    pub src: String,
+    pub pin: Option<String>,
+    pub passthru: Option<Table>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum TomlCratesPackage {
+    /// In the simple format, only a crate name is specified, eg.
+    /// `package = "<crate>"`
+    Simple(String),
+    /// The simple format is equivalent to a detailed dependency
+    /// specifying only a crate name, eg.
+    /// `package = { src = "<crate>" }`
+    Detailed(DetailedTomlCratesPackage),
+}
+
+impl<'de> de::Deserialize<'de> for TomlCratesPackage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct TomlCratesPackageVisitor;
+
+        impl<'de> de::Visitor<'de> for TomlCratesPackageVisitor {
+            type Value = TomlCratesPackage;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str(
+                    "a crate name like \"ripgrep\" or a \
+                     detailed dependency like { src = \"ripgrep\", pin = \"13.0.0\" }",
+                )
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(TomlCratesPackage::Simple(s.to_owned()))
+            }
+
+            fn visit_map<V>(self, map: V) -> Result<Self::Value, V::Error>
+            where
+                V: de::MapAccess<'de>,
+            {
+                let mvd = de::value::MapAccessDeserializer::new(map);
+                DetailedTomlCratesPackage::deserialize(mvd).map(TomlCratesPackage::Detailed)
+            }
+        }
+
+        deserializer.deserialize_any(TomlCratesPackageVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_crates() {
+        let test_str = r#"
+		[crates]
+        ripgrep = "ripgrep"
+        fd = { src = "fd-find", pin = "8.2.1" }
+		"#;
+
+        let manifest_crates = crate::package::TomlManifest::from_str(test_str)
+            .unwrap()
+            .crates
+            .unwrap();
+
+        match manifest_crates.get("ripgrep").unwrap() {
+            TomlCratesPackage::Simple(s) => assert_eq!(s, &String::from("ripgrep")),
+            TomlCratesPackage::Detailed(_) => unreachable!(),
+        }
+
+        match manifest_crates.get("fd").unwrap() {
+            TomlCratesPackage::Simple(_) => unreachable!(),
+            TomlCratesPackage::Detailed(detailed) => {
+                assert_eq!(&detailed.src, "fd-find");
+                assert_eq!(detailed.pin.as_ref().unwrap(), &String::from("8.2.1"));
+                assert!(&detailed.passthru.is_none());
+            }
+        }
+    }
+}
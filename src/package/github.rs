@@ -2,9 +2,16 @@ use serde::de;
 use serde::{Deserialize, Serialize};
 use toml::value::Table;
 
-use std::fmt;
+use std::{collections::BTreeMap, fmt};
 
 use super::config::Overlay;
+use crate::{
+    nix::NixBuilder,
+    sources::{
+        git::GitRef,
+        github::{AssetPattern, AssetSelection},
+    },
+};
 
 impl<'de> de::Deserialize<'de> for GitHubPin {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -125,14 +132,84 @@ pub enum GitHubPin {
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct DetailedTomlGitHubPackage {
-    src: String,
-    latest: Option<GitHubLatest>,
-    pin: Option<GitHubPin>,
-    extract: Option<String>,
-    passthru: Option<Table>,
-    overlay: Option<Overlay>,
+    pub src: String,
+    pub latest: Option<GitHubLatest>,
+    pub pin: Option<GitHubPin>,
+    pub extract: Option<String>,
+    pub passthru: Option<Table>,
+    pub overlay: Option<Overlay>,
+    /// Release asset to build from, eg. `eww-{version}-x86_64-linux.tar.gz`, or a regex
+    /// between slashes
+    pub asset: Option<String>,
+    /// Release asset to build from for each system, eg. `assets.x86_64-linux = "..."`
+    pub assets: Option<BTreeMap<String, String>>,
+    /// GitHub Enterprise Server the repository lives on, instead of settings.github_host
+    pub github_host: Option<String>,
+    /// API of that server, when it is not at `https://{github_host}/api/v3`
+    pub github_api_url: Option<String>,
+    /// Follow pre-releases too when following the newest release
+    #[serde(default)]
+    pub prerelease: bool,
 }
 
+impl DetailedTomlGitHubPackage {
+    /// What to follow in the repository; a pin wins over `latest`, and `release:latest`
+    /// or a bare `release:` follow the newest release.
+    pub fn git_ref(&self) -> GitRef {
+        match (&self.pin, &self.latest) {
+            (Some(GitHubPin::ToRelease(tag)), _) => GitRef::Release(tag.clone()),
+            (Some(GitHubPin::ToTag(tag)), _) => GitRef::Tag(tag.clone()),
+            (Some(GitHubPin::ToCommit(sha)), _) => GitRef::Commit(sha.clone()),
+            (None, Some(GitHubLatest::FromBranch(branch))) => GitRef::Branch(Some(branch.clone())),
+            (None, Some(GitHubLatest::FromRelease(prefix))) => GitRef::LatestRelease {
+                prefix: Some(prefix.clone()).filter(|p| !p.is_empty() && p != "latest"),
+                prerelease: self.prerelease,
+            },
+            (None, None) => GitRef::LatestRelease {
+                prefix: None,
+                prerelease: self.prerelease,
+            },
+        }
+    }
+
+    /// The release assets to build a prebuilt package from, if any; `assets` wins over `asset`.
+    pub fn asset_selection(&self) -> Option<AssetSelection<AssetPattern>> {
+        match (&self.assets, &self.asset) {
+            (Some(assets), _) => Some(AssetSelection::PerSystem(
+                assets
+                    .iter()
+                    .map(|(system, pattern)| (system.clone(), AssetPattern::parse(pattern)))
+                    .collect(),
+            )),
+            (None, Some(asset)) => Some(AssetSelection::Any(AssetPattern::parse(asset))),
+            (None, None) => None,
+        }
+    }
+
+    /// Vim plugins are built with `vimUtils.buildVimPlugin`, depending on the plugins
+    /// listed in `passthru.dependencies`.
+    pub fn builder(&self) -> NixBuilder {
+        match self.overlay {
+            Some(Overlay::Vim) => NixBuilder::VimPlugin {
+                dependencies: self
+                    .passthru
+                    .as_ref()
+                    .and_then(|passthru| passthru.get("dependencies"))
+                    .and_then(|dependencies| dependencies.as_array())
+                    .map(|dependencies| {
+                        dependencies
+                            .iter()
+                            .filter_map(|d| d.as_str().map(String::from))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            },
+            _ => NixBuilder::MkDerivation,
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug, Serialize)]
 #[serde(untagged)]
 pub enum TomlGitHubPackage {
@@ -158,6 +235,26 @@ mod tests {
 
 		[github.sled]
 		src = "spacejam/sled"
+
+		[github.vim-fugitive]
+		src = "tpope/vim-fugitive"
+		latest = "branch:master"
+		overlay = "vim"
+		passthru = { dependencies = ["vim-rhubarb"] }
+
+		[github.eww-bin]
+		src = "elkowar/eww"
+		asset = "eww-{version}-x86_64-linux.tar.gz"
+		prerelease = true
+
+		[github.ripgrep-bin]
+		src = "BurntSushi/ripgrep"
+		assets.x86_64-linux = "ripgrep-{version}-x86_64-unknown-linux-musl.tar.gz"
+		assets.aarch64-darwin = "/^ripgrep-.*-aarch64-apple-darwin\\.tar\\.gz$/"
+
+		[github.internal-tool]
+		src = "platform/internal-tool"
+		github_host = "github.example.com"
 		"#;
 
         let manifest_github = crate::package::TomlManifest::from_str(test_str)
@@ -167,11 +264,11 @@ mod tests {
 
         match manifest_github.get("eww").unwrap() {
             TomlGitHubPackage::Simple(s) => assert_eq!(s, &String::from("elkowar/eww")),
-            TomlGitHubPackage::Detailed(_) => assert!(false),
+            TomlGitHubPackage::Detailed(_) => unreachable!(),
         }
 
         match manifest_github.get("nixos").unwrap() {
-            TomlGitHubPackage::Simple(_) => assert!(false),
+            TomlGitHubPackage::Simple(_) => unreachable!(),
             TomlGitHubPackage::Detailed(detailed) => {
                 assert_eq!(&detailed.src, "Nixos/nixpkgs");
                 assert!(&detailed.latest.is_none());
@@ -180,17 +277,17 @@ mod tests {
                 assert!(&detailed.overlay.is_none());
                 let pin = detailed.pin.as_ref().unwrap();
                 match pin {
-                    GitHubPin::ToRelease(_) => assert!(false),
+                    GitHubPin::ToRelease(_) => unreachable!(),
                     GitHubPin::ToCommit(s) => {
                         assert_eq!(s, &String::from("13aa00156246eec1043c795a9cd3f09dac6928fa"))
                     }
-                    GitHubPin::ToTag(_) => assert!(false),
+                    GitHubPin::ToTag(_) => unreachable!(),
                 }
             }
         }
 
         match manifest_github.get("gitlab").unwrap() {
-            TomlGitHubPackage::Simple(_) => assert!(false),
+            TomlGitHubPackage::Simple(_) => unreachable!(),
             TomlGitHubPackage::Detailed(detailed) => {
                 assert_eq!(&detailed.src, "GitLab.gitlab-workflow");
                 assert!(&detailed.pin.is_none());
@@ -199,7 +296,7 @@ mod tests {
                 let latest = detailed.latest.as_ref().unwrap();
                 match latest {
                     GitHubLatest::FromBranch(s) => assert_eq!(s, &String::from("main")),
-                    GitHubLatest::FromRelease(_) => assert!(false),
+                    GitHubLatest::FromRelease(_) => unreachable!(),
                 }
                 let pt = &detailed.passthru.as_ref().unwrap();
 
@@ -216,7 +313,7 @@ mod tests {
         }
 
         match manifest_github.get("sled").unwrap() {
-            TomlGitHubPackage::Simple(_) => assert!(false),
+            TomlGitHubPackage::Simple(_) => unreachable!(),
             TomlGitHubPackage::Detailed(detailed) => {
                 assert_eq!(&detailed.src, "spacejam/sled");
                 assert!(&detailed.latest.is_none());
@@ -224,7 +321,92 @@ mod tests {
                 assert!(&detailed.extract.is_none());
                 assert!(&detailed.passthru.is_none());
                 assert!(&detailed.overlay.is_none());
+                assert!(detailed.asset_selection().is_none());
+                assert_eq!(
+                    detailed.git_ref(),
+                    GitRef::LatestRelease {
+                        prefix: None,
+                        prerelease: false
+                    }
+                );
+                assert_eq!(detailed.builder(), NixBuilder::MkDerivation);
+            }
+        }
+
+        match manifest_github.get("vim-fugitive").unwrap() {
+            TomlGitHubPackage::Simple(_) => unreachable!(),
+            TomlGitHubPackage::Detailed(detailed) => {
+                assert_eq!(detailed.overlay, Some(Overlay::Vim));
+                assert_eq!(
+                    detailed.git_ref(),
+                    GitRef::Branch(Some(String::from("master")))
+                );
+                assert_eq!(
+                    detailed.builder(),
+                    NixBuilder::VimPlugin {
+                        dependencies: vec![String::from("vim-rhubarb")]
+                    }
+                );
+            }
+        }
+
+        match manifest_github.get("eww-bin").unwrap() {
+            TomlGitHubPackage::Simple(_) => unreachable!(),
+            TomlGitHubPackage::Detailed(detailed) => {
+                assert_eq!(
+                    detailed.asset_selection(),
+                    Some(AssetSelection::Any(AssetPattern::Name(String::from(
+                        "eww-{version}-x86_64-linux.tar.gz"
+                    ))))
+                );
+                assert_eq!(
+                    detailed.git_ref(),
+                    GitRef::LatestRelease {
+                        prefix: None,
+                        prerelease: true
+                    }
+                );
+            }
+        }
+
+        match manifest_github.get("ripgrep-bin").unwrap() {
+            TomlGitHubPackage::Simple(_) => unreachable!(),
+            TomlGitHubPackage::Detailed(detailed) => {
+                let mut assets = BTreeMap::new();
+                assets.insert(
+                    String::from("aarch64-darwin"),
+                    AssetPattern::Regex(String::from(
+                        r"^ripgrep-.*-aarch64-apple-darwin\.tar\.gz$",
+                    )),
+                );
+                assets.insert(
+                    String::from("x86_64-linux"),
+                    AssetPattern::Name(String::from(
+                        "ripgrep-{version}-x86_64-unknown-linux-musl.tar.gz",
+                    )),
+                );
+                assert_eq!(
+                    detailed.asset_selection(),
+                    Some(AssetSelection::PerSystem(assets))
+                );
+            }
+        }
+
+        let manifest = crate::package::TomlManifest::from_str(test_str).unwrap();
+        let internal_tool = manifest_github.get("internal-tool").unwrap();
+        match internal_tool {
+            TomlGitHubPackage::Simple(_) => unreachable!(),
+            TomlGitHubPackage::Detailed(detailed) => {
+                assert_eq!(detailed.github_host.as_deref(), Some("github.example.com"));
+                assert!(detailed.github_api_url.is_none());
             }
         }
+        let host = manifest.github_host(internal_tool).unwrap();
+        assert_eq!(host.api_url, "https://github.example.com/api/v3");
+        assert!(manifest
+            .github_host(manifest_github.get("eww").unwrap())
+            .unwrap()
+            .is_github_com());
+        assert_eq!(manifest.github_hosts().unwrap().len(), 2);
     }
 }
@@ -0,0 +1,97 @@
+use serde::de;
+use serde::{Deserialize, Serialize};
+use toml::value::Table;
+
+use std::fmt;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DetailedTomlJetBrainsPackage {
+    /// The plugin's id, eg. `8182` for https://plugins.jetbrains.com/plugin/8182-rust
+    pub src: String,
+    pub pin: Option<String>,
+    /// IDE build number the plugin has to be compatible with, overriding `settings.jetbrains_build`
+    pub build: Option<String>,
+    pub passthru: Option<Table>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum TomlJetBrainsPackage {
+    /// In the simple format, only a plugin id is specified, eg.
+    /// `package = "<id>"`
+    Simple(String),
+    /// The simple format is equivalent to a detailed dependency
+    /// specifying only a plugin id, eg.
+    /// `package = { src = "<id>" }`
+    Detailed(DetailedTomlJetBrainsPackage),
+}
+
+impl<'de> de::Deserialize<'de> for TomlJetBrainsPackage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct TomlJetBrainsPackageVisitor;
+
+        impl<'de> de::Visitor<'de> for TomlJetBrainsPackageVisitor {
+            type Value = TomlJetBrainsPackage;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str(
+                    "a plugin id like \"8182\" or a \
+                     detailed dependency like { src = \"8182\", build = \"IU-232.9921.47\" }",
+                )
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(TomlJetBrainsPackage::Simple(s.to_owned()))
+            }
+
+            fn visit_map<V>(self, map: V) -> Result<Self::Value, V::Error>
+            where
+                V: de::MapAccess<'de>,
+            {
+                let mvd = de::value::MapAccessDeserializer::new(map);
+                DetailedTomlJetBrainsPackage::deserialize(mvd).map(TomlJetBrainsPackage::Detailed)
+            }
+        }
+
+        deserializer.deserialize_any(TomlJetBrainsPackageVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_jetbrains() {
+        let test_str = r#"
+		[jetbrains]
+        rust = "8182"
+        ideavim = { src = "164-ideavim", build = "IU-232.9921.47" }
+		"#;
+
+        let manifest_jetbrains = crate::package::TomlManifest::from_str(test_str)
+            .unwrap()
+            .jetbrains
+            .unwrap();
+
+        match manifest_jetbrains.get("rust").unwrap() {
+            TomlJetBrainsPackage::Simple(s) => assert_eq!(s, "8182"),
+            TomlJetBrainsPackage::Detailed(_) => unreachable!(),
+        }
+
+        match manifest_jetbrains.get("ideavim").unwrap() {
+            TomlJetBrainsPackage::Simple(_) => unreachable!(),
+            TomlJetBrainsPackage::Detailed(detailed) => {
+                assert_eq!(&detailed.src, "164-ideavim");
+                assert_eq!(detailed.build.as_deref(), Some("IU-232.9921.47"));
+                assert!(detailed.pin.is_none());
+                assert!(&detailed.passthru.is_none());
+            }
+        }
+    }
+}
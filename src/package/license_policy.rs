@@ -0,0 +1,304 @@
+use serde::de;
+use serde::{Deserialize, Serialize, Serializer};
+use thiserror::Error;
+
+use std::fmt;
+
+use crate::nix::{NixLicense, NixPackage};
+
+/// Groups of licenses a policy can name at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LicenseFamily {
+    /// Licenses requiring changes to be shared under the same terms
+    Copyleft,
+    /// Licenses nixpkgs does not consider free, which need `allowUnfree`
+    Unfree,
+}
+
+impl LicenseFamily {
+    pub fn contains(self, license: NixLicense) -> bool {
+        match self {
+            LicenseFamily::Copyleft => matches!(
+                license,
+                NixLicense::Agpl3
+                    | NixLicense::Agpl3Only
+                    | NixLicense::Agpl3Plus
+                    | NixLicense::Apsl20
+                    | NixLicense::CcByNcSa20
+                    | NixLicense::CcByNcSa25
+                    | NixLicense::CcByNcSa30
+                    | NixLicense::CcByNcSa40
+                    | NixLicense::CcBySa25
+                    | NixLicense::CcBySa30
+                    | NixLicense::CcBySa40
+                    | NixLicense::Cddl
+                    | NixLicense::Cecill20
+                    | NixLicense::CecillC
+                    | NixLicense::Cpal10
+                    | NixLicense::Cpl10
+                    | NixLicense::Epl10
+                    | NixLicense::Epl20
+                    | NixLicense::Eupl11
+                    | NixLicense::Eupl12
+                    | NixLicense::Fdl11
+                    | NixLicense::Fdl11Only
+                    | NixLicense::Fdl11Plus
+                    | NixLicense::Fdl12
+                    | NixLicense::Fdl12Only
+                    | NixLicense::Fdl12Plus
+                    | NixLicense::Fdl13
+                    | NixLicense::Fdl13Only
+                    | NixLicense::Fdl13Plus
+                    | NixLicense::Gpl1
+                    | NixLicense::Gpl1Only
+                    | NixLicense::Gpl1Plus
+                    | NixLicense::Gpl2
+                    | NixLicense::Gpl2Classpath
+                    | NixLicense::Gpl2ClasspathPlus
+                    | NixLicense::Gpl2Only
+                    | NixLicense::Gpl2Oss
+                    | NixLicense::Gpl2Plus
+                    | NixLicense::Gpl3
+                    | NixLicense::Gpl3ClasspathPlus
+                    | NixLicense::Gpl3Only
+                    | NixLicense::Gpl3Plus
+                    | NixLicense::Ipl10
+                    | NixLicense::Lgpl2
+                    | NixLicense::Lgpl21
+                    | NixLicense::Lgpl21Only
+                    | NixLicense::Lgpl21Plus
+                    | NixLicense::Lgpl2Only
+                    | NixLicense::Lgpl2Plus
+                    | NixLicense::Lgpl3
+                    | NixLicense::Lgpl3Only
+                    | NixLicense::Lgpl3Plus
+                    | NixLicense::Lgpllr
+                    | NixLicense::Llgpl21
+                    | NixLicense::Mpl10
+                    | NixLicense::Mpl11
+                    | NixLicense::Mpl20
+                    | NixLicense::Nposl3
+                    | NixLicense::Odbl
+                    | NixLicense::Osl2
+                    | NixLicense::Osl21
+                    | NixLicense::Osl3
+                    | NixLicense::Parity70
+                    | NixLicense::Qpl
+                    | NixLicense::Sleepycat
+                    | NixLicense::Sspl
+            ),
+            LicenseFamily::Unfree => !license.is_free(),
+        }
+    }
+}
+
+/// A license, by its SPDX identifier or `lib.licenses` attribute, or a family of licenses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LicenseRule {
+    License(NixLicense),
+    Family(LicenseFamily),
+}
+
+impl LicenseRule {
+    pub fn matches(self, license: NixLicense) -> bool {
+        match self {
+            LicenseRule::License(rule) => rule == license,
+            LicenseRule::Family(family) => family.contains(license),
+        }
+    }
+}
+
+impl fmt::Display for LicenseRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LicenseRule::License(license) => f.write_str(license.nix_attr()),
+            LicenseRule::Family(LicenseFamily::Copyleft) => f.write_str("copyleft"),
+            LicenseRule::Family(LicenseFamily::Unfree) => f.write_str("unfree"),
+        }
+    }
+}
+
+impl Serialize for LicenseRule {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for LicenseRule {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct LicenseRuleVisitor;
+
+        impl<'de> de::Visitor<'de> for LicenseRuleVisitor {
+            type Value = LicenseRule;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str(
+                    "a license like \"MIT\" or \"unfreeRedistributable\", or a license family like \"copyleft\"",
+                )
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                match s {
+                    "copyleft" => return Ok(LicenseRule::Family(LicenseFamily::Copyleft)),
+                    "unfree" => return Ok(LicenseRule::Family(LicenseFamily::Unfree)),
+                    _ => (),
+                }
+                NixLicense::from_str(s)
+                    .or_else(|| NixLicense::from_nix_attr(s))
+                    .map(|license| LicenseRule::License(*license))
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Str(s), &self))
+            }
+        }
+
+        deserializer.deserialize_str(LicenseRuleVisitor)
+    }
+}
+
+/// Licenses packages may be shipped under, set in `[settings.license_policy]`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct LicensePolicy {
+    /// Licenses packages may use; when empty, any license not denied may be used
+    #[serde(default)]
+    pub allow: Vec<LicenseRule>,
+    /// Licenses packages may not use
+    #[serde(default)]
+    pub deny: Vec<LicenseRule>,
+    /// Whether packages without a recognised license break the policy
+    #[serde(default = "bool::default")]
+    pub require_known: bool,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum LicenseViolation {
+    #[error("{package} is licensed under {}, which is denied as {rule}", license.nix_attr())]
+    Denied {
+        package: String,
+        license: NixLicense,
+        rule: LicenseRule,
+    },
+    #[error("{package} is licensed under {}, which is not allowed", license.nix_attr())]
+    NotAllowed {
+        package: String,
+        license: NixLicense,
+    },
+    #[error("{package} has no recognised license")]
+    Unknown { package: String },
+}
+
+impl LicensePolicy {
+    /// Every way the packages break the policy. Licenses are checked one by one, as
+    /// nix does not tell whether a package's licenses apply together or as alternatives.
+    pub fn check(&self, packages: &[NixPackage]) -> Vec<LicenseViolation> {
+        let mut violations = Vec::new();
+        for package in packages {
+            let licenses = package.meta.license.as_deref().unwrap_or_default();
+            if licenses.is_empty() && self.require_known {
+                violations.push(LicenseViolation::Unknown {
+                    package: package.name.clone(),
+                });
+            }
+            for &license in licenses {
+                if let Some(&rule) = self.deny.iter().find(|rule| rule.matches(license)) {
+                    violations.push(LicenseViolation::Denied {
+                        package: package.name.clone(),
+                        license,
+                        rule,
+                    });
+                } else if !self.allow.is_empty()
+                    && !self.allow.iter().any(|rule| rule.matches(license))
+                {
+                    violations.push(LicenseViolation::NotAllowed {
+                        package: package.name.clone(),
+                        license,
+                    });
+                }
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::{NixBuilder, NixFetcher, NixPackageMeta};
+
+    fn package(name: &str, license: Option<Vec<NixLicense>>) -> NixPackage {
+        NixPackage {
+            name: String::from(name),
+            pname: String::from(name),
+            src: format!("https://example.com/{}.tar.gz", name),
+            version: String::from("1.0.0"),
+            sha256: String::new(),
+            meta: NixPackageMeta {
+                license,
+                ..Default::default()
+            },
+            fetcher: NixFetcher::FetchUrl { name: None },
+            builder: NixBuilder::MkDerivation,
+        }
+    }
+
+    #[test]
+    fn test_license_policy() {
+        let policy: LicensePolicy = toml::from_str(
+            r#"
+            allow = ["MIT", "asl20", "copyleft", "unfreeRedistributable"]
+            deny = ["AGPL-3.0-only", "unfree"]
+            require_known = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            policy.allow,
+            vec![
+                LicenseRule::License(NixLicense::Mit),
+                LicenseRule::License(NixLicense::Asl20),
+                LicenseRule::Family(LicenseFamily::Copyleft),
+                LicenseRule::License(NixLicense::UnfreeRedistributable),
+            ]
+        );
+
+        let packages = vec![
+            package("ok", Some(vec![NixLicense::Mit, NixLicense::Gpl3Plus])),
+            package("agpl", Some(vec![NixLicense::Agpl3Only])),
+            package("redist", Some(vec![NixLicense::UnfreeRedistributable])),
+            package("isc", Some(vec![NixLicense::Isc])),
+            package("unknown", None),
+        ];
+        assert_eq!(
+            policy.check(&packages),
+            vec![
+                LicenseViolation::Denied {
+                    package: String::from("agpl"),
+                    license: NixLicense::Agpl3Only,
+                    rule: LicenseRule::License(NixLicense::Agpl3Only),
+                },
+                LicenseViolation::Denied {
+                    package: String::from("redist"),
+                    license: NixLicense::UnfreeRedistributable,
+                    rule: LicenseRule::Family(LicenseFamily::Unfree),
+                },
+                LicenseViolation::NotAllowed {
+                    package: String::from("isc"),
+                    license: NixLicense::Isc,
+                },
+                LicenseViolation::Unknown {
+                    package: String::from("unknown"),
+                },
+            ]
+        );
+
+        assert!(LicensePolicy::default().check(&packages).is_empty());
+        assert!(toml::from_str::<LicensePolicy>(r#"deny = ["Beer"]"#).is_err());
+    }
+}
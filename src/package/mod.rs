@@ -1,7 +1,13 @@
+mod amo;
 mod config;
+mod crates;
 mod error;
 mod github;
+mod jetbrains;
+mod license_policy;
+mod npm;
 mod openvsx;
+mod pypi;
 mod settings;
 mod vsmarketplace;
 
@@ -0,0 +1,96 @@
+use serde::de;
+use serde::{Deserialize, Serialize};
+use toml::value::Table;
+
+use std::fmt;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DetailedTomlNpmPackage {
+    pub src: String,
+    /// dist-tag to follow, defaults to `latest`
+    pub tag: Option<String>,
+    pub pin: Option<String>,
+    pub passthru: Option<Table>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum TomlNpmPackage {
+    /// In the simple format, only a package name is specified, eg.
+    /// `package = "<package>"`
+    Simple(String),
+    /// The simple format is equivalent to a detailed dependency
+    /// specifying only a package name, eg.
+    /// `package = { src = "<package>" }`
+    Detailed(DetailedTomlNpmPackage),
+}
+
+impl<'de> de::Deserialize<'de> for TomlNpmPackage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct TomlNpmPackageVisitor;
+
+        impl<'de> de::Visitor<'de> for TomlNpmPackageVisitor {
+            type Value = TomlNpmPackage;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str(
+                    "a package name like \"typescript-language-server\" or a \
+                     detailed dependency like { src = \"typescript\", tag = \"next\" }",
+                )
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(TomlNpmPackage::Simple(s.to_owned()))
+            }
+
+            fn visit_map<V>(self, map: V) -> Result<Self::Value, V::Error>
+            where
+                V: de::MapAccess<'de>,
+            {
+                let mvd = de::value::MapAccessDeserializer::new(map);
+                DetailedTomlNpmPackage::deserialize(mvd).map(TomlNpmPackage::Detailed)
+            }
+        }
+
+        deserializer.deserialize_any(TomlNpmPackageVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_npm() {
+        let test_str = r#"
+		[npm]
+        typescript-language-server = "typescript-language-server"
+        "@vue/language-server" = { src = "@vue/language-server", tag = "next" }
+		"#;
+
+        let manifest_npm = crate::package::TomlManifest::from_str(test_str)
+            .unwrap()
+            .npm
+            .unwrap();
+
+        match manifest_npm.get("typescript-language-server").unwrap() {
+            TomlNpmPackage::Simple(s) => assert_eq!(s, "typescript-language-server"),
+            TomlNpmPackage::Detailed(_) => unreachable!(),
+        }
+
+        match manifest_npm.get("@vue/language-server").unwrap() {
+            TomlNpmPackage::Simple(_) => unreachable!(),
+            TomlNpmPackage::Detailed(detailed) => {
+                assert_eq!(&detailed.src, "@vue/language-server");
+                assert_eq!(detailed.tag.as_deref(), Some("next"));
+                assert!(detailed.pin.is_none());
+                assert!(&detailed.passthru.is_none());
+            }
+        }
+    }
+}
@@ -19,6 +19,12 @@ pub struct DetailedTomlOpenVSXPackage {
     #[serde(default = "default_overlay")]
     pub overlay: Option<Overlay>,
     pub passthru: Option<Table>,
+    /// VS Code version the extension has to be compatible with, overriding
+    /// `settings.vscode_version`
+    pub vscode_version: Option<String>,
+    /// Allow pre-release versions
+    #[serde(default)]
+    pub prerelease: bool,
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -78,6 +84,7 @@ mod tests {
         let test_str = r#"
 		[openvsx]
         dart = "Dart-Code.dart-code"
+        rust-analyzer = { src = "rust-lang.rust-analyzer", vscode_version = "1.85.2", prerelease = true }
         gitlab = {src = "GitLab.gitlab-workflow", pin = "3.28.1", passthru = { license = "mit", homepage = "https://open-vsx.org/extension/GitLab/gitlab-workflow", description = "GitLab VSCode integration" } }
 		"#;
 
@@ -97,6 +104,8 @@ mod tests {
                 assert_eq!(&detailed.src, "GitLab.gitlab-workflow");
                 assert_eq!(detailed.pin.as_ref().unwrap(), &String::from("3.28.1"));
                 assert!(&detailed.extract.is_none());
+                assert!(&detailed.vscode_version.is_none());
+                assert!(!detailed.prerelease);
 
                 let pt = &detailed.passthru.as_ref().unwrap();
                 assert_eq!(pt.get("license").unwrap().as_str().unwrap(), "mit");
@@ -115,5 +124,14 @@ mod tests {
                 }
             }
         }
+
+        match manifest_openvsx.get("rust-analyzer").unwrap() {
+            TomlOpenVSXPackage::Simple(_) => unreachable!(),
+            TomlOpenVSXPackage::Detailed(detailed) => {
+                assert_eq!(&detailed.src, "rust-lang.rust-analyzer");
+                assert_eq!(detailed.vscode_version.as_deref(), Some("1.85.2"));
+                assert!(detailed.prerelease);
+            }
+        }
     }
 }
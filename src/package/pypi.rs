@@ -0,0 +1,96 @@
+use serde::de;
+use serde::{Deserialize, Serialize};
+use toml::value::Table;
+
+use std::fmt;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DetailedTomlPypiPackage {
+    pub src: String,
+    pub pin: Option<String>,
+    /// Compatibility tag of the wheel to use instead of the sdist, eg. `py3-none-any`
+    pub wheel: Option<String>,
+    pub passthru: Option<Table>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum TomlPypiPackage {
+    /// In the simple format, only a project name is specified, eg.
+    /// `package = "<project>"`
+    Simple(String),
+    /// The simple format is equivalent to a detailed dependency
+    /// specifying only a project name, eg.
+    /// `package = { src = "<project>" }`
+    Detailed(DetailedTomlPypiPackage),
+}
+
+impl<'de> de::Deserialize<'de> for TomlPypiPackage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct TomlPypiPackageVisitor;
+
+        impl<'de> de::Visitor<'de> for TomlPypiPackageVisitor {
+            type Value = TomlPypiPackage;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str(
+                    "a project name like \"requests\" or a \
+                     detailed dependency like { src = \"requests\", pin = \"2.26.0\" }",
+                )
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(TomlPypiPackage::Simple(s.to_owned()))
+            }
+
+            fn visit_map<V>(self, map: V) -> Result<Self::Value, V::Error>
+            where
+                V: de::MapAccess<'de>,
+            {
+                let mvd = de::value::MapAccessDeserializer::new(map);
+                DetailedTomlPypiPackage::deserialize(mvd).map(TomlPypiPackage::Detailed)
+            }
+        }
+
+        deserializer.deserialize_any(TomlPypiPackageVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_pypi() {
+        let test_str = r#"
+		[pypi]
+        requests = "requests"
+        black = { src = "black", pin = "21.7b0", wheel = "py3-none-any" }
+		"#;
+
+        let manifest_pypi = crate::package::TomlManifest::from_str(test_str)
+            .unwrap()
+            .pypi
+            .unwrap();
+
+        match manifest_pypi.get("requests").unwrap() {
+            TomlPypiPackage::Simple(s) => assert_eq!(s, &String::from("requests")),
+            TomlPypiPackage::Detailed(_) => unreachable!(),
+        }
+
+        match manifest_pypi.get("black").unwrap() {
+            TomlPypiPackage::Simple(_) => unreachable!(),
+            TomlPypiPackage::Detailed(detailed) => {
+                assert_eq!(&detailed.src, "black");
+                assert_eq!(detailed.pin.as_deref(), Some("21.7b0"));
+                assert_eq!(detailed.wheel.as_deref(), Some("py3-none-any"));
+                assert!(&detailed.passthru.is_none());
+            }
+        }
+    }
+}
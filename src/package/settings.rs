@@ -1,8 +1,12 @@
-use serde::de;
 use serde::{Deserialize, Serialize};
-use toml::value::Table;
 
-use std::fmt;
+use std::{collections::BTreeMap, path::PathBuf};
+
+use super::license_policy::LicensePolicy;
+
+fn default_output() -> PathBuf {
+    PathBuf::from("_sources")
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct TomlSettings {
@@ -12,6 +16,55 @@ pub struct TomlSettings {
     pub create_overlay: bool,
     #[serde(default = "bool::default")]
     pub create_package: bool,
+    /// Directory generated expressions are written to
+    #[serde(default = "default_output")]
+    pub output: PathBuf,
+    /// IDE build number, eg. `IU-232.9921.47`, JetBrains plugins have to be compatible with
+    pub jetbrains_build: Option<String>,
+    /// VS Code version extensions have to be compatible with, eg. `1.89.1`
+    pub vscode_version: Option<String>,
+    /// nixpkgs commit or branch to read the VS Code version from, when `vscode_version`
+    /// is not set, eg. `nixos-24.05`
+    pub vscode_nixpkgs: Option<String>,
+    /// Add the Open VSX extensions that extensions depend on or bundle in a pack,
+    /// instead of expecting them in nixpkgs
+    #[serde(default = "bool::default")]
+    pub add_extension_dependencies: bool,
+    /// File holding a token for `github_host`, used when `GITHUB_TOKEN` is not set
+    pub github_token_file: Option<PathBuf>,
+    /// GitHub Enterprise Server repositories live on, instead of github.com
+    pub github_host: Option<String>,
+    /// API of that server, when it is not at `https://{github_host}/api/v3`
+    pub github_api_url: Option<String>,
+    /// Files holding tokens for other GitHub hosts, by host
+    #[serde(default)]
+    pub github_token_files: BTreeMap<String, PathBuf>,
+    /// Licenses packages may and may not be shipped under
+    pub license_policy: Option<LicensePolicy>,
+    /// Set `meta.broken` on deprecated packages, rather than only warning about them
+    #[serde(default = "bool::default")]
+    pub mark_deprecated_broken: bool,
+}
+
+impl Default for TomlSettings {
+    fn default() -> Self {
+        TomlSettings {
+            create_flake: false,
+            create_overlay: false,
+            create_package: false,
+            output: default_output(),
+            jetbrains_build: None,
+            vscode_version: None,
+            vscode_nixpkgs: None,
+            add_extension_dependencies: false,
+            github_token_file: None,
+            github_host: None,
+            github_api_url: None,
+            github_token_files: BTreeMap::new(),
+            license_policy: None,
+            mark_deprecated_broken: false,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -25,6 +78,19 @@ mod tests {
         create_flake = false
         create_overlay = true
         create_package = true
+        output = "pkgs/generated"
+        jetbrains_build = "IU-232.9921.47"
+        vscode_version = "1.89.1"
+        vscode_nixpkgs = "nixos-24.05"
+        add_extension_dependencies = true
+        mark_deprecated_broken = true
+        github_token_file = "/run/secrets/github-token"
+        github_host = "github.example.com"
+        github_token_files."github.internal.example.com" = "/run/secrets/ghes-token"
+
+        [settings.license_policy]
+        deny = ["unfree", "AGPL-3.0-only"]
+        require_known = true
         "#;
 
         let test_str2 = r#"
@@ -41,6 +107,35 @@ mod tests {
         assert!(!manifest_settings.create_flake);
         assert!(manifest_settings.create_overlay);
         assert!(manifest_settings.create_package);
+        assert_eq!(manifest_settings.output, PathBuf::from("pkgs/generated"));
+        assert_eq!(
+            manifest_settings.jetbrains_build.as_deref(),
+            Some("IU-232.9921.47")
+        );
+        assert_eq!(manifest_settings.vscode_version.as_deref(), Some("1.89.1"));
+        assert_eq!(
+            manifest_settings.vscode_nixpkgs.as_deref(),
+            Some("nixos-24.05")
+        );
+        assert!(manifest_settings.add_extension_dependencies);
+        assert!(manifest_settings.mark_deprecated_broken);
+        assert_eq!(
+            manifest_settings.github_token_file,
+            Some(PathBuf::from("/run/secrets/github-token"))
+        );
+        assert_eq!(
+            manifest_settings.github_host.as_deref(),
+            Some("github.example.com")
+        );
+        assert!(manifest_settings.github_api_url.is_none());
+        assert_eq!(
+            manifest_settings.github_token_files["github.internal.example.com"],
+            PathBuf::from("/run/secrets/ghes-token")
+        );
+        let license_policy = manifest_settings.license_policy.unwrap();
+        assert!(license_policy.allow.is_empty());
+        assert_eq!(license_policy.deny.len(), 2);
+        assert!(license_policy.require_known);
 
         let manifest2 = crate::package::TomlManifest::from_str(test_str2).unwrap();
         assert!(manifest2.github.is_none());
@@ -51,5 +146,15 @@ mod tests {
         assert!(manifest_settings2.create_flake);
         assert!(!manifest_settings2.create_overlay);
         assert!(!manifest_settings2.create_package);
+        assert_eq!(manifest_settings2.output, PathBuf::from("_sources"));
+        assert!(manifest_settings2.github_token_file.is_none());
+        assert!(manifest_settings2.github_host.is_none());
+        assert!(manifest_settings2.github_token_files.is_empty());
+        assert!(manifest_settings2.jetbrains_build.is_none());
+        assert!(manifest_settings2.vscode_version.is_none());
+        assert!(manifest_settings2.vscode_nixpkgs.is_none());
+        assert!(!manifest_settings2.add_extension_dependencies);
+        assert!(!manifest_settings2.mark_deprecated_broken);
+        assert!(manifest_settings2.license_policy.is_none());
     }
 }
@@ -13,12 +13,18 @@ fn default_overlay() -> Option<Overlay> {
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct DetailedTomlVSCodeMarketPlacePackage {
-    src: String,
-    pin: Option<String>,
-    extract: Option<String>,
+    pub src: String,
+    pub pin: Option<String>,
+    pub extract: Option<String>,
     #[serde(default = "default_overlay")]
-    overlay: Option<Overlay>,
-    passthru: Option<Table>,
+    pub overlay: Option<Overlay>,
+    pub passthru: Option<Table>,
+    /// VS Code version the extension has to be compatible with, overriding
+    /// `settings.vscode_version`
+    pub vscode_version: Option<String>,
+    /// Allow pre-release versions
+    #[serde(default)]
+    pub prerelease: bool,
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -80,6 +86,7 @@ mod tests {
 		[vsmarketplace]
         dart = "Dart-Code.dart-code"
         gitlab = {src = "GitLab.gitlab-workflow", pin = "3.28.1", passthru = { license = "mit", homepage = "https://open-vsx.org/extension/GitLab/gitlab-workflow", description = "GitLab VSCode integration" } }
+        cpptools = { src = "ms-vscode.cpptools", vscode_version = "1.85.2", prerelease = true }
 		"#;
 
         let manifest_vsmarketplace = crate::package::TomlManifest::from_str(test_str)
@@ -100,6 +107,8 @@ mod tests {
                 assert_eq!(&detailed.src, "GitLab.gitlab-workflow");
                 assert_eq!(detailed.pin.as_ref().unwrap(), &String::from("3.28.1"));
                 assert!(&detailed.extract.is_none());
+                assert!(detailed.vscode_version.is_none());
+                assert!(!detailed.prerelease);
 
                 let pt = &detailed.passthru.as_ref().unwrap();
                 assert_eq!(pt.get("license").unwrap().as_str().unwrap(), "mit");
@@ -118,5 +127,14 @@ mod tests {
                 }
             }
         }
+
+        match manifest_vsmarketplace.get("cpptools").unwrap() {
+            TomlVSCodeMarketPlacePackage::Simple(_) => unreachable!(),
+            TomlVSCodeMarketPlacePackage::Detailed(detailed) => {
+                assert_eq!(&detailed.src, "ms-vscode.cpptools");
+                assert_eq!(detailed.vscode_version.as_deref(), Some("1.85.2"));
+                assert!(detailed.prerelease);
+            }
+        }
     }
 }
@@ -0,0 +1,305 @@
+use serde::{Deserialize, Serialize};
+
+use color_eyre::{
+    eyre::{eyre, Report, Result},
+    Section,
+};
+use tracing::warn;
+
+use std::collections::BTreeMap;
+
+use crate::nix::{
+    hex_to_nix_base32, LicenseConfidence, NixBuilder, NixFetcher, NixLicense, NixPackage,
+    NixPackageMeta, PackageLinks,
+};
+
+const AMO_API: &str = "https://addons.mozilla.org/api/v5/addons/addon";
+
+/// A translated field, which the API returns keyed by locale.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum AmoLocalized {
+    Plain(String),
+    Locales(BTreeMap<String, Option<String>>),
+}
+
+impl AmoLocalized {
+    /// The `en-US` text, or the add-on's default locale when there is none.
+    fn text(&self) -> Option<&str> {
+        match self {
+            AmoLocalized::Plain(s) => Some(s.as_str()),
+            AmoLocalized::Locales(locales) => locales
+                .get("en-US")
+                .and_then(Option::as_deref)
+                .or_else(|| locales.values().find_map(Option::as_deref)),
+        }
+    }
+}
+
+/// /api/v5/addons/addon/{slug|guid}/
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AmoAddon {
+    pub guid: String,
+    pub slug: String,
+    pub name: Option<AmoLocalized>,
+    pub summary: Option<AmoLocalized>,
+    pub homepage: Option<AmoHomepage>,
+    pub url: String,
+    #[serde(default)]
+    pub is_disabled: bool,
+    pub status: String,
+    pub current_version: AmoVersion,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AmoHomepage {
+    pub url: Option<AmoLocalized>,
+}
+
+/// /api/v5/addons/addon/{slug|guid}/versions/{version}/
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AmoVersion {
+    pub version: String,
+    pub license: Option<AmoLicense>,
+    pub file: AmoFile,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AmoLicense {
+    #[serde(default)]
+    pub is_custom: bool,
+    pub name: Option<AmoLocalized>,
+    pub slug: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AmoFile {
+    /// `sha256:<hex>`
+    pub hash: String,
+    pub url: String,
+}
+
+impl AmoLicense {
+    fn to_nix(&self) -> Option<NixLicense> {
+        match self.slug.as_deref() {
+            Some("all-rights-reserved") | Some("cc-all-rights-reserved") => {
+                Some(NixLicense::Unfree)
+            }
+            Some(slug) => NixLicense::from_str(slug).copied(),
+            None => None,
+        }
+    }
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(url: String, addon: &str) -> Result<T, Report> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .header("User-Agent", env!("CARGO_PKG_NAME"))
+        .send()
+        .await?;
+
+    let resp_status = response.status();
+
+    if resp_status.is_success() {
+        match response.json().await {
+            Ok(amo_response) => Ok(amo_response),
+            Err(e) => Err(eyre!("Unable to parse json from addons.mozilla.org")).error(e),
+        }
+    } else if let Some(reason) = resp_status.canonical_reason() {
+        Err(eyre!(
+            "Recieved {}, while attempting to get {} from addons.mozilla.org.",
+            reason,
+            addon
+        ))
+    } else {
+        Err(eyre!("{}", resp_status.to_string()))
+    }
+}
+
+impl AmoAddon {
+    pub async fn get(addon: String) -> Result<Self, Report> {
+        get_json(format!("{}/{}/", AMO_API, addon), &addon).await
+    }
+
+    pub async fn get_with_version(addon: String, version: String) -> Result<Self, Report> {
+        let mut amo_addon = Self::get(addon.clone()).await?;
+        amo_addon.current_version = get_json(
+            format!("{}/{}/versions/{}/", AMO_API, addon, version),
+            &addon,
+        )
+        .await?;
+        Ok(amo_addon)
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_nixpkg(self, pname: String) -> Result<NixPackage, Report> {
+        let file = &self.current_version.file;
+        let sha256 = match file
+            .hash
+            .strip_prefix("sha256:")
+            .and_then(hex_to_nix_base32)
+        {
+            Some(sha256) => sha256,
+            None => {
+                return Err(eyre!(
+                    "Unsupported hash for {} {}: {}",
+                    self.slug,
+                    self.current_version.version,
+                    file.hash
+                ))
+            }
+        };
+
+        if self.is_disabled || self.status != "public" {
+            warn!(
+                "{} is no longer publicly listed on addons.mozilla.org",
+                self.slug
+            );
+        }
+
+        let license = match &self.current_version.license {
+            Some(license) => {
+                let nix_license = license.to_nix();
+                if nix_license.is_none() {
+                    warn!(
+                        "{}: unable to map license {} to a nix license",
+                        pname,
+                        license
+                            .name
+                            .as_ref()
+                            .and_then(AmoLocalized::text)
+                            .unwrap_or("(custom)")
+                    );
+                }
+                nix_license.map(|l| vec![l])
+            }
+            None => None,
+        };
+
+        let homepage = self
+            .homepage
+            .as_ref()
+            .and_then(|h| h.url.as_ref())
+            .and_then(AmoLocalized::text)
+            .map(String::from);
+
+        let meta = NixPackageMeta {
+            description: self
+                .summary
+                .as_ref()
+                .and_then(AmoLocalized::text)
+                .map(|s| s.trim().to_string()),
+            license_confidence: license.as_ref().map(|_| LicenseConfidence::Spdx),
+            license,
+            ..NixPackageMeta::from_links(PackageLinks {
+                homepage,
+                repository: None,
+                store_page: Some(self.url.clone()),
+            })
+        };
+
+        Ok(NixPackage {
+            name: pname.clone(),
+            pname,
+            src: self.current_version.file.url,
+            version: self.current_version.version,
+            sha256,
+            meta,
+            fetcher: NixFetcher::default(),
+            builder: NixBuilder::FirefoxXpiAddon {
+                addon_id: self.guid,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use serde_json::json;
+
+    fn ublock_origin() -> AmoAddon {
+        serde_json::from_value(json!({
+            "id": 607454,
+            "guid": "uBlock0@raymondhill.net",
+            "slug": "ublock-origin",
+            "name": { "en-US": "uBlock Origin" },
+            "summary": { "en-US": "Finally, an efficient wide-spectrum content blocker. Easy on CPU and memory." },
+            "homepage": {
+                "url": { "en-US": "https://github.com/gorhill/uBlock#ublock-origin" },
+                "outgoing": { "en-US": "https://outgoing.prod.mozaws.net/v1/0/https%3A//github.com/gorhill/uBlock" }
+            },
+            "url": "https://addons.mozilla.org/en-US/firefox/addon/ublock-origin/",
+            "is_disabled": false,
+            "status": "public",
+            "current_version": {
+                "id": 5646534,
+                "version": "1.52.2",
+                "license": {
+                    "id": 6,
+                    "is_custom": false,
+                    "name": { "en-US": "GNU General Public License v3.0" },
+                    "slug": "GPL-3.0-or-later",
+                    "url": "http://www.gnu.org/licenses/gpl-3.0.html"
+                },
+                "file": {
+                    "id": 4171020,
+                    "hash": "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                    "url": "https://addons.mozilla.org/firefox/downloads/file/4171020/ublock_origin-1.52.2.xpi"
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_amo_to_nixpkg() {
+        let pkg = ublock_origin()
+            .to_nixpkg(String::from("ublock-origin"))
+            .unwrap();
+
+        assert_eq!(pkg.version, "1.52.2");
+        assert_eq!(
+            pkg.sha256,
+            "0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73"
+        );
+        assert_eq!(pkg.meta.license, Some(vec![NixLicense::Gpl3Plus]));
+        assert_eq!(
+            pkg.meta.homepage.as_deref(),
+            Some("https://github.com/gorhill/uBlock#ublock-origin")
+        );
+        assert_eq!(
+            pkg.meta.download_page.as_deref(),
+            Some("https://addons.mozilla.org/en-US/firefox/addon/ublock-origin/")
+        );
+
+        let nix = pkg.to_nix();
+        assert!(nix.starts_with("{ buildFirefoxXpiAddon, lib }:"));
+        assert!(nix.contains("  addonId = \"uBlock0@raymondhill.net\";\n"));
+        assert!(nix.contains(
+            "  url = \"https://addons.mozilla.org/firefox/downloads/file/4171020/ublock_origin-1.52.2.xpi\";\n"
+        ));
+        assert!(!nix.contains("src ="));
+    }
+
+    #[test]
+    fn test_amo_license() {
+        let mut addon = ublock_origin();
+        addon.current_version.license = Some(AmoLicense {
+            is_custom: false,
+            name: Some(AmoLocalized::Plain(String::from("All Rights Reserved"))),
+            slug: Some(String::from("all-rights-reserved")),
+            url: None,
+        });
+        assert_eq!(
+            addon
+                .to_nixpkg(String::from("ublock-origin"))
+                .unwrap()
+                .meta
+                .license,
+            Some(vec![NixLicense::Unfree])
+        );
+    }
+}
@@ -0,0 +1,566 @@
+use serde::{Deserialize, Serialize};
+
+use color_eyre::{
+    eyre::{eyre, Report, Result, WrapErr},
+    Section,
+};
+use semver::Version;
+use tokio::{runtime::Handle, task};
+use tracing::warn;
+
+use std::{collections::BTreeMap, fs, path::Path, process::Command};
+
+use crate::{
+    nix::{
+        CargoLock, LicenseConfidence, NixBuilder, NixFetcher, NixLicense, NixPackage,
+        NixPackageMeta, PackageLinks,
+    },
+    sources::{download, git::GitRepository, nix_hash, unpack_tarball},
+};
+
+const CRATES_IO_API: &str = "https://crates.io/api/v1/crates";
+const CRATES_IO_INDEX: &str = "https://index.crates.io";
+const CRATES_IO: &str = "https://crates.io/crates";
+
+/// /api/v1/crates/{crate}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CratesIoResponse {
+    #[serde(rename = "crate")]
+    pub krate: CratesIoCrateInfo,
+    pub versions: Vec<CratesIoVersion>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CratesIoCrateInfo {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub documentation: Option<String>,
+    pub repository: Option<String>,
+    pub max_version: String,
+    pub max_stable_version: Option<String>,
+    pub newest_version: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CratesIoVersion {
+    pub id: u64,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub num: String,
+    pub dl_path: String,
+    pub checksum: String,
+    pub yanked: bool,
+    pub license: Option<String>,
+    pub crate_size: Option<u64>,
+    pub created_at: String,
+}
+
+/// A line of a crate's file in the sparse index, one per version.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CratesIndexVersion {
+    pub name: String,
+    pub vers: String,
+    pub cksum: String,
+    pub yanked: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CratesIoCrate {
+    pub name: String,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub repository: Option<String>,
+    pub version: String,
+    pub license: Option<String>,
+    pub download_url: String,
+}
+
+impl CratesIoResponse {
+    async fn get(crate_name: &str) -> Result<Self, Report> {
+        let response = reqwest::Client::new()
+            .get(format!("{}/{}", CRATES_IO_API, crate_name))
+            .header("User-Agent", env!("CARGO_PKG_NAME"))
+            .send()
+            .await?;
+
+        let resp_status = response.status();
+
+        if resp_status.is_success() {
+            match response.json().await {
+                Ok(crate_response) => Ok(crate_response),
+                Err(e) => Err(eyre!("Unable to parse json from crates.io")).error(e),
+            }
+        } else if let Some(reason) = resp_status.canonical_reason() {
+            Err(eyre!(
+                "Recieved {}, while attempting to get {} from crates.io.",
+                reason,
+                crate_name
+            ))
+        } else {
+            Err(eyre!("{}", resp_status.to_string()))
+        }
+    }
+
+    /// The crate from the API, or else from the sparse index, which only knows its
+    /// versions.
+    async fn get_or_index(crate_name: &str) -> Result<Self, Report> {
+        match Self::get(crate_name).await {
+            Ok(response) => Ok(response),
+            Err(e) => {
+                warn!(
+                    "{}, looking {} up in the sparse index instead",
+                    e, crate_name
+                );
+                Self::get_from_index(crate_name).await
+            }
+        }
+    }
+
+    async fn get_from_index(crate_name: &str) -> Result<Self, Report> {
+        let response = reqwest::Client::new()
+            .get(format!("{}/{}", CRATES_IO_INDEX, index_path(crate_name)))
+            .header("User-Agent", env!("CARGO_PKG_NAME"))
+            .send()
+            .await?;
+
+        let resp_status = response.status();
+
+        if resp_status.is_success() {
+            Self::from_index(&response.text().await?)
+        } else if let Some(reason) = resp_status.canonical_reason() {
+            Err(eyre!(
+                "Recieved {}, while attempting to get {} from the crates.io index.",
+                reason,
+                crate_name
+            ))
+        } else {
+            Err(eyre!("{}", resp_status.to_string()))
+        }
+    }
+
+    /// A crate's file in the sparse index, without the metadata only the API has.
+    fn from_index(index_file: &str) -> Result<Self, Report> {
+        let versions = index_file
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<CratesIndexVersion>, _>>()
+            .wrap_err("Unable to parse the crates.io index")?;
+        let name = match versions.last() {
+            Some(version) => version.name.clone(),
+            None => return Err(eyre!("No versions in the crates.io index")),
+        };
+        let newest = versions
+            .last()
+            .map(|version| version.vers.clone())
+            .unwrap_or_default();
+
+        Ok(CratesIoResponse {
+            krate: CratesIoCrateInfo {
+                id: name.clone(),
+                name: name.clone(),
+                description: None,
+                homepage: None,
+                documentation: None,
+                repository: None,
+                max_version: newest.clone(),
+                max_stable_version: None,
+                newest_version: newest,
+                updated_at: String::new(),
+            },
+            versions: versions
+                .into_iter()
+                .map(|version| CratesIoVersion {
+                    id: 0,
+                    dl_path: format!("/api/v1/crates/{}/{}/download", name, version.vers),
+                    krate: version.name,
+                    num: version.vers,
+                    checksum: version.cksum,
+                    yanked: version.yanked,
+                    license: None,
+                    crate_size: None,
+                    created_at: String::new(),
+                })
+                .collect(),
+        })
+    }
+
+    /// The newest version that has not been yanked, preferring stable releases.
+    fn newest_version(&self) -> Option<&CratesIoVersion> {
+        let candidates: Vec<(Version, &CratesIoVersion)> = self
+            .versions
+            .iter()
+            .filter(|v| !v.yanked)
+            .filter_map(|v| Version::parse(&v.num).ok().map(|semver| (semver, v)))
+            .collect();
+
+        let stable = candidates
+            .iter()
+            .filter(|(semver, _)| semver.pre.is_empty())
+            .max_by(|(a, _), (b, _)| a.cmp(b));
+
+        stable
+            .or_else(|| candidates.iter().max_by(|(a, _), (b, _)| a.cmp(b)))
+            .map(|(_, v)| *v)
+    }
+
+    fn refine(self, version: &CratesIoVersion) -> CratesIoCrate {
+        CratesIoCrate {
+            download_url: format!(
+                "{}/{}/{}/download",
+                CRATES_IO_API, self.krate.name, version.num
+            ),
+            name: self.krate.name,
+            description: self.krate.description,
+            homepage: self.krate.homepage,
+            repository: self.krate.repository,
+            version: version.num.clone(),
+            license: version.license.clone(),
+        }
+    }
+}
+
+/// Path of a crate's file in the sparse index: `1/a`, `2/ab`, `3/a/abc` or
+/// `ab/cd/abcd`, by the lowercased name.
+fn index_path(crate_name: &str) -> String {
+    let name = crate_name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+    }
+}
+
+/// A git dependency in `Cargo.lock`, which `importCargoLock` needs the hash of.
+#[derive(Debug, Clone, PartialEq)]
+struct GitDependency {
+    /// `name-version`, as `outputHashes` is keyed
+    key: String,
+    url: String,
+    rev: String,
+}
+
+/// Read the git dependencies of `Cargo.lock`, whose sources are
+/// `git+{url}?{reference}#{rev}`.
+fn git_dependencies(lock_file: &str) -> Result<Vec<GitDependency>> {
+    let lock: toml::Value = toml::from_str(lock_file).wrap_err("Invalid Cargo.lock")?;
+
+    let mut dependencies = Vec::new();
+    if let Some(packages) = lock.get("package").and_then(|p| p.as_array()) {
+        for package in packages {
+            let source = package
+                .get("source")
+                .and_then(|s| s.as_str())
+                .and_then(|s| s.strip_prefix("git+"));
+            if let (Some(name), Some(version), Some(source)) = (
+                package.get("name").and_then(|n| n.as_str()),
+                package.get("version").and_then(|v| v.as_str()),
+                source,
+            ) {
+                let (url, rev) = match source.split_once('#') {
+                    Some((url, rev)) => (url, rev),
+                    None => return Err(eyre!("{} in Cargo.lock has no commit", source)),
+                };
+                let url = url.split('?').next().unwrap_or(url);
+                dependencies.push(GitDependency {
+                    key: format!("{}-{}", name, version),
+                    url: url.to_string(),
+                    rev: rev.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(dependencies)
+}
+
+/// Write the `Cargo.lock` of the crate in `crate_dir`, resolving its dependencies the
+/// way `cargo install` does without `--locked`.
+fn generate_lockfile(crate_dir: &Path) -> Result<String> {
+    let cargo_process = Command::new("cargo")
+        .arg("generate-lockfile")
+        .arg("--manifest-path")
+        .arg(crate_dir.join("Cargo.toml"))
+        .output()
+        .wrap_err("Unable to run cargo")
+        .suggestion("Make sure cargo is installed and in your PATH, it writes the Cargo.lock crates that do not ship one are built with")?;
+
+    if cargo_process.status.success() {
+        Ok(fs::read_to_string(crate_dir.join("Cargo.lock"))?)
+    } else {
+        Err(eyre!(
+            "cargo generate-lockfile failed: {}",
+            String::from_utf8_lossy(&cargo_process.stderr).trim()
+        ))
+    }
+}
+
+impl CratesIoCrate {
+    pub async fn get(crate_name: String) -> Result<Self, Report> {
+        let response = CratesIoResponse::get_or_index(&crate_name).await?;
+
+        match response.newest_version().cloned() {
+            Some(version) => Ok(response.refine(&version)),
+            None => Err(eyre!(
+                "No versions of {} that have not been yanked",
+                crate_name
+            )),
+        }
+    }
+
+    pub async fn get_with_version(crate_name: String, version: String) -> Result<Self, Report> {
+        let response = CratesIoResponse::get_or_index(&crate_name).await?;
+
+        match response.versions.iter().find(|v| v.num == version).cloned() {
+            Some(v) => {
+                if v.yanked {
+                    warn!("{} {} has been yanked", crate_name, version);
+                }
+                Ok(response.refine(&v))
+            }
+            None => Err(eyre!("No version {} found for {}", version, crate_name)),
+        }
+    }
+
+    /// Download the `.crate` tarball, returning the hash `fetchCrate` expects along
+    /// with the crate's `Cargo.lock`, written by cargo if the crate does not ship one,
+    /// and the hashes of its git dependencies.
+    async fn prefetch(&self) -> Result<(String, CargoLock)> {
+        let (tmp_dir, dest_path) = download(&self.download_url).await?;
+        let unpack_dir = tmp_dir.path().join("unpacked");
+        fs::create_dir(&unpack_dir)?;
+
+        let crate_dir = unpack_tarball(&dest_path, &unpack_dir)?;
+        let sha256 = nix_hash(&crate_dir, false)?;
+        let lock_file = match fs::read_to_string(crate_dir.join("Cargo.lock")) {
+            Ok(lock_file) => lock_file,
+            Err(_) => {
+                warn!(
+                    "{} {} does not ship a Cargo.lock, writing one with the newest compatible dependencies",
+                    self.name, self.version
+                );
+                generate_lockfile(&crate_dir)?
+            }
+        };
+
+        tmp_dir.close()?;
+
+        // crates of one repository share its hash
+        let mut repositories: BTreeMap<(String, String), String> = BTreeMap::new();
+        let mut output_hashes = BTreeMap::new();
+        for dependency in git_dependencies(&lock_file)? {
+            let repository = (dependency.url.clone(), dependency.rev.clone());
+            let hash = match repositories.get(&repository) {
+                Some(hash) => hash.clone(),
+                None => {
+                    let (hash, _) = GitRepository::prefetch(&dependency.url, &dependency.rev)
+                        .await
+                        .wrap_err_with(|| {
+                            format!("Unable to get the hash of {}", dependency.key)
+                        })?;
+                    repositories.insert(repository, hash.clone());
+                    hash
+                }
+            };
+            output_hashes.insert(dependency.key, hash);
+        }
+
+        Ok((
+            sha256,
+            CargoLock {
+                lock_file,
+                output_hashes,
+            },
+        ))
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_nixpkg(self, pname: String) -> Result<NixPackage, Report> {
+        let (sha256, cargo_lock) =
+            task::block_in_place(|| Handle::current().block_on(self.prefetch())).wrap_err_with(
+                || format!("Unable to get the hash of {} {}", self.name, self.version),
+            )?;
+
+        let license = self
+            .license
+            .as_deref()
+            .map(NixLicense::from_spdx)
+            .filter(|licenses| !licenses.is_empty());
+
+        let meta = NixPackageMeta {
+            description: self.description.map(|d| d.trim().to_string()),
+            license_confidence: license.as_ref().map(|_| LicenseConfidence::Spdx),
+            license,
+            ..NixPackageMeta::from_links(PackageLinks {
+                homepage: self.homepage,
+                repository: self.repository,
+                store_page: Some(format!("{}/{}", CRATES_IO, self.name)),
+            })
+        };
+
+        Ok(NixPackage {
+            name: pname.clone(),
+            pname,
+            src: self.download_url,
+            version: self.version,
+            sha256,
+            meta,
+            fetcher: NixFetcher::FetchCrate {
+                crate_name: self.name,
+            },
+            builder: NixBuilder::RustPackage { cargo_lock },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use serde_json::json;
+
+    fn ripgrep() -> CratesIoResponse {
+        serde_json::from_value(json!({
+            "crate": {
+                "id": "ripgrep",
+                "name": "ripgrep",
+                "description": "ripgrep is a line-oriented search tool that recursively searches your current\ndirectory for a regex pattern while respecting your gitignore rules. ripgrep\nhas first class support on Windows, macOS and Linux.\n",
+                "homepage": "https://github.com/BurntSushi/ripgrep",
+                "documentation": "https://github.com/BurntSushi/ripgrep",
+                "repository": "https://github.com/BurntSushi/ripgrep",
+                "max_version": "14.0.0-pre.1",
+                "max_stable_version": "13.0.0",
+                "newest_version": "14.0.0-pre.1",
+                "updated_at": "2021-06-12T12:24:26.402357+00:00"
+            },
+            "versions": [
+                {
+                    "id": 3,
+                    "crate": "ripgrep",
+                    "num": "14.0.0-pre.1",
+                    "dl_path": "/api/v1/crates/ripgrep/14.0.0-pre.1/download",
+                    "checksum": "0000000000000000000000000000000000000000000000000000000000000000",
+                    "yanked": false,
+                    "license": "Unlicense OR MIT",
+                    "crate_size": 1,
+                    "created_at": "2021-07-01T12:24:26.402357+00:00"
+                },
+                {
+                    "id": 2,
+                    "crate": "ripgrep",
+                    "num": "13.0.1",
+                    "dl_path": "/api/v1/crates/ripgrep/13.0.1/download",
+                    "checksum": "0000000000000000000000000000000000000000000000000000000000000000",
+                    "yanked": true,
+                    "license": "Unlicense OR MIT",
+                    "crate_size": 1,
+                    "created_at": "2021-06-20T12:24:26.402357+00:00"
+                },
+                {
+                    "id": 1,
+                    "crate": "ripgrep",
+                    "num": "13.0.0",
+                    "dl_path": "/api/v1/crates/ripgrep/13.0.0/download",
+                    "checksum": "cb6e4b5f3ab5bb3b02cbc1d9e3a9a1cb6e4b5f3ab5bb3b02cbc1d9e3a9a1cb6e",
+                    "yanked": false,
+                    "license": "Unlicense/MIT",
+                    "crate_size": 1,
+                    "created_at": "2021-06-12T12:24:26.402357+00:00"
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_newest_version() {
+        let response = ripgrep();
+        assert_eq!(response.newest_version().unwrap().num, "13.0.0");
+
+        let krate = response.refine(&ripgrep().versions[2]);
+        assert_eq!(krate.version, "13.0.0");
+        assert_eq!(
+            krate.download_url,
+            "https://crates.io/api/v1/crates/ripgrep/13.0.0/download"
+        );
+    }
+
+    #[test]
+    fn test_spdx_licenses() {
+        assert_eq!(
+            NixLicense::from_spdx("Unlicense/MIT"),
+            vec![NixLicense::Unlicense, NixLicense::Mit]
+        );
+        assert_eq!(
+            NixLicense::from_spdx("MIT OR Apache-2.0"),
+            vec![NixLicense::Mit, NixLicense::Asl20]
+        );
+        assert!(NixLicense::from_spdx("LicenseRef-Proprietary").is_empty());
+    }
+
+    #[test]
+    fn test_index_path() {
+        assert_eq!(index_path("a"), "1/a");
+        assert_eq!(index_path("cc"), "2/cc");
+        assert_eq!(index_path("syn"), "3/s/syn");
+        assert_eq!(index_path("RipGrep"), "ri/pg/ripgrep");
+    }
+
+    #[test]
+    fn test_from_index() {
+        let response = CratesIoResponse::from_index(
+            r#"{"name":"ripgrep","vers":"13.0.0","deps":[],"cksum":"abc","features":{},"yanked":false}
+{"name":"ripgrep","vers":"14.0.0","deps":[],"cksum":"def","features":{},"yanked":true}
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(response.krate.name, "ripgrep");
+        assert_eq!(response.versions.len(), 2);
+        assert_eq!(
+            response.versions[0].dl_path,
+            "/api/v1/crates/ripgrep/13.0.0/download"
+        );
+        assert_eq!(
+            response.newest_version().map(|v| v.num.as_str()),
+            Some("13.0.0")
+        );
+    }
+
+    #[test]
+    fn test_git_dependencies() {
+        let dependencies = git_dependencies(
+            r#"
+[[package]]
+name = "grep"
+version = "0.2.8"
+
+[[package]]
+name = "memchr"
+version = "2.4.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "pcre2"
+version = "0.2.3"
+source = "git+https://github.com/BurntSushi/rust-pcre2?rev=abc#abc"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            dependencies,
+            vec![GitDependency {
+                key: String::from("pcre2-0.2.3"),
+                url: String::from("https://github.com/BurntSushi/rust-pcre2"),
+                rev: String::from("abc"),
+            }]
+        );
+        assert!(git_dependencies("[[package]\n").is_err());
+    }
+}
@@ -0,0 +1,9 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SourceError {
+    #[error("GitHub API rate limit exceeded, it resets in {0} seconds")]
+    RateLimited(u64),
+    #[error("{0} is no longer published")]
+    Unpublished(String),
+}
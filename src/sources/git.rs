@@ -0,0 +1,521 @@
+use color_eyre::{
+    eyre::{eyre, Report, Result, WrapErr},
+    Section,
+};
+use tempfile::Builder;
+use tokio::process::Command;
+use tracing::warn;
+
+use std::{cmp::Ordering, fs, path::Path};
+
+use crate::{
+    nix::{NixBuilder, NixFetcher, NixPackage, NixPackageMeta, PackageLinks},
+    sources::nix_hash,
+};
+
+/// What to follow in a git repository, shared by every git-based backend.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GitRef {
+    /// The newest release, optionally only among tags starting with a prefix, and
+    /// counting pre-releases only if opted in
+    LatestRelease {
+        prefix: Option<String>,
+        prerelease: bool,
+    },
+    /// The head of a branch, or of the default branch
+    Branch(Option<String>),
+    /// A release, by its tag
+    Release(String),
+    Tag(String),
+    Commit(String),
+}
+
+/// A commit resolved from a [`GitRef`], and the version it gets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitRevision {
+    /// Full commit SHA, or tag name for releases and tags
+    pub rev: String,
+    pub version: String,
+    /// The branch followed, if the revision follows one
+    pub branch: Option<String>,
+}
+
+impl GitRevision {
+    /// A revision following a branch or pinned to a commit, versioned by its committer date
+    /// and the newest tag reachable from it.
+    pub fn unstable(sha: String, committer_date: &str, last_tag: Option<&str>) -> Self {
+        GitRevision {
+            version: unstable_version(committer_date, last_tag),
+            rev: sha,
+            branch: None,
+        }
+    }
+
+    /// A revision pinned to a tag, versioned by the tag.
+    pub fn tagged(tag: String) -> Self {
+        GitRevision {
+            version: tag_version(&tag),
+            rev: tag,
+            branch: None,
+        }
+    }
+
+    /// The same revision, as the head of `branch`.
+    pub fn on_branch(self, branch: &str) -> Self {
+        GitRevision {
+            branch: Some(branch.to_string()),
+            ..self
+        }
+    }
+}
+
+/// nixpkgs-style `unstable-YYYY-MM-DD` from an ISO 8601 committer date, prefixed by
+/// the version of the last reachable tag as `1.2.3-unstable-YYYY-MM-DD`.
+pub fn unstable_version(committer_date: &str, last_tag: Option<&str>) -> String {
+    let date = committer_date.get(..10).unwrap_or(committer_date);
+    match last_tag {
+        Some(tag) => format!("{}-unstable-{}", tag_version(tag), date),
+        None => format!("unstable-{}", date),
+    }
+}
+
+/// The version a tag stands for: `v1.2.3` and `release-1.2.3` are `1.2.3`.
+pub fn tag_version(tag: &str) -> String {
+    match tag.find(|c: char| c.is_ascii_digit()) {
+        Some(start) => tag[start..].to_string(),
+        None => tag.to_string(),
+    }
+}
+
+/// Whether a tag is a semver pre-release, like `v1.2.0-rc.1` or `2.0-beta`; date tags
+/// like `2021-08-01` are not.
+pub fn is_prerelease_tag(tag: &str) -> bool {
+    tag_version(tag)
+        .split_once('-')
+        .is_some_and(|(_, pre)| pre.starts_with(|c: char| c.is_ascii_alphabetic()))
+}
+
+/// How many of the newest tags are checked for being reachable from a commit, as
+/// hosted backends spend a request on each.
+pub const LAST_TAG_CANDIDATES: usize = 10;
+
+/// The tags most likely to be the last one reachable from a commit, newest first.
+pub fn last_tag_candidates<'a>(tags: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut tags: Vec<&str> = tags.into_iter().collect();
+    tags.sort_by(|a, b| compare_tags(b, a));
+    tags.truncate(LAST_TAG_CANDIDATES);
+    tags
+}
+
+/// Order tags by the numbers in them, so `v1.10.0` comes after `v1.9.2`.
+pub fn compare_tags(a: &str, b: &str) -> Ordering {
+    let numbers = |tag: &str| -> Vec<u64> {
+        tag.split(|c: char| !c.is_ascii_digit())
+            .filter(|n| !n.is_empty())
+            .filter_map(|n| n.parse().ok())
+            .collect()
+    };
+    numbers(a).cmp(&numbers(b)).then_with(|| a.cmp(b))
+}
+
+async fn git(args: &[&str], dir: Option<&Path>) -> Result<String> {
+    let mut command = Command::new("git");
+    command.args(args);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command
+        .output()
+        .await
+        .wrap_err("Unable to run git")
+        .suggestion("Make sure git is installed and on your PATH")?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(eyre!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Remove the `.git` of `dir` and of every submodule in it, which is a file pointing
+/// into the parent's `.git` for submodules.
+fn remove_dot_git(dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if entry.file_name() == ".git" {
+            if file_type.is_dir() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+        } else if file_type.is_dir() {
+            remove_dot_git(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Parse `git ls-remote` output into `(sha, ref)` pairs, resolving annotated tags
+/// to the commit they point at.
+fn parse_ls_remote(output: &str) -> Vec<(String, String)> {
+    let mut refs: Vec<(String, String)> = Vec::new();
+    for line in output.lines() {
+        if let Some((sha, name)) = line.split_once('\t') {
+            match name.strip_suffix("^{}") {
+                Some(tag) => {
+                    if let Some(existing) = refs.iter_mut().find(|(_, n)| n == tag) {
+                        existing.0 = sha.to_string();
+                    }
+                }
+                None => refs.push((sha.to_string(), name.to_string())),
+            }
+        }
+    }
+    refs
+}
+
+/// A package built from a plain git repository, through `fetchgit`.
+#[derive(Debug)]
+pub struct GitRepository {
+    pub url: String,
+    pub rev: String,
+    pub version: String,
+    pub branch: Option<String>,
+    pub sha256: String,
+}
+
+impl GitRepository {
+    pub async fn get(url: String, git_ref: GitRef) -> Result<Self, Report> {
+        let refs = parse_ls_remote(&git(&["ls-remote", &url], None).await?);
+        let find = |name: &str| {
+            refs.iter()
+                .find(|(_, n)| n == name)
+                .map(|(sha, _)| sha.clone())
+        };
+
+        // The ref to fetch, and whether the version comes from a tag
+        let (fetch, tag) = match &git_ref {
+            GitRef::Branch(None) => ("HEAD".to_string(), None),
+            GitRef::Branch(Some(branch)) => (format!("refs/heads/{}", branch), None),
+            GitRef::Release(tag) | GitRef::Tag(tag) => {
+                (format!("refs/tags/{}", tag), Some(tag.clone()))
+            }
+            GitRef::Commit(sha) => (sha.clone(), None),
+            GitRef::LatestRelease { prefix, prerelease } => {
+                let newest = refs
+                    .iter()
+                    .filter_map(|(_, n)| n.strip_prefix("refs/tags/"))
+                    .filter(|tag| prefix.as_deref().map_or(true, |p| tag.starts_with(p)))
+                    .filter(|tag| *prerelease || !is_prerelease_tag(tag))
+                    .max_by(|a, b| compare_tags(a, b));
+                match newest {
+                    Some(tag) => (format!("refs/tags/{}", tag), Some(tag.to_string())),
+                    None => return Err(eyre!("{} has no tags to take a release from", url)),
+                }
+            }
+        };
+
+        let sha = match &git_ref {
+            GitRef::Commit(sha) => sha.clone(),
+            _ => match find(&fetch) {
+                Some(sha) => sha,
+                None => return Err(eyre!("{} has no {}", url, fetch)),
+            },
+        };
+
+        let (sha256, committer_date) = Self::prefetch(&url, &fetch).await?;
+        let revision = match tag {
+            Some(tag) => GitRevision {
+                version: tag_version(&tag),
+                rev: sha,
+                branch: None,
+            },
+            None => {
+                let last_tag = Self::last_tag(&url, &fetch).await.unwrap_or_else(|e| {
+                    warn!("Unable to find the last tag of {}: {}", url, e);
+                    None
+                });
+                GitRevision::unstable(sha, &committer_date, last_tag.as_deref())
+            }
+        };
+        // the default branch is only known as HEAD
+        let revision = match &git_ref {
+            GitRef::Branch(Some(branch)) => revision.on_branch(branch),
+            _ => revision,
+        };
+
+        Ok(GitRepository {
+            url,
+            rev: revision.rev,
+            version: revision.version,
+            branch: revision.branch,
+            sha256,
+        })
+    }
+
+    /// Check out `fetch` without its `.git`, the way `fetchgit` does, returning the
+    /// hash of the checkout and the commit's committer date.
+    pub async fn prefetch(url: &str, fetch: &str) -> Result<(String, String)> {
+        let tmp_dir = Builder::new().prefix(env!("CARGO_PKG_NAME")).tempdir()?;
+        let checkout = tmp_dir.path().join("checkout");
+        fs::create_dir(&checkout)?;
+
+        let committer_date = Self::checkout(url, fetch, &checkout).await?;
+        let sha256 = nix_hash(&checkout, false)?;
+
+        tmp_dir.close()?;
+
+        Ok((sha256, committer_date))
+    }
+
+    /// Check out `fetch` into the empty directory `checkout` with its submodules, as
+    /// `fetchgit` does by default, and remove every `.git`, returning the commit's
+    /// committer date.
+    async fn checkout(url: &str, fetch: &str, checkout: &Path) -> Result<String> {
+        git(&["init", "--quiet"], Some(checkout)).await?;
+        git(
+            &["fetch", "--quiet", "--depth", "1", url, fetch],
+            Some(checkout),
+        )
+        .await?;
+        git(&["checkout", "--quiet", "FETCH_HEAD"], Some(checkout)).await?;
+        git(
+            &["submodule", "update", "--quiet", "--init", "--recursive"],
+            Some(checkout),
+        )
+        .await?;
+        let committer_date = git(&["log", "-1", "--format=%cI"], Some(checkout)).await?;
+
+        remove_dot_git(checkout)?;
+
+        Ok(committer_date.trim().to_string())
+    }
+
+    /// The newest tag reachable from `fetch`, found through the history without any
+    /// trees or blobs.
+    async fn last_tag(url: &str, fetch: &str) -> Result<Option<String>> {
+        let tmp_dir = Builder::new().prefix(env!("CARGO_PKG_NAME")).tempdir()?;
+        let history = tmp_dir.path();
+
+        git(&["init", "--quiet", "--bare"], Some(history)).await?;
+        git(
+            &[
+                "fetch",
+                "--quiet",
+                "--filter=tree:0",
+                "--tags",
+                url,
+                &format!("+{}:refs/{}/head", fetch, env!("CARGO_PKG_NAME")),
+            ],
+            Some(history),
+        )
+        .await?;
+        let last_tag = git(
+            &[
+                "describe",
+                "--tags",
+                "--abbrev=0",
+                &format!("refs/{}/head", env!("CARGO_PKG_NAME")),
+            ],
+            Some(history),
+        )
+        .await
+        .ok()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty());
+
+        tmp_dir.close()?;
+
+        Ok(last_tag)
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_nixpkg(self, pname: String, builder: NixBuilder) -> NixPackage {
+        let meta = NixPackageMeta {
+            branch: self.branch,
+            ..NixPackageMeta::from_links(PackageLinks {
+                homepage: None,
+                // only web URLs, git:// and ssh hosts need not serve pages
+                repository: Some(self.url.clone()).filter(|u| u.starts_with("http")),
+                store_page: None,
+            })
+        };
+
+        NixPackage {
+            name: pname.clone(),
+            pname,
+            src: self.url,
+            version: self.version,
+            sha256: self.sha256,
+            meta,
+            fetcher: NixFetcher::FetchGit { rev: self.rev },
+            builder,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(
+            unstable_version("2026-10-18T09:12:44Z", None),
+            "unstable-2026-10-18"
+        );
+        assert_eq!(
+            unstable_version("2026-10-18T11:12:44+02:00", Some("v0.4.1")),
+            "0.4.1-unstable-2026-10-18"
+        );
+        assert_eq!(tag_version("v1.2.3"), "1.2.3");
+        assert_eq!(tag_version("release-2021.08"), "2021.08");
+        assert_eq!(tag_version("nightly"), "nightly");
+
+        assert!(is_prerelease_tag("v1.2.0-rc.1"));
+        assert!(is_prerelease_tag("release-2.0-beta"));
+        assert!(!is_prerelease_tag("v1.2.0"));
+        assert!(!is_prerelease_tag("2021-08-01"));
+        assert!(!is_prerelease_tag("nightly"));
+
+        let mut tags = vec!["v1.9.2", "v1.10.0", "v1.2.0"];
+        tags.sort_by(|a, b| compare_tags(a, b));
+        assert_eq!(tags, vec!["v1.2.0", "v1.9.2", "v1.10.0"]);
+        assert_eq!(
+            last_tag_candidates(tags),
+            vec!["v1.10.0", "v1.9.2", "v1.2.0"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_last_tag() {
+        let tmp_dir = Builder::new()
+            .prefix(env!("CARGO_PKG_NAME"))
+            .tempdir()
+            .unwrap();
+        let repo = tmp_dir.path();
+        let url = format!("file://{}", repo.display());
+        let commit = |message: &'static str| async move {
+            git(
+                &[
+                    "-c",
+                    "user.name=nxpkgr",
+                    "-c",
+                    "user.email=nxpkgr@localhost",
+                    "commit",
+                    "--quiet",
+                    "--allow-empty",
+                    "-m",
+                    message,
+                ],
+                Some(repo),
+            )
+            .await
+            .unwrap()
+        };
+
+        git(&["init", "--quiet"], Some(repo)).await.unwrap();
+        commit("initial").await;
+        assert_eq!(GitRepository::last_tag(&url, "HEAD").await.unwrap(), None);
+
+        git(&["tag", "v0.1.0"], Some(repo)).await.unwrap();
+        commit("feature").await;
+        git(&["tag", "v0.2.0"], Some(repo)).await.unwrap();
+        commit("fix").await;
+        assert_eq!(
+            GitRepository::last_tag(&url, "HEAD").await.unwrap(),
+            Some(String::from("v0.2.0"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checkout_with_submodules() {
+        // submodules from file:// URLs are refused unless allowed
+        std::env::set_var("GIT_CONFIG_COUNT", "1");
+        std::env::set_var("GIT_CONFIG_KEY_0", "protocol.file.allow");
+        std::env::set_var("GIT_CONFIG_VALUE_0", "always");
+
+        let tmp_dir = Builder::new()
+            .prefix(env!("CARGO_PKG_NAME"))
+            .tempdir()
+            .unwrap();
+        let commit = |repo: std::path::PathBuf| async move {
+            git(
+                &[
+                    "-c",
+                    "user.name=nxpkgr",
+                    "-c",
+                    "user.email=nxpkgr@localhost",
+                    "commit",
+                    "--quiet",
+                    "-m",
+                    "initial",
+                ],
+                Some(&repo),
+            )
+            .await
+            .unwrap()
+        };
+
+        let library = tmp_dir.path().join("library");
+        fs::create_dir(&library).unwrap();
+        git(&["init", "--quiet"], Some(&library)).await.unwrap();
+        fs::write(library.join("library.vim"), "\" library\n").unwrap();
+        git(&["add", "."], Some(&library)).await.unwrap();
+        commit(library.clone()).await;
+
+        let plugin = tmp_dir.path().join("plugin");
+        fs::create_dir(&plugin).unwrap();
+        git(&["init", "--quiet"], Some(&plugin)).await.unwrap();
+        fs::write(plugin.join("plugin.vim"), "\" plugin\n").unwrap();
+        git(
+            &[
+                "submodule",
+                "add",
+                "--quiet",
+                &format!("file://{}", library.display()),
+                "deps/library",
+            ],
+            Some(&plugin),
+        )
+        .await
+        .unwrap();
+        git(&["add", "."], Some(&plugin)).await.unwrap();
+        commit(plugin.clone()).await;
+
+        let checkout = tmp_dir.path().join("checkout");
+        fs::create_dir(&checkout).unwrap();
+        GitRepository::checkout(&format!("file://{}", plugin.display()), "HEAD", &checkout)
+            .await
+            .unwrap();
+
+        assert!(checkout.join("plugin.vim").is_file());
+        assert!(checkout.join("deps/library/library.vim").is_file());
+        assert!(!checkout.join(".git").exists());
+        assert!(!checkout.join("deps/library/.git").exists());
+    }
+
+    #[test]
+    fn test_parse_ls_remote() {
+        let refs = parse_ls_remote(
+            "1111111111111111111111111111111111111111\tHEAD\n\
+             1111111111111111111111111111111111111111\trefs/heads/master\n\
+             2222222222222222222222222222222222222222\trefs/tags/v1.0\n\
+             3333333333333333333333333333333333333333\trefs/tags/v1.0^{}\n",
+        );
+        assert_eq!(refs.len(), 3);
+        assert_eq!(
+            refs[2],
+            (
+                String::from("3333333333333333333333333333333333333333"),
+                String::from("refs/tags/v1.0")
+            )
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use color_eyre::{
+    eyre::{eyre, Report, Result, WrapErr},
+    Section,
+};
+use tokio::{runtime::Handle, task};
+use tracing::warn;
+
+use crate::{
+    nix::{
+        LicenseConfidence, NixBuilder, NixFetcher, NixLicense, NixPackage, NixPackageMeta,
+        PackageLinks,
+    },
+    sources::{
+        get_unpacked_hash,
+        git::{is_prerelease_tag, last_tag_candidates, GitRef, GitRevision},
+    },
+};
+
+const GITLAB_HOST: &str = "gitlab.com";
+
+/// /api/v4/projects/{id}?license=true
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GitLabProject {
+    pub id: u64,
+    pub path: String,
+    pub path_with_namespace: String,
+    pub description: Option<String>,
+    pub web_url: String,
+    pub default_branch: Option<String>,
+    #[serde(default)]
+    pub archived: bool,
+    pub license: Option<GitLabLicense>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GitLabLicense {
+    /// choosealicense.com key, eg. `apache-2.0`
+    pub key: String,
+    pub name: String,
+}
+
+/// /api/v4/projects/{id}/repository/commits/{ref}
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GitLabCommit {
+    pub id: String,
+    pub committed_date: String,
+}
+
+/// /api/v4/projects/{id}/repository/tags
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GitLabTag {
+    pub name: String,
+    pub commit: GitLabCommit,
+}
+
+/// /api/v4/projects/{id}/releases
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GitLabRelease {
+    pub tag_name: String,
+    #[serde(default)]
+    pub upcoming_release: bool,
+}
+
+impl GitLabLicense {
+    /// GitLab reports licenses by their lowercased choosealicense.com key.
+    fn to_nix(&self) -> Option<NixLicense> {
+        let spdx = match self.key.as_str() {
+            "agpl-3.0" => "AGPL-3.0-only",
+            "apache-2.0" => "Apache-2.0",
+            "bsd-2-clause" => "BSD-2-Clause",
+            "bsd-3-clause" => "BSD-3-Clause",
+            "bsl-1.0" => "BSL-1.0",
+            "cc0-1.0" => "CC0-1.0",
+            "epl-2.0" => "EPL-2.0",
+            "gpl-2.0" => "GPL-2.0-only",
+            "gpl-3.0" => "GPL-3.0-only",
+            "isc" => "ISC",
+            "lgpl-2.1" => "LGPL-2.1-only",
+            "lgpl-3.0" => "LGPL-3.0-only",
+            "mit" => "MIT",
+            "mpl-2.0" => "MPL-2.0",
+            "unlicense" => "Unlicense",
+            _ => return None,
+        };
+        NixLicense::from_str(spdx).copied()
+    }
+}
+
+/// Split `[host/]namespace/project`, where `namespace` may contain subgroups.
+fn host_project(src: &str) -> Result<(&str, &str)> {
+    let (host, project) = match src.split_once('/') {
+        Some((host, project)) if host.contains('.') => (host, project),
+        _ => (GITLAB_HOST, src),
+    };
+    if project.contains('/') && !project.starts_with('/') && !project.ends_with('/') {
+        Ok((host, project))
+    } else {
+        Err(eyre!("{} is not a GitLab project", src)).suggestion(
+            "GitLab projects are written as \"namespace/project\", or \"host/namespace/project\" for other instances",
+        )
+    }
+}
+
+/// A package built from a GitLab project, through `fetchFromGitLab`.
+#[derive(Debug)]
+pub struct GitLabSource {
+    pub host: String,
+    pub project: GitLabProject,
+    pub revision: GitRevision,
+}
+
+impl GitLabSource {
+    async fn get_json<T: DeserializeOwned>(host: &str, project: &str, path: &str) -> Result<T> {
+        let url = format!(
+            "https://{}/api/v4/projects/{}{}",
+            host,
+            project.replace('/', "%2F"),
+            path
+        );
+        let response = reqwest::Client::new()
+            .get(url)
+            .header("User-Agent", env!("CARGO_PKG_NAME"))
+            .send()
+            .await?;
+
+        let resp_status = response.status();
+
+        if resp_status.is_success() {
+            match response.json().await {
+                Ok(gitlab_response) => Ok(gitlab_response),
+                Err(e) => Err(eyre!("Unable to parse json from {}", host)).error(e),
+            }
+        } else if let Some(reason) = resp_status.canonical_reason() {
+            Err(eyre!(
+                "Recieved {}, while attempting to get {}{} from {}.",
+                reason,
+                project,
+                path,
+                host
+            ))
+        } else {
+            Err(eyre!("{}", resp_status.to_string()))
+        }
+    }
+
+    /// The newest tag whose commit is the merge base of itself and `sha`, ie. an ancestor.
+    async fn last_tag(host: &str, project: &str, sha: &str) -> Result<Option<String>> {
+        let tags: Vec<GitLabTag> = Self::get_json(
+            host,
+            project,
+            "/repository/tags?order_by=version&per_page=100",
+        )
+        .await?;
+        for name in last_tag_candidates(tags.iter().map(|tag| tag.name.as_str())) {
+            let tag = tags.iter().find(|tag| tag.name == name);
+            let merge_base: GitLabCommit = Self::get_json(
+                host,
+                project,
+                &format!(
+                    "/repository/merge_base?refs%5B%5D={}&refs%5B%5D={}",
+                    name, sha
+                ),
+            )
+            .await?;
+            if tag.is_some_and(|tag| tag.commit.id == merge_base.id) {
+                return Ok(Some(name.to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn get(src: String, git_ref: GitRef) -> Result<Self, Report> {
+        let (host, project_path) = host_project(&src)?;
+        let project: GitLabProject = Self::get_json(host, project_path, "?license=true").await?;
+
+        let commit = |git_ref: String| async move {
+            let commit: GitLabCommit = Self::get_json(
+                host,
+                project_path,
+                &format!("/repository/commits/{}", git_ref),
+            )
+            .await?;
+            let last_tag = Self::last_tag(host, project_path, &commit.id)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Unable to find the last tag of {}: {}", project_path, e);
+                    None
+                });
+            Ok::<_, Report>(GitRevision::unstable(
+                commit.id,
+                &commit.committed_date,
+                last_tag.as_deref(),
+            ))
+        };
+        let default_branch = project
+            .default_branch
+            .clone()
+            .unwrap_or_else(|| String::from("HEAD"));
+        let on_branch = |revision: GitRevision, branch: &str| match branch {
+            "HEAD" => revision,
+            branch => revision.on_branch(branch),
+        };
+
+        let revision = match git_ref {
+            GitRef::LatestRelease { prefix, prerelease } => {
+                let releases: Vec<GitLabRelease> =
+                    Self::get_json(host, project_path, "/releases").await?;
+                let release = releases.into_iter().find(|release| {
+                    !release.upcoming_release
+                        && (prerelease || !is_prerelease_tag(&release.tag_name))
+                        && prefix
+                            .as_deref()
+                            .map_or(true, |p| release.tag_name.starts_with(p))
+                });
+                match (release, prefix) {
+                    (Some(release), _) => GitRevision::tagged(release.tag_name),
+                    (None, None) => {
+                        warn!(
+                            "{} has no releases, following {} instead",
+                            src, default_branch
+                        );
+                        on_branch(commit(default_branch.clone()).await?, &default_branch)
+                    }
+                    (None, Some(prefix)) => {
+                        return Err(eyre!(
+                            "{} has no releases with a tag starting with {}",
+                            src,
+                            prefix
+                        ))
+                    }
+                }
+            }
+            GitRef::Release(tag) | GitRef::Tag(tag) => GitRevision::tagged(tag),
+            GitRef::Branch(branch) => {
+                let branch = branch.unwrap_or(default_branch);
+                on_branch(commit(branch.clone()).await?, &branch)
+            }
+            GitRef::Commit(sha) => commit(sha).await?,
+        };
+
+        Ok(GitLabSource {
+            host: host.to_string(),
+            project,
+            revision,
+        })
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_nixpkg(self, pname: String, builder: NixBuilder) -> Result<NixPackage, Report> {
+        let url = format!(
+            "{}/-/archive/{rev}/{}-{rev}.tar.gz",
+            self.project.web_url,
+            self.project.path,
+            rev = self.revision.rev
+        );
+        let sha256 = task::block_in_place(|| Handle::current().block_on(get_unpacked_hash(&url)))
+            .wrap_err_with(|| format!("Unable to get the hash of {}", url))?;
+
+        if self.project.archived {
+            warn!("{} has been archived", self.project.path_with_namespace);
+        }
+
+        let license = self
+            .project
+            .license
+            .as_ref()
+            .and_then(GitLabLicense::to_nix)
+            .map(|license| vec![license]);
+
+        let (owner, repo) = self
+            .project
+            .path_with_namespace
+            .rsplit_once('/')
+            .map(|(owner, repo)| (owner.to_string(), repo.to_string()))
+            .unwrap_or_default();
+
+        let meta = NixPackageMeta {
+            description: self.project.description.filter(|d| !d.is_empty()),
+            branch: self.revision.branch,
+            license_confidence: license.as_ref().map(|_| LicenseConfidence::Spdx),
+            license,
+            ..NixPackageMeta::from_links(PackageLinks {
+                homepage: None,
+                repository: Some(self.project.web_url),
+                store_page: None,
+            })
+        };
+
+        Ok(NixPackage {
+            name: pname.clone(),
+            pname,
+            src: url,
+            version: self.revision.version,
+            sha256,
+            meta,
+            fetcher: NixFetcher::FetchFromGitLab {
+                domain: Some(self.host).filter(|host| host != GITLAB_HOST),
+                owner,
+                repo,
+                rev: self.revision.rev,
+            },
+            builder,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_project() {
+        assert_eq!(
+            host_project("inkscape/inkscape").unwrap(),
+            ("gitlab.com", "inkscape/inkscape")
+        );
+        assert_eq!(
+            host_project("gitlab.freedesktop.org/mesa/mesa").unwrap(),
+            ("gitlab.freedesktop.org", "mesa/mesa")
+        );
+        assert_eq!(
+            host_project("gnome/world/podcasts").unwrap(),
+            ("gitlab.com", "gnome/world/podcasts")
+        );
+        assert!(host_project("inkscape").is_err());
+        assert!(host_project("gitlab.freedesktop.org/mesa").is_err());
+    }
+
+    #[test]
+    fn test_gitlab_license() {
+        let license = GitLabLicense {
+            key: String::from("apache-2.0"),
+            name: String::from("Apache License 2.0"),
+        };
+        assert_eq!(license.to_nix(), Some(NixLicense::Asl20));
+    }
+}
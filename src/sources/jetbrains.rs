@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+
+use color_eyre::{
+    eyre::{eyre, Report, Result, WrapErr},
+    Section,
+};
+use tokio::{runtime::Handle, task};
+use tracing::warn;
+
+use std::cmp::Ordering;
+
+use crate::{
+    nix::{NixBuilder, NixFetcher, NixPackage, NixPackageMeta, PackageLinks},
+    sources::get_hash,
+};
+
+const JETBRAINS_API: &str = "https://plugins.jetbrains.com/api/plugins";
+const JETBRAINS_DOWNLOADS: &str = "https://downloads.marketplace.jetbrains.com/files";
+const JETBRAINS_MARKETPLACE: &str = "https://plugins.jetbrains.com";
+
+/// Number of updates requested per page, and how many pages to look through
+/// before giving up on finding a compatible one.
+const UPDATES_PAGE_SIZE: usize = 50;
+const UPDATES_MAX_PAGES: usize = 10;
+
+/// /api/plugins/{id}
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JetBrainsPluginInfo {
+    pub id: u64,
+    pub name: String,
+    pub xml_id: String,
+    pub link: String,
+    pub preview: Option<String>,
+    pub urls: Option<JetBrainsPluginUrls>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JetBrainsPluginUrls {
+    pub url: Option<String>,
+    pub source_code_url: Option<String>,
+}
+
+/// /api/plugins/{id}/updates
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct JetBrainsUpdate {
+    pub id: u64,
+    pub version: String,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Path of the zip or jar below the download server
+    pub file: String,
+    #[serde(default)]
+    pub channel: String,
+}
+
+#[derive(Debug)]
+pub struct JetBrainsPlugin {
+    pub info: JetBrainsPluginInfo,
+    pub update: JetBrainsUpdate,
+}
+
+/// Parse a build number like `IU-232.9921.47` or an until-build like `232.*`.
+/// Wildcards are `None`, and `SNAPSHOT` builds sort after every release.
+fn build_components(build: &str) -> Option<Vec<Option<u64>>> {
+    let build = match build.split_once('-') {
+        Some((product, number)) if product.chars().all(|c| c.is_ascii_uppercase()) => number,
+        _ => build,
+    };
+    build
+        .trim()
+        .split('.')
+        .map(|component| match component {
+            "*" => Some(None),
+            "SNAPSHOT" => Some(Some(u64::MAX)),
+            n => n.parse().ok().map(Some),
+        })
+        .collect()
+}
+
+/// Whether an IDE with build number `build` can load a plugin declaring
+/// `since-build` and `until-build`. A missing bound, or one that fails to parse,
+/// does not restrict compatibility.
+fn is_compatible(build: &[Option<u64>], since: Option<&str>, until: Option<&str>) -> bool {
+    let component = |i: usize| build.get(i).copied().flatten().unwrap_or(0);
+
+    if let Some(since) = since.filter(|s| !s.is_empty()).and_then(build_components) {
+        for (i, since) in since.iter().enumerate() {
+            match since.map(|s| component(i).cmp(&s)) {
+                None | Some(Ordering::Greater) => break,
+                Some(Ordering::Less) => return false,
+                Some(Ordering::Equal) => continue,
+            }
+        }
+    }
+
+    if let Some(until) = until.filter(|u| !u.is_empty()).and_then(build_components) {
+        for (i, until) in until.iter().enumerate() {
+            match until.map(|u| component(i).cmp(&u)) {
+                None | Some(Ordering::Less) => break,
+                Some(Ordering::Greater) => return false,
+                Some(Ordering::Equal) => continue,
+            }
+        }
+    }
+
+    true
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(url: String, plugin: &str) -> Result<T, Report> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .header("User-Agent", env!("CARGO_PKG_NAME"))
+        .send()
+        .await?;
+
+    let resp_status = response.status();
+
+    if resp_status.is_success() {
+        match response.json().await {
+            Ok(jetbrains_response) => Ok(jetbrains_response),
+            Err(e) => Err(eyre!("Unable to parse json from the JetBrains Marketplace")).error(e),
+        }
+    } else if let Some(reason) = resp_status.canonical_reason() {
+        Err(eyre!(
+            "Recieved {}, while attempting to get {} from the JetBrains Marketplace.",
+            reason,
+            plugin
+        ))
+    } else {
+        Err(eyre!("{}", resp_status.to_string()))
+    }
+}
+
+/// Plugin ids are numeric; accept the `8182-rust` form used in marketplace urls too.
+fn plugin_id(plugin: &str) -> Result<u64, Report> {
+    let id = plugin.split('-').next().unwrap_or(plugin);
+    id.parse().map_err(|_| {
+        eyre!("{} is not a JetBrains Marketplace plugin id", plugin).suggestion(
+            "Use the number from the plugin's url, eg. 8182 for https://plugins.jetbrains.com/plugin/8182-rust",
+        )
+    })
+}
+
+impl JetBrainsPlugin {
+    /// The newest stable update that an IDE with build number `build` can load.
+    pub async fn get(plugin: String, build: Option<String>) -> Result<Self, Report> {
+        let id = plugin_id(&plugin)?;
+        let info: JetBrainsPluginInfo =
+            get_json(format!("{}/{}", JETBRAINS_API, id), &plugin).await?;
+
+        let build_number = match build.as_deref() {
+            Some(build) => match build_components(build) {
+                Some(build_number) => Some(build_number),
+                None => return Err(eyre!("Invalid IDE build number {}", build)),
+            },
+            None => {
+                warn!(
+                    "No IDE build number configured, {} will not be checked for compatibility",
+                    plugin
+                );
+                None
+            }
+        };
+
+        for page in 1..=UPDATES_MAX_PAGES {
+            let updates: Vec<JetBrainsUpdate> = get_json(
+                format!(
+                    "{}/{}/updates?channel=&page={}&size={}",
+                    JETBRAINS_API, id, page, UPDATES_PAGE_SIZE
+                ),
+                &plugin,
+            )
+            .await?;
+            let last_page = updates.len() < UPDATES_PAGE_SIZE;
+
+            let compatible = updates.into_iter().find(|update| {
+                update.channel.is_empty()
+                    && build_number.as_ref().map_or(true, |build| {
+                        is_compatible(build, update.since.as_deref(), update.until.as_deref())
+                    })
+            });
+            if let Some(update) = compatible {
+                return Ok(JetBrainsPlugin { info, update });
+            }
+            if last_page {
+                break;
+            }
+        }
+
+        Err(eyre!(
+            "No update of {} is compatible with build {}",
+            plugin,
+            build.unwrap_or_default()
+        ))
+    }
+
+    pub async fn get_with_version(
+        plugin: String,
+        version: String,
+        build: Option<String>,
+    ) -> Result<Self, Report> {
+        let id = plugin_id(&plugin)?;
+        let info: JetBrainsPluginInfo =
+            get_json(format!("{}/{}", JETBRAINS_API, id), &plugin).await?;
+
+        for page in 1..=UPDATES_MAX_PAGES {
+            let updates: Vec<JetBrainsUpdate> = get_json(
+                format!(
+                    "{}/{}/updates?page={}&size={}",
+                    JETBRAINS_API, id, page, UPDATES_PAGE_SIZE
+                ),
+                &plugin,
+            )
+            .await?;
+            let last_page = updates.len() < UPDATES_PAGE_SIZE;
+
+            if let Some(update) = updates.into_iter().find(|u| u.version == version) {
+                if let Some(build_number) = build.as_deref().and_then(build_components) {
+                    if !is_compatible(
+                        &build_number,
+                        update.since.as_deref(),
+                        update.until.as_deref(),
+                    ) {
+                        warn!(
+                            "{} {} is not compatible with build {}",
+                            plugin,
+                            version,
+                            build.unwrap_or_default()
+                        );
+                    }
+                }
+                return Ok(JetBrainsPlugin { info, update });
+            }
+            if last_page {
+                break;
+            }
+        }
+
+        Err(eyre!("No version {} found for {}", version, plugin))
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_nixpkg(self, pname: String) -> Result<NixPackage, Report> {
+        let url = format!("{}/{}", JETBRAINS_DOWNLOADS, self.update.file);
+        let sha256 = task::block_in_place(|| Handle::current().block_on(get_hash(&url)))
+            .wrap_err_with(|| format!("Unable to get the hash of {}", url))?;
+
+        let jar = if self.update.file.ends_with(".jar") {
+            self.update.file.rsplit('/').next().map(String::from)
+        } else {
+            None
+        };
+
+        let plugin_page = format!("{}{}", JETBRAINS_MARKETPLACE, self.info.link);
+        let (project_url, source_code_url) = match self.info.urls {
+            Some(urls) => (urls.url, urls.source_code_url),
+            None => (None, None),
+        };
+        let meta = NixPackageMeta {
+            description: self
+                .info
+                .preview
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .or(Some(self.info.name)),
+            ..NixPackageMeta::from_links(PackageLinks {
+                homepage: project_url,
+                repository: source_code_url,
+                store_page: Some(plugin_page),
+            })
+        };
+
+        Ok(NixPackage {
+            name: pname.clone(),
+            pname,
+            src: url,
+            version: self.update.version,
+            sha256,
+            meta,
+            fetcher: NixFetcher::default(),
+            builder: NixBuilder::JetBrainsPlugin { jar },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_build_components() {
+        assert_eq!(
+            build_components("IU-232.9921.47"),
+            Some(vec![Some(232), Some(9921), Some(47)])
+        );
+        assert_eq!(build_components("232.*"), Some(vec![Some(232), None]));
+        assert_eq!(
+            build_components("233.SNAPSHOT"),
+            Some(vec![Some(233), Some(u64::MAX)])
+        );
+        assert_eq!(build_components("latest"), None);
+    }
+
+    #[test]
+    fn test_is_compatible() {
+        let build = build_components("IU-232.9921.47").unwrap();
+
+        assert!(is_compatible(&build, Some("232.8660"), Some("232.*")));
+        assert!(is_compatible(&build, Some("223"), None));
+        assert!(is_compatible(&build, None, Some("232")));
+        assert!(is_compatible(
+            &build,
+            Some("232.9921.47"),
+            Some("232.9921.47")
+        ));
+        assert!(!is_compatible(&build, Some("233.11799"), Some("233.*")));
+        assert!(!is_compatible(&build, Some("223.7571"), Some("231.*")));
+        assert!(!is_compatible(&build, Some("231"), Some("232.9000")));
+    }
+
+    #[test]
+    fn test_plugin_id() {
+        assert_eq!(plugin_id("8182").unwrap(), 8182);
+        assert_eq!(plugin_id("8182-rust").unwrap(), 8182);
+        assert!(plugin_id("org.rust.lang").is_err());
+    }
+}
@@ -1,125 +1,159 @@
+pub mod amo;
+pub mod crates;
+pub mod error;
+pub mod git;
 pub mod github;
+pub mod gitlab;
+pub mod jetbrains;
+pub mod npm;
 pub mod openvsx;
+pub mod pypi;
+pub mod readme;
+pub mod vscode;
 pub mod vscodemarketplace;
+pub mod vsix;
 
-use pulldown_cmark::{Event, Options, Parser, Tag};
-use tempfile::Builder;
+use flate2::read::GzDecoder;
+use reqwest::RequestBuilder;
+use tempfile::{Builder, TempDir};
+use tracing::warn;
 
 use color_eyre::{
-    eyre::{eyre, Report, Result, WrapErr},
+    eyre::{eyre, Result, WrapErr},
     Section,
 };
 
-use std::{fs::File, io::copy, process::Command};
+use std::{
+    fs::{self, File},
+    io::copy,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-pub async fn get_hash(url: &str) -> Result<String> {
+/// Download `url` into a new temporary directory, returning the directory
+/// (deleted once dropped) and the path of the downloaded file.
+pub async fn download(url: &str) -> Result<(TempDir, PathBuf)> {
+    download_from(reqwest::Client::new().get(url)).await
+}
+
+/// Like [`download`], for a request that needs more than its URL, eg. a token.
+pub async fn download_from(request: RequestBuilder) -> Result<(TempDir, PathBuf)> {
     // Create a temporary directoty inside of the directory returned by `std::env::temp_dir()`
     let tmp_dir = Builder::new().prefix(env!("CARGO_PKG_NAME")).tempdir()?;
 
-    let response = reqwest::get(url).await?;
+    let response = request
+        .header("User-Agent", env!("CARGO_PKG_NAME"))
+        .send()
+        .await?
+        .error_for_status()?;
 
     let (mut dest_file, dest_path) = {
         let fname = response
             .url()
             .path_segments()
-            .and_then(|segments| segments.last())
+            .and_then(|mut segments| segments.next_back())
             .and_then(|name| if name.is_empty() { None } else { Some(name) })
             .unwrap_or("tmp.vsix");
 
         let fname = tmp_dir.path().join(fname);
         (File::create(&fname)?, fname)
     };
-    let content = response.text().await?;
-    copy(&mut content.as_bytes(), &mut dest_file)?;
+    let content = response.bytes().await?;
+    copy(&mut content.as_ref(), &mut dest_file)?;
 
-    let nix_hash_process = Command::new("nix-hash")
-        .arg("--flat")
+    Ok((tmp_dir, dest_path))
+}
+
+/// Hash `path` with `nix-hash`, as a flat file (`fetchurl`) or as a NAR
+/// of the directory (`fetchzip` and the fetchers built on it).
+pub fn nix_hash(path: &Path, flat: bool) -> Result<String> {
+    let mut nix_hash_command = Command::new("nix-hash");
+    if flat {
+        nix_hash_command.arg("--flat");
+    }
+    let nix_hash_process = nix_hash_command
         .arg("--base32")
         .arg("--type")
         .arg("sha256")
-        .arg(&dest_path.to_str().unwrap())
+        .arg(path)
         .output()
-        .unwrap();
+        .wrap_err("Unable to run nix-hash")
+        .suggestion("Make sure nix is installed and nix-hash is in your PATH")?;
+
+    if nix_hash_process.status.success() {
+        Ok(String::from_utf8(nix_hash_process.stdout)?
+            .trim()
+            .to_string())
+    } else {
+        Err(eyre!(
+            "nix-hash failed: {}",
+            String::from_utf8_lossy(&nix_hash_process.stderr).trim()
+        ))
+    }
+}
+
+/// Unpack the gzipped tarball `archive` into `into`, returning the path of
+/// the directory fetchers hash: the single top-level directory, if any.
+pub fn unpack_tarball(archive: &Path, into: &Path) -> Result<PathBuf> {
+    let tarball = File::open(archive)?;
+    tar::Archive::new(GzDecoder::new(tarball))
+        .unpack(into)
+        .wrap_err_with(|| format!("Unable to unpack {}", archive.display()))?;
+
+    let mut entries: Vec<PathBuf> = fs::read_dir(into)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    if entries.len() == 1 && entries[0].is_dir() {
+        Ok(entries.remove(0))
+    } else {
+        Ok(into.to_path_buf())
+    }
+}
+
+pub async fn get_hash(url: &str) -> Result<String> {
+    let (tmp_dir, dest_path) = download(url).await?;
 
-    let hash = String::from_utf8(nix_hash_process.stdout)?;
+    let hash = nix_hash(&dest_path, true)?;
 
     // By closing the `TempDir` explicitly, we can check that it has
     // been deleted successfully. If we don't close it explicitly,
     // the directory will still be deleted when `dir` goes out
     // of scope, but we won't know whether deleting the directory
     // succeeded.
-    //drop(input_file);
     tmp_dir.close()?;
 
     Ok(hash)
 }
 
-#[derive(PartialEq)]
-enum ProgressLongDesc {
-    LookingForMainHeader,
-    FoundMainHeader,
-    ReadingText,
-    Done,
+/// The changelog at `url`, to describe updates with. Failing to get it is only worth a
+/// warning.
+pub async fn get_changelog(url: &str) -> Option<String> {
+    let text = async { reqwest::get(url).await?.error_for_status()?.text().await };
+    match text.await {
+        Ok(text) => Some(text),
+        Err(e) => {
+            warn!("Unable to get the changelog at {}: {}", url, e);
+            None
+        }
+    }
 }
 
-pub async fn get_long_description(url: String) -> Result<String, Report> {
-    let resp = reqwest::get(url).await?;
-
-    let status = resp.status();
-
-    if status.is_success() {
-        let markdown = resp.text().await?;
-        let mut long_description = String::new();
-        let mut progress = ProgressLongDesc::LookingForMainHeader;
-        let parser = Parser::new_ext(&markdown, Options::empty());
-
-        for event in parser {
-            match event {
-                Event::Start(inner) => match inner {
-                    Tag::Heading(_n) => {
-                        if progress == ProgressLongDesc::LookingForMainHeader {
-                            progress = ProgressLongDesc::FoundMainHeader;
-                        } else if progress == ProgressLongDesc::ReadingText {
-                            progress = ProgressLongDesc::Done;
-                        }
-                    }
-                    Tag::Paragraph => {
-                        if progress == ProgressLongDesc::FoundMainHeader {
-                            progress = ProgressLongDesc::ReadingText;
-                        }
-                    }
-                    _ => (),
-                },
-                Event::Text(cow_str) => {
-                    if progress == ProgressLongDesc::ReadingText {
-                        long_description.push_str(cow_str.into_string().as_str())
-                    }
-                }
-                Event::SoftBreak => {
-                    if progress == ProgressLongDesc::ReadingText {
-                        long_description.push('\n')
-                    }
-                }
-                Event::End(inner) => {
-                    if inner == Tag::Paragraph && progress == ProgressLongDesc::ReadingText {
-                        progress = ProgressLongDesc::Done;
-                    }
-                }
-                _ => (),
-            }
-
-            if progress == ProgressLongDesc::Done {
-                break;
-            }
-        }
+/// Download and unpack a source archive, returning the hash `fetchzip`-based
+/// fetchers (`fetchFromGitHub`, `fetchFromGitLab`, ...) expect.
+pub async fn get_unpacked_hash(url: &str) -> Result<String> {
+    get_unpacked_hash_from(reqwest::Client::new().get(url)).await
+}
 
-        Ok(long_description)
-    } else if let Some(reason) = status.canonical_reason() {
-        Err(eyre!(
-            "Recieved {}, while attempting to get meta.longDescription.",
-            reason
-        ))
-    } else {
-        Err(eyre!("{}", status.to_string()))
-    }
+/// Like [`get_unpacked_hash`], for a request that needs more than its URL, eg. a token.
+pub async fn get_unpacked_hash_from(request: RequestBuilder) -> Result<String> {
+    let (tmp_dir, dest_path) = download_from(request).await?;
+    let unpack_dir = tmp_dir.path().join("unpacked");
+    fs::create_dir(&unpack_dir)?;
+
+    let source_dir = unpack_tarball(&dest_path, &unpack_dir)?;
+    let hash = nix_hash(&source_dir, false)?;
+
+    tmp_dir.close()?;
+
+    Ok(hash)
 }
@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use color_eyre::{
+    eyre::{eyre, Report, Result},
+    Section,
+};
+use tracing::warn;
+
+use std::collections::HashMap;
+
+use crate::nix::{
+    hex_to_sri, LicenseConfidence, NixBuilder, NixFetcher, NixLicense, NixPackage, NixPackageMeta,
+    PackageLinks,
+};
+
+const NPM_REGISTRY: &str = "https://registry.npmjs.org";
+const NPM_WEBSITE: &str = "https://www.npmjs.com/package";
+
+/// /{package}, the registry's packument
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NpmPackument {
+    pub name: String,
+    #[serde(rename = "dist-tags", default)]
+    pub dist_tags: HashMap<String, String>,
+    #[serde(default)]
+    pub versions: HashMap<String, NpmVersion>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NpmVersion {
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    /// Either `"MIT"` or, in older packages, `{ "type": "MIT" }`
+    pub license: Option<Value>,
+    /// Either a url or `{ "type": "git", "url": ... }`
+    pub repository: Option<Value>,
+    pub bugs: Option<Value>,
+    pub deprecated: Option<String>,
+    pub dist: NpmDist,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NpmDist {
+    pub tarball: String,
+    pub shasum: String,
+    pub integrity: Option<String>,
+}
+
+/// Scoped packages (`@scope/name`) have their `/` escaped in registry urls.
+fn registry_url(package: &str) -> String {
+    format!("{}/{}", NPM_REGISTRY, package.replacen('/', "%2f", 1))
+}
+
+impl NpmPackument {
+    async fn get(package: &str) -> Result<Self, Report> {
+        let response = reqwest::Client::new()
+            .get(registry_url(package))
+            .header("User-Agent", env!("CARGO_PKG_NAME"))
+            .send()
+            .await?;
+
+        let resp_status = response.status();
+
+        if resp_status.is_success() {
+            match response.json().await {
+                Ok(packument) => Ok(packument),
+                Err(e) => Err(eyre!("Unable to parse json from the npm registry")).error(e),
+            }
+        } else if let Some(reason) = resp_status.canonical_reason() {
+            Err(eyre!(
+                "Recieved {}, while attempting to get {} from the npm registry.",
+                reason,
+                package
+            ))
+        } else {
+            Err(eyre!("{}", resp_status.to_string()))
+        }
+    }
+
+    fn version(mut self, version: &str) -> Result<NpmVersion, Report> {
+        match self.versions.remove(version) {
+            Some(v) => Ok(v),
+            None => Err(eyre!("No version {} found for {}", version, self.name)),
+        }
+    }
+
+    fn tagged(self, tag: &str) -> Result<NpmVersion, Report> {
+        match self.dist_tags.get(tag).cloned() {
+            Some(version) => self.version(&version),
+            None => {
+                let mut tags: Vec<&String> = self.dist_tags.keys().collect();
+                tags.sort();
+                Err(eyre!("{} has no dist-tag {}", self.name, tag)).note(format!(
+                    "Available dist-tags: {}",
+                    tags.into_iter()
+                        .map(String::as_str)
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                ))
+            }
+        }
+    }
+}
+
+impl NpmVersion {
+    pub async fn get(package: String, tag: Option<String>) -> Result<Self, Report> {
+        NpmPackument::get(&package)
+            .await?
+            .tagged(tag.as_deref().unwrap_or("latest"))
+    }
+
+    pub async fn get_with_version(package: String, version: String) -> Result<Self, Report> {
+        NpmPackument::get(&package).await?.version(&version)
+    }
+
+    /// `dist.integrity` as is, or `dist.shasum` for packages published before npm recorded it.
+    fn hash(&self) -> Option<String> {
+        self.dist
+            .integrity
+            .clone()
+            .or_else(|| hex_to_sri("sha1", &self.dist.shasum))
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_nixpkg(self, pname: String) -> Result<NixPackage, Report> {
+        let sha256 = match self.hash() {
+            Some(hash) => hash,
+            None => {
+                return Err(eyre!(
+                    "{} {} has neither an integrity hash nor a valid shasum",
+                    self.name,
+                    self.version
+                ))
+            }
+        };
+
+        if let Some(deprecated) = &self.deprecated {
+            warn!(
+                "{} {} is deprecated: {}",
+                self.name, self.version, deprecated
+            );
+        }
+
+        let license = match &self.license {
+            Some(Value::String(license)) => Some(license.as_str()),
+            Some(Value::Object(license)) => license.get("type").and_then(Value::as_str),
+            _ => None,
+        }
+        .map(NixLicense::from_spdx)
+        .filter(|licenses| !licenses.is_empty());
+
+        let repository = match &self.repository {
+            Some(Value::String(url)) => Some(url.as_str()),
+            Some(Value::Object(repository)) => repository.get("url").and_then(Value::as_str),
+            _ => None,
+        };
+        let meta = NixPackageMeta {
+            description: self.description.filter(|d| !d.is_empty()),
+            license_confidence: license.as_ref().map(|_| LicenseConfidence::Spdx),
+            license,
+            ..NixPackageMeta::from_links(PackageLinks {
+                homepage: self.homepage,
+                repository: repository.map(String::from),
+                store_page: Some(format!("{}/{}", NPM_WEBSITE, self.name)),
+            })
+        };
+
+        Ok(NixPackage {
+            name: pname.clone(),
+            pname,
+            src: self.dist.tarball,
+            version: self.version,
+            sha256,
+            meta,
+            fetcher: NixFetcher::FetchUrl { name: None },
+            builder: NixBuilder::NodePackage {
+                package_name: self.name,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use serde_json::json;
+
+    fn vue_language_server() -> NpmPackument {
+        serde_json::from_value(json!({
+            "name": "@vue/language-server",
+            "dist-tags": {
+                "latest": "1.8.27",
+                "next": "2.0.0-alpha.1"
+            },
+            "versions": {
+                "1.8.27": {
+                    "name": "@vue/language-server",
+                    "version": "1.8.27",
+                    "description": "",
+                    "license": "MIT",
+                    "repository": {
+                        "type": "git",
+                        "url": "git+https://github.com/vuejs/language-tools.git"
+                    },
+                    "dist": {
+                        "tarball": "https://registry.npmjs.org/@vue/language-server/-/language-server-1.8.27.tgz",
+                        "shasum": "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+                        "integrity": "sha512-z4PhNX7vuL3xVChQ1m2AB9Yg5AULVxXcg/SpIdNs6c5H0NE8XYXysP+DGNKHfuwvY7kxvUdBeoGlODJ6+SfaPg=="
+                    }
+                },
+                "2.0.0-alpha.1": {
+                    "name": "@vue/language-server",
+                    "version": "2.0.0-alpha.1",
+                    "license": { "type": "MIT" },
+                    "dist": {
+                        "tarball": "https://registry.npmjs.org/@vue/language-server/-/language-server-2.0.0-alpha.1.tgz",
+                        "shasum": "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+                    }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_registry_url() {
+        assert_eq!(
+            registry_url("@vue/language-server"),
+            "https://registry.npmjs.org/@vue%2flanguage-server"
+        );
+        assert_eq!(
+            registry_url("typescript"),
+            "https://registry.npmjs.org/typescript"
+        );
+    }
+
+    #[test]
+    fn test_npm_latest() {
+        let pkg = vue_language_server()
+            .tagged("latest")
+            .unwrap()
+            .to_nixpkg(String::from("vue-language-server"))
+            .unwrap();
+
+        assert_eq!(pkg.version, "1.8.27");
+        assert_eq!(pkg.sha256, "sha512-z4PhNX7vuL3xVChQ1m2AB9Yg5AULVxXcg/SpIdNs6c5H0NE8XYXysP+DGNKHfuwvY7kxvUdBeoGlODJ6+SfaPg==");
+        assert_eq!(pkg.meta.description, None);
+        assert_eq!(
+            pkg.meta.homepage.as_deref(),
+            Some("https://github.com/vuejs/language-tools")
+        );
+        assert_eq!(pkg.meta.license, Some(vec![NixLicense::Mit]));
+        assert!(pkg.to_nix().contains("    hash = \"sha512-"));
+        assert!(pkg
+            .to_nix()
+            .contains("mkdir -p \"$out/lib/node_modules/@vue/language-server\""));
+    }
+
+    #[test]
+    fn test_npm_tag() {
+        let pkg = vue_language_server()
+            .tagged("next")
+            .unwrap()
+            .to_nixpkg(String::from("vue-language-server"))
+            .unwrap();
+
+        assert_eq!(pkg.version, "2.0.0-alpha.1");
+        assert_eq!(pkg.sha256, "sha1-2jmj7l5rSw0yVb/vlWAYkK/YBwk=");
+        assert_eq!(pkg.meta.license, Some(vec![NixLicense::Mit]));
+
+        assert!(vue_language_server().tagged("beta").is_err());
+    }
+}
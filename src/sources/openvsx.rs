@@ -1,18 +1,29 @@
 use serde::{Deserialize, Serialize};
 
 use color_eyre::{
-    eyre::{eyre, Report, Result, WrapErr},
+    eyre::{eyre, Result, WrapErr},
     Section,
 };
+use reqwest::StatusCode;
+use semver::Version;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::{
-    nix::{NixLicense, NixPackage, NixPackageMeta},
-    sources::{get_hash, get_long_description},
+    nix::{
+        LicenseConfidence, NixBuilder, NixFetcher, NixLicense, NixPackage, NixPackageMeta,
+        NixPlatforms, PackageLinks, PackageStatus,
+    },
+    sources::{
+        error::SourceError,
+        get_changelog, readme,
+        vscode::{engine_accepts, vscode_target_systems, VSCodeExtensionManifest},
+        vsix,
+    },
 };
 
 use tokio::{runtime::Handle, task};
+use tracing::warn;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -41,12 +52,53 @@ pub struct OpenVSXExtension {
     pub extension_kind: Vec<String>,
     pub tags: Vec<String>,
     pub license: String,
+    /// The homepage in package.json, unlike `published_by.homepage` which is the publisher's
+    #[serde(default)]
+    pub homepage: Option<String>,
     pub repository: String,
     pub bugs: String,
     pub gallery_color: Option<String>,
     pub gallery_theme: Option<String>,
     pub dependencies: Vec<String>,
     pub bundled_extensions: Vec<String>,
+    /// `universal`, or the platform this VSIX was built for, eg. `linux-x64`
+    #[serde(default)]
+    pub target_platform: Option<String>,
+    #[serde(default)]
+    pub all_target_platform_versions: Option<Vec<OpenVSXTargetPlatformVersion>>,
+    /// Published as a pre-release, which VS Code only installs when asked to
+    #[serde(default)]
+    pub pre_release: bool,
+    /// Deprecated by its publisher, in favour of `replacement` when there is one
+    #[serde(default)]
+    pub deprecated: bool,
+    #[serde(default)]
+    pub replacement: Option<OpenVSXReplacement>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenVSXReplacement {
+    pub url: String,
+    pub display_name: String,
+}
+
+impl OpenVSXReplacement {
+    /// The unique id of the replacement, when it is on Open VSX, or else its name.
+    fn name(&self) -> String {
+        self.url
+            .strip_prefix("https://open-vsx.org/extension/")
+            .and_then(|path| path.trim_end_matches('/').split_once('/'))
+            .map(|(namespace, name)| format!("{}.{}", namespace, name))
+            .unwrap_or_else(|| self.display_name.clone())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenVSXTargetPlatformVersion {
+    pub version: String,
+    pub target_platforms: Vec<String>,
 }
 
 #[allow(non_snake_case)]
@@ -57,6 +109,8 @@ pub struct OpenVSXExtensionFile {
     readme: String,
     icon: String,
     manifest: String,
+    #[serde(default)]
+    license: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -80,10 +134,12 @@ impl OpenVSXExtension {
             extension = extension
         );
 
-        let response: OpenVSXExtension =
-            reqwest::Client::new().get(url).send().await?.json().await?;
-
-        Ok(response)
+        // Open VSX answers with a 404 once an extension is removed
+        let response = reqwest::Client::new().get(url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(SourceError::Unpublished(unique_id).into());
+        }
+        Ok(response.error_for_status()?.json().await?)
     }
 
     pub async fn get_with_version(unique_id: String, version: String) -> Result<Self> {
@@ -103,71 +159,232 @@ impl OpenVSXExtension {
         Ok(response)
     }
 
-    pub fn to_nixpkg(self, pname: String, extract: Option<String>) -> NixPackage {
+    /// The newest stable version whose `engines.vscode` accepts VS Code `vscode`, or
+    /// any version accepted when no VS Code version is configured. Pre-releases are
+    /// only picked when `prerelease` is set.
+    pub async fn get_compatible(
+        unique_id: String,
+        vscode: Option<&Version>,
+        prerelease: bool,
+    ) -> Result<Self> {
+        let usable = |extension: &Self| {
+            (prerelease || !extension.pre_release) && vscode.map_or(true, |v| extension.accepts(v))
+        };
+
+        let latest = Self::get(unique_id.clone()).await?;
+        if usable(&latest) {
+            return Ok(latest);
+        }
+
+        let mut versions: Vec<Version> = latest
+            .all_versions
+            .keys()
+            .filter_map(|version| Version::parse(version).ok())
+            .filter(|version| {
+                (prerelease || version.pre.is_empty()) && version.to_string() != latest.version
+            })
+            .collect();
+        versions.sort();
+        for version in versions.into_iter().rev() {
+            let extension = Self::get_with_version(unique_id.clone(), version.to_string()).await?;
+            if usable(&extension) {
+                return Ok(extension);
+            }
+        }
+
+        let kind = if prerelease {
+            "version"
+        } else {
+            "stable version"
+        };
+        let report = match vscode {
+            Some(vscode) => eyre!(
+                "No {} of {} is compatible with VS Code {}",
+                kind,
+                unique_id,
+                vscode
+            ),
+            None => eyre!("{} has no {}", unique_id, kind),
+        };
+        if prerelease {
+            Err(report)
+        } else {
+            Err(report).suggestion("Set `prerelease = true` to allow pre-release versions")
+        }
+    }
+
+    /// Whether the publisher deprecated the extension.
+    pub fn status(&self) -> Option<PackageStatus> {
+        self.deprecated.then(|| PackageStatus::Deprecated {
+            replacement: self.replacement.as_ref().map(OpenVSXReplacement::name),
+        })
+    }
+
+    /// Whether VS Code `vscode` loads this version.
+    pub fn accepts(&self, vscode: &Version) -> bool {
+        engine_accepts(self.engines.get("vscode").map(String::as_str), vscode)
+    }
+
+    /// The `targetPlatform` of this version's VSIX for each Nix system, empty when it
+    /// is only published as `universal`.
+    pub fn target_systems(&self) -> BTreeMap<String, String> {
+        vscode_target_systems(
+            self.all_target_platform_versions
+                .iter()
+                .flatten()
+                .filter(|v| v.version == self.version)
+                .flat_map(|v| v.target_platforms.iter().map(String::as_str)),
+        )
+    }
+
+    /// The `package.json` of this version, naming the extensions it depends on.
+    pub async fn manifest(&self) -> Result<VSCodeExtensionManifest> {
+        VSCodeExtensionManifest::get(&self.files.manifest).await
+    }
+
+    /// The licenses of this version, from its `license` field, or its license file when
+    /// the field is empty, `SEE LICENSE IN ...` or unrecognised.
+    fn license(&self) -> (Option<Vec<NixLicense>>, Option<LicenseConfidence>) {
+        if let Some(found) = NixLicense::from_name(&self.license) {
+            return (Some(vec![found.license]), Some(found.confidence));
+        }
+        if !self.license.is_empty() && !self.license.starts_with("SEE LICENSE IN") {
+            let licenses = NixLicense::from_spdx(&self.license);
+            if !licenses.is_empty() {
+                return (Some(licenses), Some(LicenseConfidence::Spdx));
+            }
+        }
+
+        let url = match &self.files.license {
+            Some(url) => url.clone(),
+            None => return (None, None),
+        };
+        let text = task::block_in_place(|| {
+            Handle::current().block_on(async move {
+                reqwest::Client::new()
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await
+            })
+        });
+        match text.map(|text| NixLicense::from_text(&text)) {
+            Ok(Some(found)) => (Some(vec![found.license]), Some(found.confidence)),
+            Ok(None) => (None, None),
+            Err(e) => {
+                warn!(
+                    "Unable to get the license file of {}.{}: {}",
+                    self.namespace, self.name, e
+                );
+                (None, None)
+            }
+        }
+    }
+
+    /// `dependencies` are the unique ids of the extensions installing this one has to
+    /// install too.
+    pub fn to_nixpkg(
+        self,
+        pname: String,
+        extract: Option<String>,
+        dependencies: Vec<String>,
+    ) -> Result<NixPackage> {
+        let target_systems = self.target_systems();
+        let status = self.status();
+        let (license, license_confidence) = self.license();
         let namespace: String = self.namespace;
         let extension: String = self.name;
         let version: String = self.version;
         let src = format!("https://open-vsx.org/api/{namespace}/{extension}/{version}/file/{namespace}.{extension}-{version}.vsix", namespace=&namespace, extension=&extension, version=&version);
         let src_clone = &src.to_string();
         let readme = self.files.readme;
+        // extensions without a changelog have an empty link to it
+        let changelog = Some(self.files.changelog).filter(|changelog| !changelog.is_empty());
 
-        let long_description: String = task::block_in_place(move || {
-            Handle::current().block_on(async move {
-                // do something async
-                get_long_description(readme)
-                    .await
-                    .expect("Error: unable to get readme of extension")
-            })
+        let long_description = task::block_in_place(|| {
+            Handle::current().block_on(readme::get_long_description(&readme))
         });
-
-        let sha256: String = task::block_in_place(move || {
-            Handle::current().block_on(async move {
-                // do something async
-                get_hash(src_clone)
-                    .await
-                    .expect("Error: unable to get hash of extension's vsix")
-            })
+        let changelog_text = changelog.as_ref().and_then(|changelog| {
+            task::block_in_place(|| Handle::current().block_on(get_changelog(changelog)))
         });
 
-        let description = if !&self.description.is_empty() {
-            Some(String::from(&self.description))
-        } else {
-            None
-        };
-
-        let long_description = if !long_description.is_empty() {
-            Some(long_description)
+        let name = Some(format!("{}-{}.zip", namespace, extension));
+        let (sha256, fetcher, platforms, native) = if target_systems.is_empty() {
+            let (sha256, native) = task::block_in_place(|| {
+                Handle::current().block_on(vsix::get_hash_and_native_binaries(src_clone))
+            })
+            .wrap_err_with(|| format!("Unable to get the hash of {}", src_clone))?;
+            (sha256, NixFetcher::FetchUrl { name }, None, native)
         } else {
-            None
+            let urls = target_systems
+                .iter()
+                .map(|(system, target)| {
+                    (system.clone(), format!("https://open-vsx.org/api/{namespace}/{extension}/{target}/{version}/file/{namespace}.{extension}-{version}@{target}.vsix", namespace=&namespace, extension=&extension, target=target, version=&version))
+                })
+                .collect();
+            let (sources, native) = task::block_in_place(|| {
+                Handle::current().block_on(vsix::get_system_sources_and_native_binaries(urls))
+            })
+            .wrap_err_with(|| format!("Unable to get the hashes of {}.{}", namespace, extension))?;
+            (
+                String::new(),
+                NixFetcher::FetchUrlBySystem { name, sources },
+                Some(NixPlatforms::Systems(target_systems.into_keys().collect())),
+                native,
+            )
         };
 
-        let homepage = if !&self.published_by.homepage.is_empty() {
-            Some(String::from(&self.published_by.homepage))
+        let description = if !&self.description.is_empty() {
+            Some(String::from(&self.description))
         } else {
             None
         };
 
-        let nix_license = NixLicense::from_str(&self.license);
+        // the description is the one in package.json
+        let long_description = long_description.or_else(|| description.clone());
 
-        let license = nix_license.map(|lic| vec![*lic]);
+        // issues are mostly tracked in the repository
+        let bugs = self.bugs;
+        let repository = Some(self.repository)
+            .filter(|repository| !repository.is_empty())
+            .or_else(|| bugs.strip_suffix("/issues").map(String::from));
 
         let meta = NixPackageMeta {
             description,
             long_description,
-            homepage,
             license,
-            changelog: Some(vec![self.files.changelog]),
-            ..Default::default()
+            license_confidence,
+            changelog: changelog.map(|changelog| vec![changelog]),
+            changelog_text,
+            status,
+            platforms,
+            ..NixPackageMeta::from_links(PackageLinks {
+                homepage: self.homepage,
+                repository,
+                store_page: Some(format!(
+                    "https://open-vsx.org/extension/{}/{}",
+                    namespace, extension
+                )),
+            })
         };
 
-        NixPackage {
+        Ok(NixPackage {
             name: pname.clone(),
             pname,
             src,
             version,
             sha256,
             meta,
-        }
+            fetcher,
+            builder: NixBuilder::VSCodeExtension {
+                publisher: namespace,
+                name: extension,
+                dependencies,
+                native,
+            },
+        })
     }
 }
 
@@ -361,4 +578,100 @@ mod tests {
             indent_one_space_0_2_6.bundled_extensions
         );
     }
+
+    fn rust_analyzer() -> OpenVSXExtension {
+        serde_json::from_value(json!({
+            "namespaceUrl":"https://open-vsx.org/api/rust-lang",
+            "reviewsUrl":"https://open-vsx.org/api/rust-lang/rust-analyzer/reviews",
+            "files":{
+                "changelog":"",
+                "download":"https://open-vsx.org/api/rust-lang/rust-analyzer/linux-x64/0.3.1850/file/rust-lang.rust-analyzer-0.3.1850@linux-x64.vsix",
+                "readme":"https://open-vsx.org/api/rust-lang/rust-analyzer/linux-x64/0.3.1850/file/README.md",
+                "icon":"https://open-vsx.org/api/rust-lang/rust-analyzer/linux-x64/0.3.1850/file/icon.png",
+                "manifest":"https://open-vsx.org/api/rust-lang/rust-analyzer/linux-x64/0.3.1850/file/package.json"
+            },
+            "name":"rust-analyzer",
+            "namespace":"rust-lang",
+            "version":"0.3.1850",
+            "targetPlatform":"linux-x64",
+            "publishedBy":{
+                "loginName":"rust-lang-owner",
+                "fullName":"",
+                "avatarUrl":"",
+                "homepage":"",
+                "provider":"github"
+            },
+            "verified":true,
+            "unrelatedPublisher":false,
+            "namespaceAccess":"restricted",
+            "allVersions":{},
+            "allTargetPlatformVersions":[
+                {"version":"0.3.1850","targetPlatforms":["alpine-x64","darwin-arm64","darwin-x64","linux-arm64","linux-armhf","linux-x64","win32-x64"]},
+                {"version":"0.3.1839","targetPlatforms":["linux-x64"]}
+            ],
+            "reviewCount":0,
+            "versionAlias":["latest"],
+            "timestamp":"2024-02-26T08:51:32.785936Z",
+            "preview":false,
+            "displayName":"rust-analyzer",
+            "description":"Rust language support for Visual Studio Code",
+            "engines":{"vscode":"^1.78.0"},
+            "categories":["Programming Languages"],
+            "extensionKind":[],
+            "tags":["rust"],
+            "license":"MIT OR Apache-2.0",
+            "repository":"https://github.com/rust-lang/rust-analyzer",
+            "bugs":"https://github.com/rust-lang/rust-analyzer/issues",
+            "dependencies":[],
+            "bundledExtensions":[]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_target_systems() {
+        let mut rust_analyzer = rust_analyzer();
+
+        let systems = rust_analyzer.target_systems();
+        assert_eq!(
+            systems.keys().map(String::as_str).collect::<Vec<_>>(),
+            vec![
+                "aarch64-darwin",
+                "aarch64-linux",
+                "armv7l-linux",
+                "x86_64-darwin",
+                "x86_64-linux"
+            ]
+        );
+        assert_eq!(systems["aarch64-darwin"], "darwin-arm64");
+
+        rust_analyzer.all_target_platform_versions = Some(vec![OpenVSXTargetPlatformVersion {
+            version: String::from("0.3.1850"),
+            target_platforms: vec![String::from("universal"), String::from("linux-x64")],
+        }]);
+        assert!(rust_analyzer.target_systems().is_empty());
+    }
+
+    #[test]
+    fn test_status() {
+        let mut extension = rust_analyzer();
+        assert_eq!(extension.status(), None);
+
+        extension.deprecated = true;
+        assert_eq!(
+            extension.status(),
+            Some(PackageStatus::Deprecated { replacement: None })
+        );
+
+        extension.replacement = Some(OpenVSXReplacement {
+            url: String::from("https://open-vsx.org/extension/rust-lang/rust-analyzer"),
+            display_name: String::from("rust-analyzer"),
+        });
+        assert_eq!(
+            extension.status(),
+            Some(PackageStatus::Deprecated {
+                replacement: Some(String::from("rust-lang.rust-analyzer"))
+            })
+        );
+    }
 }
@@ -0,0 +1,577 @@
+use serde::{Deserialize, Serialize};
+
+use color_eyre::{
+    eyre::{eyre, Report, Result},
+    Section,
+};
+
+use std::collections::HashMap;
+
+use crate::nix::{
+    hex_to_nix_base32, LicenseConfidence, NixBuilder, NixFetcher, NixLicense, NixPackage,
+    NixPackageMeta, PackageLinks, WheelTag,
+};
+
+const PYPI_API: &str = "https://pypi.org/pypi";
+const PYPI_PROJECTS: &str = "https://pypi.org/project";
+
+/*
+    Trove classifiers taken from
+    https://pypi.org/classifiers/
+    mapped to their SPDX identifier where there is an unambiguous one.
+*/
+const LICENSE_CLASSIFIERS: &[(&str, &str)] = &[
+    (
+        "License :: OSI Approved :: Academic Free License (AFL)",
+        "AFL-3.0",
+    ),
+    (
+        "License :: OSI Approved :: Apache Software License",
+        "Apache-2.0",
+    ),
+    (
+        "License :: OSI Approved :: Artistic License",
+        "Artistic-2.0",
+    ),
+    (
+        "License :: OSI Approved :: Boost Software License 1.0 (BSL-1.0)",
+        "BSL-1.0",
+    ),
+    (
+        "License :: OSI Approved :: Eclipse Public License 1.0 (EPL-1.0)",
+        "EPL-1.0",
+    ),
+    (
+        "License :: OSI Approved :: Eclipse Public License 2.0 (EPL-2.0)",
+        "EPL-2.0",
+    ),
+    (
+        "License :: OSI Approved :: European Union Public Licence 1.1 (EUPL 1.1)",
+        "EUPL-1.1",
+    ),
+    (
+        "License :: OSI Approved :: European Union Public Licence 1.2 (EUPL 1.2)",
+        "EUPL-1.2",
+    ),
+    (
+        "License :: OSI Approved :: GNU Affero General Public License v3",
+        "AGPL-3.0-only",
+    ),
+    (
+        "License :: OSI Approved :: GNU Affero General Public License v3 or later (AGPLv3+)",
+        "AGPL-3.0-or-later",
+    ),
+    (
+        "License :: OSI Approved :: GNU Free Documentation License (FDL)",
+        "GFDL-1.3",
+    ),
+    (
+        "License :: OSI Approved :: GNU General Public License v2 (GPLv2)",
+        "GPL-2.0-only",
+    ),
+    (
+        "License :: OSI Approved :: GNU General Public License v2 or later (GPLv2+)",
+        "GPL-2.0-or-later",
+    ),
+    (
+        "License :: OSI Approved :: GNU General Public License v3 (GPLv3)",
+        "GPL-3.0-only",
+    ),
+    (
+        "License :: OSI Approved :: GNU General Public License v3 or later (GPLv3+)",
+        "GPL-3.0-or-later",
+    ),
+    (
+        "License :: OSI Approved :: GNU Lesser General Public License v2 (LGPLv2)",
+        "LGPL-2.0-only",
+    ),
+    (
+        "License :: OSI Approved :: GNU Lesser General Public License v2 or later (LGPLv2+)",
+        "LGPL-2.0-or-later",
+    ),
+    (
+        "License :: OSI Approved :: GNU Lesser General Public License v3 (LGPLv3)",
+        "LGPL-3.0-only",
+    ),
+    (
+        "License :: OSI Approved :: GNU Lesser General Public License v3 or later (LGPLv3+)",
+        "LGPL-3.0-or-later",
+    ),
+    ("License :: OSI Approved :: ISC License (ISCL)", "ISC"),
+    ("License :: OSI Approved :: MIT License", "MIT"),
+    (
+        "License :: OSI Approved :: MIT No Attribution License (MIT-0)",
+        "MIT-0",
+    ),
+    (
+        "License :: OSI Approved :: Mozilla Public License 1.0 (MPL)",
+        "MPL-1.0",
+    ),
+    (
+        "License :: OSI Approved :: Mozilla Public License 1.1 (MPL 1.1)",
+        "MPL-1.1",
+    ),
+    (
+        "License :: OSI Approved :: Mozilla Public License 2.0 (MPL 2.0)",
+        "MPL-2.0",
+    ),
+    (
+        "License :: OSI Approved :: PostgreSQL License",
+        "PostgreSQL",
+    ),
+    (
+        "License :: OSI Approved :: Python Software Foundation License",
+        "Python-2.0",
+    ),
+    (
+        "License :: OSI Approved :: The Unlicense (Unlicense)",
+        "Unlicense",
+    ),
+    (
+        "License :: OSI Approved :: Universal Permissive License (UPL)",
+        "UPL-1.0",
+    ),
+    ("License :: OSI Approved :: zlib/libpng License", "Zlib"),
+    ("License :: OSI Approved :: Zope Public License", "ZPL-2.1"),
+    (
+        "License :: CC0 1.0 Universal (CC0 1.0) Public Domain Dedication",
+        "CC0-1.0",
+    ),
+];
+
+/// `project_urls` keys, lowercased, in the order they are preferred.
+const HOMEPAGE_KEYS: &[&str] = &["homepage", "home", "home page"];
+const REPOSITORY_KEYS: &[&str] = &["source", "source code", "repository", "code", "github"];
+const CHANGELOG_KEYS: &[&str] = &[
+    "changelog",
+    "change log",
+    "changes",
+    "release notes",
+    "history",
+    "what's new",
+];
+
+/// /pypi/{project}/json and /pypi/{project}/{version}/json
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PyPIResponse {
+    pub info: PyPIInfo,
+    pub urls: Vec<PyPIUrl>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PyPIInfo {
+    pub name: String,
+    pub version: String,
+    pub summary: Option<String>,
+    pub home_page: Option<String>,
+    pub license: Option<String>,
+    #[serde(default)]
+    pub classifiers: Vec<String>,
+    pub project_urls: Option<HashMap<String, String>>,
+    pub requires_python: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PyPIUrl {
+    pub filename: String,
+    pub packagetype: String,
+    pub python_version: String,
+    pub url: String,
+    pub digests: PyPIDigests,
+    #[serde(default)]
+    pub yanked: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PyPIDigests {
+    pub md5: String,
+    pub sha256: String,
+}
+
+#[derive(Debug)]
+pub struct PyPIProject {
+    pub info: PyPIInfo,
+    pub file: PyPIUrl,
+    /// Set when `file` is a wheel rather than an sdist
+    pub wheel: Option<WheelTag>,
+}
+
+impl PyPIResponse {
+    async fn get(url: String, project: &str) -> Result<Self, Report> {
+        let response = reqwest::Client::new()
+            .get(url)
+            .header("User-Agent", env!("CARGO_PKG_NAME"))
+            .send()
+            .await?;
+
+        let resp_status = response.status();
+
+        if resp_status.is_success() {
+            match response.json().await {
+                Ok(pypi_response) => Ok(pypi_response),
+                Err(e) => Err(eyre!("Unable to parse json from PyPI")).error(e),
+            }
+        } else if let Some(reason) = resp_status.canonical_reason() {
+            Err(eyre!(
+                "Recieved {}, while attempting to get {} from PyPI.",
+                reason,
+                project
+            ))
+        } else {
+            Err(eyre!("{}", resp_status.to_string()))
+        }
+    }
+
+    /// Pick the sdist, or a pure Python 3 wheel when there is no sdist, or the wheel
+    /// matching `wheel_tag` (`py3-none-any`) if one is asked for.
+    fn select(self, wheel_tag: Option<&str>) -> Result<PyPIProject, Report> {
+        let files: Vec<PyPIUrl> = self.urls.into_iter().filter(|u| !u.yanked).collect();
+        let wheels = || {
+            files
+                .iter()
+                .filter(|u| u.packagetype == "bdist_wheel")
+                .filter_map(|u| wheel_tag_of(&u.filename).map(|wheel| (u, wheel)))
+        };
+
+        let selected = match wheel_tag {
+            Some(tag) => wheels()
+                .find(|(_, wheel)| {
+                    format!("{}-{}-{}", wheel.python, wheel.abi, wheel.platform) == tag
+                })
+                .map(|(u, wheel)| (u, Some(wheel))),
+            None => files
+                .iter()
+                .find(|u| u.packagetype == "sdist")
+                .map(|u| (u, None))
+                .or_else(|| {
+                    // projects only publishing wheels can still be built from a pure one
+                    wheels()
+                        .find(|(_, wheel)| {
+                            wheel.python.split('.').any(|python| python == "py3")
+                                && wheel.abi == "none"
+                                && wheel.platform == "any"
+                        })
+                        .map(|(u, wheel)| (u, Some(wheel)))
+                }),
+        };
+
+        match selected {
+            Some((file, wheel)) => Ok(PyPIProject {
+                info: self.info,
+                file: file.clone(),
+                wheel,
+            }),
+            None => Err(eyre!(
+                "No {} found for {} {}",
+                wheel_tag.map_or(String::from("sdist or py3-none-any wheel"), |tag| {
+                    format!("{} wheel", tag)
+                }),
+                self.info.name,
+                self.info.version
+            )),
+        }
+    }
+}
+
+/// Split `{dist}-{version}(-{build})?-{python}-{abi}-{platform}.whl` into its tag.
+fn wheel_tag_of(filename: &str) -> Option<WheelTag> {
+    let parts: Vec<&str> = filename.strip_suffix(".whl")?.rsplitn(4, '-').collect();
+    match parts.as_slice() {
+        [platform, abi, python, _] => Some(WheelTag {
+            python: python.to_string(),
+            abi: abi.to_string(),
+            platform: platform.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// Split an sdist filename, `{name}-{version}.{extension}`, into the name `fetchPypi` builds the url from and the extension.
+fn sdist_name_of(filename: &str, version: &str) -> Option<(String, String)> {
+    let (name, extension) = filename.split_once(&format!("-{}.", version))?;
+    Some((name.to_string(), extension.to_string()))
+}
+
+fn classifier_licenses(classifiers: &[String]) -> Vec<NixLicense> {
+    classifiers
+        .iter()
+        .filter_map(|classifier| {
+            LICENSE_CLASSIFIERS
+                .iter()
+                .find(|(trove, _)| trove == classifier)
+                .and_then(|(_, spdx)| NixLicense::from_str(spdx))
+        })
+        .copied()
+        .collect()
+}
+
+fn project_url(project_urls: &HashMap<String, String>, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| {
+        project_urls
+            .iter()
+            .find(|(name, _)| name.to_lowercase() == *key)
+            .map(|(_, url)| url.to_string())
+    })
+}
+
+impl PyPIProject {
+    pub async fn get(project: String, wheel: Option<String>) -> Result<Self, Report> {
+        PyPIResponse::get(format!("{}/{}/json", PYPI_API, project), &project)
+            .await?
+            .select(wheel.as_deref())
+    }
+
+    pub async fn get_with_version(
+        project: String,
+        version: String,
+        wheel: Option<String>,
+    ) -> Result<Self, Report> {
+        PyPIResponse::get(
+            format!("{}/{}/{}/json", PYPI_API, project, version),
+            &project,
+        )
+        .await?
+        .select(wheel.as_deref())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_nixpkg(self, pname: String) -> Result<NixPackage, Report> {
+        let PyPIProject { info, file, wheel } = self;
+
+        let sha256 = match hex_to_nix_base32(&file.digests.sha256) {
+            Some(sha256) => sha256,
+            None => {
+                return Err(eyre!(
+                    "Invalid sha256 digest for {}: {}",
+                    file.filename,
+                    file.digests.sha256
+                ))
+            }
+        };
+
+        let (pypi_name, extension) = match &wheel {
+            Some(_) => (
+                file.filename
+                    .split('-')
+                    .next()
+                    .unwrap_or(&info.name)
+                    .to_string(),
+                None,
+            ),
+            None => match sdist_name_of(&file.filename, &info.version) {
+                Some((name, extension)) if extension == "tar.gz" => (name, None),
+                Some((name, extension)) => (name, Some(extension)),
+                None => (info.name.clone(), None),
+            },
+        };
+
+        let classifier_license = classifier_licenses(&info.classifiers);
+        let (license, license_confidence) = if !classifier_license.is_empty() {
+            (Some(classifier_license), Some(LicenseConfidence::Name))
+        } else {
+            match info.license.as_deref().and_then(NixLicense::from_name) {
+                Some(found) => (Some(vec![found.license]), Some(found.confidence)),
+                None => (None, None),
+            }
+        };
+
+        let project_urls = info.project_urls.unwrap_or_default();
+        let home_page = info.home_page;
+        let homepage = project_url(&project_urls, HOMEPAGE_KEYS)
+            .or_else(|| home_page.filter(|h| !h.is_empty() && h != "UNKNOWN"))
+            .or_else(|| project_url(&project_urls, &["documentation"]));
+        let changelog = project_url(&project_urls, CHANGELOG_KEYS).map(|c| vec![c]);
+
+        let meta = NixPackageMeta {
+            description: info.summary.filter(|s| !s.is_empty()),
+            changelog,
+            license,
+            license_confidence,
+            ..NixPackageMeta::from_links(PackageLinks {
+                homepage,
+                repository: project_url(&project_urls, REPOSITORY_KEYS),
+                store_page: Some(format!("{}/{}/", PYPI_PROJECTS, info.name)),
+            })
+        };
+
+        Ok(NixPackage {
+            name: pname.clone(),
+            pname,
+            src: file.url,
+            version: info.version,
+            sha256,
+            meta,
+            fetcher: NixFetcher::FetchPypi {
+                pypi_name,
+                extension,
+                wheel: wheel.clone(),
+            },
+            builder: NixBuilder::PythonPackage {
+                wheel: wheel.is_some(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use serde_json::json;
+
+    fn requests() -> PyPIResponse {
+        serde_json::from_value(json!({
+            "info": {
+                "name": "requests",
+                "version": "2.26.0",
+                "summary": "Python HTTP for Humans.",
+                "home_page": "https://requests.readthedocs.io",
+                "license": "Apache 2.0",
+                "classifiers": [
+                    "Development Status :: 5 - Production/Stable",
+                    "License :: OSI Approved :: Apache Software License",
+                    "Programming Language :: Python :: 3"
+                ],
+                "project_urls": {
+                    "Documentation": "https://requests.readthedocs.io",
+                    "Source": "https://github.com/psf/requests",
+                    "Changelog": "https://github.com/psf/requests/blob/master/HISTORY.md"
+                },
+                "requires_python": ">=2.7, !=3.0.*, !=3.1.*, !=3.2.*, !=3.3.*, !=3.4.*, !=3.5.*"
+            },
+            "urls": [
+                {
+                    "filename": "requests-2.26.0-py2.py3-none-any.whl",
+                    "packagetype": "bdist_wheel",
+                    "python_version": "py2.py3",
+                    "url": "https://files.pythonhosted.org/packages/92/96/144f70b972a9c0eabbd4391ef93ccd49d0f2747f4f6a2a2738e99e5adc65/requests-2.26.0-py2.py3-none-any.whl",
+                    "digests": {
+                        "md5": "9f4ba4f1ae1e2bc8e4d9b3ee4bbbc8ba",
+                        "sha256": "6c1246513ecd5ecd4528a0906f910e8f0f9c6b8ec72030dc9fd154dc1a6efd24"
+                    },
+                    "yanked": false
+                },
+                {
+                    "filename": "requests-2.26.0.tar.gz",
+                    "packagetype": "sdist",
+                    "python_version": "source",
+                    "url": "https://files.pythonhosted.org/packages/e7/01/3569e0b535fb2e4a6c384bdbed00c55b9d78b5084e0fb7f4d0bf523d7670/requests-2.26.0.tar.gz",
+                    "digests": {
+                        "md5": "8c745949ad3e9ae83d9927fed213db8a",
+                        "sha256": "b8aa58f8cf793ffd8782d3d8cb19e66ef36f7aba4353eec859e74678b01b07a7"
+                    },
+                    "yanked": false
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_pypi_sdist() {
+        let pkg = requests()
+            .select(None)
+            .unwrap()
+            .to_nixpkg(String::from("requests"))
+            .unwrap();
+
+        assert_eq!(pkg.version, "2.26.0");
+        assert_eq!(
+            pkg.sha256,
+            hex_to_nix_base32("b8aa58f8cf793ffd8782d3d8cb19e66ef36f7aba4353eec859e74678b01b07a7")
+                .unwrap()
+        );
+        assert_eq!(
+            pkg.fetcher,
+            NixFetcher::FetchPypi {
+                pypi_name: String::from("requests"),
+                extension: None,
+                wheel: None,
+            }
+        );
+        assert_eq!(pkg.builder, NixBuilder::PythonPackage { wheel: false });
+        assert!(pkg.to_nix().contains("  format = \"setuptools\";\n"));
+        assert_eq!(pkg.meta.license, Some(vec![NixLicense::Asl20]));
+        assert_eq!(
+            pkg.meta.homepage.as_deref(),
+            Some("https://requests.readthedocs.io")
+        );
+        assert_eq!(
+            pkg.meta.repository.as_deref(),
+            Some("https://github.com/psf/requests")
+        );
+        assert_eq!(
+            pkg.meta.download_page.as_deref(),
+            Some("https://pypi.org/project/requests/")
+        );
+        assert_eq!(
+            pkg.meta.changelog,
+            Some(vec![String::from(
+                "https://github.com/psf/requests/blob/master/HISTORY.md"
+            )])
+        );
+    }
+
+    #[test]
+    fn test_pypi_wheel() {
+        let pkg = requests()
+            .select(Some("py2.py3-none-any"))
+            .unwrap()
+            .to_nixpkg(String::from("requests"))
+            .unwrap();
+
+        assert_eq!(
+            pkg.fetcher,
+            NixFetcher::FetchPypi {
+                pypi_name: String::from("requests"),
+                extension: None,
+                wheel: Some(WheelTag {
+                    python: String::from("py2.py3"),
+                    abi: String::from("none"),
+                    platform: String::from("any"),
+                }),
+            }
+        );
+        assert_eq!(pkg.builder, NixBuilder::PythonPackage { wheel: true });
+        assert!(pkg.to_nix().contains("  format = \"wheel\";\n"));
+
+        assert!(requests()
+            .select(Some("cp39-cp39-manylinux1_x86_64"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_pypi_wheel_without_sdist() {
+        let mut wheels_only = requests();
+        wheels_only.urls.retain(|u| u.packagetype != "sdist");
+        let pkg = wheels_only
+            .select(None)
+            .unwrap()
+            .to_nixpkg(String::from("requests"))
+            .unwrap();
+
+        assert_eq!(
+            pkg.sha256,
+            hex_to_nix_base32("6c1246513ecd5ecd4528a0906f910e8f0f9c6b8ec72030dc9fd154dc1a6efd24")
+                .unwrap()
+        );
+        assert_eq!(pkg.builder, NixBuilder::PythonPackage { wheel: true });
+
+        // a wheel built for one platform is not picked on its own
+        let mut platform_wheel_only = requests();
+        platform_wheel_only
+            .urls
+            .retain(|u| u.packagetype != "sdist");
+        platform_wheel_only.urls[0].filename =
+            String::from("requests-2.26.0-cp39-cp39-manylinux1_x86_64.whl");
+        assert!(platform_wheel_only.select(None).is_err());
+    }
+
+    #[test]
+    fn test_sdist_name_of() {
+        assert_eq!(
+            sdist_name_of("typing_extensions-3.10.0.0.zip", "3.10.0.0"),
+            Some((String::from("typing_extensions"), String::from("zip")))
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use regex::Regex;
+use reqwest::header::CONTENT_TYPE;
+use tracing::warn;
+
+use color_eyre::eyre::{eyre, Result};
+
+/// Longest `meta.longDescription` taken from a README; longer paragraphs are cut at the
+/// end of a sentence, or else of a word.
+const MAX_LONG_DESCRIPTION: usize = 800;
+
+/// Collapse whitespace, and drop paragraphs without words, like those left of badges.
+fn finish(paragraph: &str) -> Option<String> {
+    let text = paragraph
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if !text.chars().any(char::is_alphabetic) {
+        return None;
+    }
+    if text.chars().count() <= MAX_LONG_DESCRIPTION {
+        return Some(text);
+    }
+
+    let cut: String = text.chars().take(MAX_LONG_DESCRIPTION).collect();
+    match cut.rfind(". ") {
+        Some(end) if end > MAX_LONG_DESCRIPTION / 2 => Some(cut[..=end].to_string()),
+        _ => {
+            let end = cut.rfind(' ').unwrap_or(cut.len());
+            Some(format!(
+                "{}...",
+                cut[..end].trim_end_matches([',', ';', ':'])
+            ))
+        }
+    }
+}
+
+/// The first paragraph of a markdown README that has words in it, skipping paragraphs
+/// of badges and images and those nested in lists, quotes or tables.
+pub fn long_description_from_markdown(markdown: &str) -> Option<String> {
+    let mut nesting = 0;
+    let mut image = 0;
+    let mut paragraph: Option<String> = None;
+
+    for event in Parser::new_ext(markdown, Options::ENABLE_TABLES) {
+        match (event, paragraph.as_mut()) {
+            (Event::Start(Tag::Paragraph), None) if nesting == 0 => paragraph = Some(String::new()),
+            (Event::End(Tag::Paragraph), Some(text)) => match finish(text) {
+                Some(text) => return Some(text),
+                None => paragraph = None,
+            },
+            (Event::Start(Tag::Image(..)), _) => image += 1,
+            (Event::End(Tag::Image(..)), _) => image -= 1,
+            (Event::Text(text), Some(paragraph)) if image == 0 => paragraph.push_str(&text),
+            (Event::Code(code), Some(paragraph)) if image == 0 => {
+                paragraph.push_str(&format!("`{}`", code))
+            }
+            (Event::SoftBreak, Some(paragraph)) | (Event::HardBreak, Some(paragraph)) => {
+                paragraph.push(' ')
+            }
+            (
+                Event::Start(Tag::BlockQuote)
+                | Event::Start(Tag::List(_))
+                | Event::Start(Tag::Table(_))
+                | Event::Start(Tag::CodeBlock(_)),
+                _,
+            ) => nesting += 1,
+            (
+                Event::End(Tag::BlockQuote)
+                | Event::End(Tag::List(_))
+                | Event::End(Tag::Table(_))
+                | Event::End(Tag::CodeBlock(_)),
+                _,
+            ) => nesting -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The first `<p>` of a README rendered as HTML that has words in it, as the VS Code
+/// Marketplace serves `Microsoft.VisualStudio.Services.Content.Details`.
+pub fn long_description_from_html(html: &str) -> Option<String> {
+    let paragraphs = Regex::new(r"(?is)<p\b[^>]*>(.*?)</p>").unwrap();
+    let code = Regex::new(r"(?is)<code\b[^>]*>(.*?)</code>").unwrap();
+    let tags = Regex::new(r"(?s)<[^>]*>").unwrap();
+
+    let found = paragraphs.captures_iter(html).find_map(|paragraph| {
+        let text = code.replace_all(&paragraph[1], "`$1`");
+        let text = tags.replace_all(&text, "");
+        finish(&decode_entities(&text))
+    });
+    found
+}
+
+async fn fetch_long_description(url: &str) -> Result<Option<String>> {
+    let response = reqwest::get(url).await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(eyre!("Received {}", status));
+    }
+
+    let html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/html"));
+    let readme = response.text().await?;
+    let start = readme.trim_start().to_lowercase();
+    if html || start.starts_with("<!doctype html") || start.starts_with("<html") {
+        Ok(long_description_from_html(&readme))
+    } else {
+        Ok(long_description_from_markdown(&readme))
+    }
+}
+
+/// `meta.longDescription` from the README at `url`. Failing to get one is only worth a
+/// warning, packages are fine without it.
+pub async fn get_long_description(url: &str) -> Option<String> {
+    match fetch_long_description(url).await {
+        Ok(long_description) => long_description,
+        Err(e) => {
+            warn!("Unable to get meta.longDescription from {}: {}", url, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_description_from_markdown() {
+        let readme = r#"
+[![Build](https://github.com/o/r/actions/workflows/ci.yml/badge.svg)](https://github.com/o/r/actions)
+![Version](https://img.shields.io/visual-studio-marketplace/v/o.r)
+
+# Indent one space
+
+<p align="center"><img src="demo.gif"></p>
+
+![demo](demo.gif)
+
+Move code to the left or right
+by one space, with `alt+left` and [`alt+right`](#keybindings).
+
+## Features
+"#;
+        assert_eq!(
+            long_description_from_markdown(readme).as_deref(),
+            Some("Move code to the left or right by one space, with `alt+left` and `alt+right`.")
+        );
+
+        let readme = "> Deprecated\n\n- a list\n- of things\n\nPlain text.\n";
+        assert_eq!(
+            long_description_from_markdown(readme).as_deref(),
+            Some("Plain text.")
+        );
+
+        assert_eq!(
+            long_description_from_markdown("# Title\n\n![logo](a.png)\n"),
+            None
+        );
+        assert_eq!(long_description_from_markdown(""), None);
+    }
+
+    #[test]
+    fn test_long_description_from_html() {
+        let html = r#"<!DOCTYPE html><html><body>
+<h1>Spacemacs</h1>
+<p><a href="https://ci"><img src="badge.svg" alt="build"></a></p>
+<p>Spacemacs themes for
+  <strong>Visual Studio Code</strong>, set with <code>workbench.colorTheme</code> &amp; friends.</p>
+</body></html>"#;
+        assert_eq!(
+            long_description_from_html(html).as_deref(),
+            Some("Spacemacs themes for Visual Studio Code, set with `workbench.colorTheme` & friends.")
+        );
+        assert_eq!(
+            long_description_from_html("<p><img src=\"a.png\"></p>"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_finish() {
+        let sentence = "This sentence is part of a long README paragraph. ";
+        let long = sentence.repeat(30);
+        let cut = finish(&long).unwrap();
+        assert!(cut.len() <= MAX_LONG_DESCRIPTION);
+        assert!(cut.ends_with("paragraph."));
+
+        let words = "word ".repeat(300);
+        let cut = finish(&words).unwrap();
+        assert!(cut.chars().count() <= MAX_LONG_DESCRIPTION + 3);
+        assert!(cut.ends_with("word..."));
+
+        assert_eq!(finish(" | - | "), None);
+    }
+}
@@ -0,0 +1,192 @@
+use color_eyre::{
+    eyre::{eyre, Result},
+    Section,
+};
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use tracing::debug;
+
+use std::collections::BTreeMap;
+
+const NIXPKGS_VSCODE: &str =
+    "https://raw.githubusercontent.com/NixOS/nixpkgs/{rev}/pkgs/applications/editors/vscode/vscode.nix";
+
+/// The Nix system a VS Code `targetPlatform` runs on; `web`, Windows and Alpine (musl)
+/// builds run on none.
+pub fn vscode_target_system(target_platform: &str) -> Option<&'static str> {
+    match target_platform {
+        "linux-x64" => Some("x86_64-linux"),
+        "linux-arm64" => Some("aarch64-linux"),
+        "linux-armhf" => Some("armv7l-linux"),
+        "darwin-x64" => Some("x86_64-darwin"),
+        "darwin-arm64" => Some("aarch64-darwin"),
+        _ => None,
+    }
+}
+
+/// The `targetPlatform` to take a VS Code extension from for each Nix system, or none when
+/// a `universal` build runs everywhere.
+pub fn vscode_target_systems<'a>(
+    target_platforms: impl IntoIterator<Item = &'a str>,
+) -> BTreeMap<String, String> {
+    let mut systems = BTreeMap::new();
+    for target_platform in target_platforms {
+        if target_platform == "universal" {
+            return BTreeMap::new();
+        }
+        if let Some(system) = vscode_target_system(target_platform) {
+            systems.insert(system.to_string(), target_platform.to_string());
+        }
+    }
+    systems
+}
+
+/// Whether VS Code `vscode` loads an extension declaring `engines.vscode = engine`.
+/// A missing range, or one that fails to parse, accepts every version.
+pub fn engine_accepts(engine: Option<&str>, vscode: &Version) -> bool {
+    match engine.map(str::trim).filter(|e| !e.is_empty() && *e != "*") {
+        Some(engine) => match VersionReq::parse(engine) {
+            Ok(range) => range.matches(vscode),
+            Err(e) => {
+                debug!("Unable to parse engine range {}: {}", engine, e);
+                true
+            }
+        },
+        None => true,
+    }
+}
+
+pub fn parse_vscode_version(version: &str) -> Result<Version> {
+    Version::parse(version)
+        .map_err(|e| eyre!("Invalid VS Code version {}", version).error(e))
+        .suggestion("VS Code versions are written like \"1.89.1\"")
+}
+
+/// The parts of an extension's `package.json` naming other extensions it needs.
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VSCodeExtensionManifest {
+    /// Extensions that have to be installed for this one to activate
+    #[serde(default)]
+    pub extension_dependencies: Vec<String>,
+    /// Extensions installed along with this one, when it is a pack
+    #[serde(default)]
+    pub extension_pack: Vec<String>,
+}
+
+impl VSCodeExtensionManifest {
+    pub async fn get(url: &str) -> Result<Self> {
+        let response = reqwest::Client::new()
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+
+    /// Unique ids of every extension this one needs installed, dependencies first and
+    /// without duplicates.
+    pub fn requires(&self) -> Vec<String> {
+        let mut requires: Vec<String> = Vec::new();
+        for unique_id in self
+            .extension_dependencies
+            .iter()
+            .chain(&self.extension_pack)
+        {
+            if !requires.iter().any(|r| r.eq_ignore_ascii_case(unique_id)) {
+                requires.push(unique_id.clone());
+            }
+        }
+        requires
+    }
+}
+
+/// The first `version = "...";` of a nix expression.
+fn nix_version(expression: &str) -> Option<Version> {
+    expression.lines().find_map(|line| {
+        let version = line
+            .trim()
+            .strip_prefix("version = \"")?
+            .split('"')
+            .next()?;
+        Version::parse(version).ok()
+    })
+}
+
+/// The version of VS Code in nixpkgs at `rev`, a commit or a branch like `nixos-24.05`.
+pub async fn nixpkgs_vscode_version(rev: &str) -> Result<Version> {
+    let response = reqwest::Client::new()
+        .get(NIXPKGS_VSCODE.replace("{rev}", rev))
+        .header("User-Agent", env!("CARGO_PKG_NAME"))
+        .send()
+        .await?
+        .error_for_status()?;
+    let expression = response.text().await?;
+    nix_version(&expression).ok_or_else(|| eyre!("No VS Code version found in nixpkgs {}", rev))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_accepts() {
+        let vscode = Version::parse("1.89.1").unwrap();
+        assert!(engine_accepts(Some("^1.78.0"), &vscode));
+        assert!(engine_accepts(Some(">=1.60.0"), &vscode));
+        assert!(!engine_accepts(Some("^1.90.0"), &vscode));
+        assert!(engine_accepts(Some("*"), &vscode));
+        assert!(engine_accepts(None, &vscode));
+        assert!(engine_accepts(Some("not a range"), &vscode));
+    }
+
+    #[test]
+    fn test_nix_version() {
+        let expression = r#"
+  plat = {
+    x86_64-linux = "linux-x64";
+  }.${system} or throwSystem;
+
+  # Please backport all compatible updates to the stable release.
+  # This is important for the extension ecosystem.
+  version = "1.89.1";
+  pname = "vscode" + lib.optionalString isInsiders "-insiders";
+"#;
+        assert_eq!(nix_version(expression), Some(Version::new(1, 89, 1)));
+        assert_eq!(nix_version("{ }"), None);
+    }
+
+    #[test]
+    fn test_extension_manifest() {
+        let manifest: VSCodeExtensionManifest = serde_json::from_str(
+            r#"{
+                "name": "vscode-java-pack",
+                "publisher": "vscjava",
+                "extensionDependencies": ["redhat.java"],
+                "extensionPack": ["redhat.java", "vscjava.vscode-java-debug", "vscjava.vscode-maven"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.requires(),
+            vec![
+                "redhat.java",
+                "vscjava.vscode-java-debug",
+                "vscjava.vscode-maven"
+            ]
+        );
+
+        let manifest: VSCodeExtensionManifest =
+            serde_json::from_str(r#"{ "name": "vscode-xml" }"#).unwrap();
+        assert!(manifest.requires().is_empty());
+    }
+
+    #[test]
+    fn test_target_systems() {
+        let systems = vscode_target_systems(vec!["linux-x64", "win32-x64", "darwin-arm64"]);
+        assert_eq!(systems.len(), 2);
+        assert_eq!(systems["x86_64-linux"], "linux-x64");
+        assert!(vscode_target_systems(vec!["linux-x64", "universal"]).is_empty());
+        assert_eq!(vscode_target_system("alpine-x64"), None);
+    }
+}
@@ -1,4 +1,3 @@
-use serde::de;
 use serde::{Deserialize, Serialize};
 
 use color_eyre::{
@@ -9,16 +8,32 @@ use color_eyre::{
 use reqwest::header::{
     HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE, REFERER, USER_AGENT,
 };
+use semver::Version;
 use tokio::{runtime::Handle, task};
+use tracing::warn;
 
 use crate::{
-    nix::{NixLicense, NixPackage, NixPackageMeta},
-    sources::{get_hash, get_long_description},
+    nix::{
+        LicenseConfidence, NixBuilder, NixFetcher, NixLicense, NixPackage, NixPackageMeta,
+        NixPlatforms, PackageLinks, PackageStatus,
+    },
+    sources::{
+        error::SourceError,
+        get_changelog,
+        github::{GitHubHost, GitHubLicense},
+        readme,
+        vscode::{engine_accepts, vscode_target_systems},
+        vsix,
+    },
 };
 
 const EXT_QUERY_ADDRESS: &str =
     "https://marketplace.visualstudio.com/_apis/public/gallery/extensionquery";
 
+/// Version property set on deprecated extensions, to `true` or to the unique id of the
+/// extension replacing them
+const DEPRECATED_PROPERTY: &str = "Microsoft.VisualStudio.Code.ExtensionDeprecated";
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Payload {
@@ -60,7 +75,7 @@ impl Payload {
                 sort_order: 0,
             }],
             asset_types: Vec::with_capacity(1),
-            flags: 103,
+            flags: 119,
         }
     }
 }
@@ -115,6 +130,72 @@ pub struct VSMarketPlaceExtensionVersion {
     pub files: Vec<VSMarketPlaceExtensionVersionFile>,
     pub asset_uri: String,
     pub fallback_asset_uri: String,
+    /// The platform this VSIX was built for, eg. `linux-x64`, none for universal ones
+    #[serde(default)]
+    pub target_platform: Option<String>,
+    #[serde(default)]
+    pub properties: Vec<VSMarketPlaceExtensionVersionProperty>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct VSMarketPlaceExtensionVersionProperty {
+    pub key: String,
+    pub value: String,
+}
+
+impl VSMarketPlaceExtensionVersion {
+    /// Whether VS Code `vscode` loads this version, going by its
+    /// `Microsoft.VisualStudio.Code.Engine` property.
+    fn accepts(&self, vscode: &Version) -> bool {
+        engine_accepts(self.engine(), vscode)
+    }
+
+    fn engine(&self) -> Option<&str> {
+        self.property("Microsoft.VisualStudio.Code.Engine")
+    }
+
+    /// Whether this version was published as a pre-release, going by its
+    /// `Microsoft.VisualStudio.Code.PreRelease` property.
+    fn is_prerelease(&self) -> bool {
+        self.property("Microsoft.VisualStudio.Code.PreRelease") == Some("true")
+    }
+
+    fn property(&self, key: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|property| property.key == key)
+            .map(|property| property.value.as_str())
+    }
+
+    /// Whether the extension, with the gallery flags `extension_flags`, is deprecated or
+    /// unpublished, going by those flags and this version's properties.
+    fn status(&self, extension_flags: &str) -> Option<PackageStatus> {
+        let flags: Vec<String> = extension_flags
+            .split(',')
+            .map(|flag| flag.trim().to_lowercase())
+            .collect();
+        let deprecated = self
+            .property(DEPRECATED_PROPERTY)
+            .map(str::trim)
+            .filter(|value| !value.is_empty() && !value.eq_ignore_ascii_case("false"));
+        if flags.iter().any(|flag| flag == "unpublished") {
+            Some(PackageStatus::Unpublished)
+        } else if deprecated.is_some() || flags.iter().any(|flag| flag == "deprecated") {
+            Some(PackageStatus::Deprecated {
+                replacement: deprecated
+                    .filter(|value| !value.eq_ignore_ascii_case("true"))
+                    .map(String::from),
+            })
+        } else {
+            None
+        }
+    }
+
+    fn target_platform(&self) -> String {
+        self.target_platform
+            .clone()
+            .unwrap_or_else(|| String::from("universal"))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -176,11 +257,34 @@ pub struct VSMarketPlaceExtensionRefined {
     pub vsix_manifest_url: String,
     pub changelog_url: Option<String>,
     pub readme_url: Option<String>,
+    /// License file, which holds the proprietary terms of Microsoft's own extensions
+    #[serde(default)]
+    pub license_url: Option<String>,
+    /// Every `targetPlatform` the version was published for
+    #[serde(default)]
+    pub target_platforms: Vec<String>,
+    /// VS Code versions the version supports, its `Microsoft.VisualStudio.Code.Engine`
+    #[serde(default)]
+    pub engine: Option<String>,
+    #[serde(skip)]
+    pub status: Option<PackageStatus>,
 }
 
 impl VSMarketPlaceExtensionRefined {
-    pub async fn get(unique_id: String) -> Result<Self, Report> {
-        let data = Payload::new(unique_id);
+    /// Whether VS Code `vscode` loads this version.
+    pub fn accepts(&self, vscode: &Version) -> bool {
+        engine_accepts(self.engine.as_deref(), vscode)
+    }
+
+    /// The newest stable version whose engine accepts VS Code `vscode`, or the newest
+    /// stable version when no VS Code version is configured. Pre-releases are only
+    /// picked when `prerelease` is set.
+    pub async fn get_compatible(
+        unique_id: String,
+        vscode: Option<&Version>,
+        prerelease: bool,
+    ) -> Result<Self, Report> {
+        let data = Payload::new(unique_id.clone());
 
         let mut headers = HeaderMap::new();
 
@@ -226,8 +330,45 @@ impl VSMarketPlaceExtensionRefined {
                     let mut vsix_manifest_url = Box::new(String::new());
                     let mut changelog_url_box = Box::new(String::new());
                     let mut readme_url_box = Box::new(String::new());
-
-                    let version_struct = extension.versions.remove(0);
+                    let mut license_url: Option<String> = None;
+
+                    let compatible = match extension.versions.iter().position(|v| {
+                        (prerelease || !v.is_prerelease())
+                            && vscode.map_or(true, |vscode| v.accepts(vscode))
+                    }) {
+                        Some(compatible) => compatible,
+                        None => {
+                            let kind = if prerelease {
+                                "version"
+                            } else {
+                                "stable version"
+                            };
+                            let report = match vscode {
+                                Some(vscode) => eyre!(
+                                    "No {} of {} is compatible with VS Code {}",
+                                    kind,
+                                    unique_id,
+                                    vscode
+                                ),
+                                None => eyre!("{} has no {}", unique_id, kind),
+                            };
+                            return if prerelease {
+                                Err(report)
+                            } else {
+                                Err(report).suggestion(
+                                    "Set `prerelease = true` to allow pre-release versions",
+                                )
+                            };
+                        }
+                    };
+                    let version_struct = extension.versions.remove(compatible);
+                    let target_platforms: Vec<String> = std::iter::once(&version_struct)
+                        .chain(&extension.versions)
+                        .filter(|v| v.version == version_struct.version)
+                        .map(VSMarketPlaceExtensionVersion::target_platform)
+                        .collect();
+                    let status = version_struct.status(&extension.flags);
+                    let engine = version_struct.engine().map(String::from);
                     extension.versions.clear();
 
                     for file in version_struct.files {
@@ -244,6 +385,9 @@ impl VSMarketPlaceExtensionRefined {
                             AssetTypeMicrosoftVisualStudio::ServicesContentDetails => {
                                 *readme_url_box = file.source;
                             }
+                            AssetTypeMicrosoftVisualStudio::ServicesContentLicense => {
+                                license_url = Some(file.source);
+                            }
                             _ => (),
                         }
                     }
@@ -271,23 +415,28 @@ impl VSMarketPlaceExtensionRefined {
                             vsix_manifest_url: *vsix_manifest_url,
                             changelog_url,
                             readme_url,
+                            license_url,
+                            target_platforms,
+                            engine,
+                            status,
                         })
                     } else {
-                        return Err(eyre!("No VSIX or VSIX Manifest found"));
+                        Err(eyre!("No VSIX or VSIX Manifest found"))
                     }
                 } else {
-                    return Err(eyre!("No extensions found from vscode marketplace query"));
+                    // the marketplace finds nothing once an extension is removed
+                    Err(SourceError::Unpublished(unique_id).into())
                 }
             } else {
-                return Err(eyre!("No results found from vscode marketplace"));
+                Err(eyre!("No results found from vscode marketplace"))
             }
         } else if let Some(reason) = resp_status.canonical_reason() {
-            return Err(eyre!(
+            Err(eyre!(
                 "Recieved {}, while attempting to get extension from vscode marketplace.",
                 reason
-            ));
+            ))
         } else {
-            return Err(eyre!("{}", resp_status.to_string()));
+            Err(eyre!("{}", resp_status.to_string()))
         }
     }
 
@@ -338,16 +487,21 @@ impl VSMarketPlaceExtensionRefined {
                     let mut vsix_manifest_url = Box::new(String::new());
                     let mut changelog_url_box = Box::new(String::new());
                     let mut readme_url_box = Box::new(String::new());
+                    let mut license_url: Option<String> = None;
 
                     let mut vers: Option<VSMarketPlaceExtensionVersion> = None;
+                    let mut target_platforms: Vec<String> = Vec::new();
 
                     for v in extension.versions {
                         if v.version == version {
+                            target_platforms.push(v.target_platform());
                             vers = Some(v);
                         }
                     }
 
                     if let Some(v) = vers {
+                        let status = v.status(&extension.flags);
+                        let engine = v.engine().map(String::from);
                         for file in v.files {
                             match file.asset_type {
                                 AssetTypeMicrosoftVisualStudio::ServicesVSIXPackage => {
@@ -362,6 +516,9 @@ impl VSMarketPlaceExtensionRefined {
                                 AssetTypeMicrosoftVisualStudio::ServicesContentDetails => {
                                     *readme_url_box = file.source;
                                 }
+                                AssetTypeMicrosoftVisualStudio::ServicesContentLicense => {
+                                    license_url = Some(file.source);
+                                }
                                 _ => (),
                             }
                         }
@@ -389,169 +546,232 @@ impl VSMarketPlaceExtensionRefined {
                                 vsix_manifest_url: *vsix_manifest_url,
                                 changelog_url,
                                 readme_url,
+                                license_url,
+                                target_platforms,
+                                engine,
+                                status,
                             })
                         } else {
-                            return Err(eyre!("No VSIX or VSIX Manifest found"));
+                            Err(eyre!("No VSIX or VSIX Manifest found"))
                         }
                     } else {
-                        return Err(eyre!(
+                        Err(eyre!(
                             "No version found for extensions from vscode marketplace query"
-                        ));
+                        ))
                     }
                 } else {
-                    return Err(eyre!("No extensions found from vscode marketplace query"));
+                    Err(eyre!("No extensions found from vscode marketplace query"))
                 }
             } else {
-                return Err(eyre!("No results found from vscode marketplace"));
+                Err(eyre!("No results found from vscode marketplace"))
             }
         } else if let Some(reason) = resp_status.canonical_reason() {
-            return Err(eyre!(
+            Err(eyre!(
                 "Recieved {}, while attempting to get extension from vscode marketplace.",
                 reason
-            ));
+            ))
         } else {
-            return Err(eyre!("{}", resp_status.to_string()));
+            Err(eyre!("{}", resp_status.to_string()))
         }
     }
-    pub fn to_nixpkg(self, pname: String) -> NixPackage {
+
+    /// The licenses of this version: the one GitHub recognises for its `github` repository
+    /// on `host`, or else the one its license file is recognised as, which is how
+    /// Microsoft's proprietary terms come out unfree.
+    fn license(
+        &self,
+        github: Option<&str>,
+        host: &GitHubHost,
+    ) -> (Option<Vec<NixLicense>>, Option<LicenseConfidence>) {
+        let from_github = github.and_then(|github_url| {
+            let owner_repo = host.owner_repo_of(github_url)?;
+            match task::block_in_place(|| {
+                Handle::current().block_on(GitHubLicense::get(host, owner_repo))
+            }) {
+                Ok(license) => license.and_then(|license| {
+                    license
+                        .spdx_id
+                        .as_deref()
+                        .and_then(NixLicense::from_name)
+                        .or_else(|| NixLicense::from_name(&license.name))
+                }),
+                Err(e) => {
+                    warn!("Unable to get the license of {}: {}", github_url, e);
+                    None
+                }
+            }
+        });
+        if let Some(found) = from_github {
+            return (Some(vec![found.license]), Some(found.confidence));
+        }
+
+        let url = match &self.license_url {
+            Some(url) => url.clone(),
+            None => return (None, None),
+        };
+        let text = task::block_in_place(|| {
+            Handle::current()
+                .block_on(async move { reqwest::get(url).await?.error_for_status()?.text().await })
+        });
+        match text.map(|text| NixLicense::from_text(&text)) {
+            Ok(Some(found)) => (Some(vec![found.license]), Some(found.confidence)),
+            Ok(None) => (None, None),
+            Err(e) => {
+                warn!(
+                    "Unable to get the license file of {}.{}: {}",
+                    self.publisher, self.extension_name, e
+                );
+                (None, None)
+            }
+        }
+    }
+
+    pub fn to_nixpkg(self, pname: String) -> Result<NixPackage, Report> {
+        let status = self.status.clone();
         let publisher: String = self.publisher.clone();
         let extension_name: String = self.extension_name.clone();
         let version: String = self.version.clone();
+        let readme_url = self.readme_url.clone();
         let src = format!("https://{publisher}.gallery.vsassets.io/_apis/public/gallery/publisher/{publisher}/extension/{extName}/{version}/assetbyname/Microsoft.VisualStudio.Services.VSIXPackage", publisher=&publisher, extName=&extension_name, version=&version);
         let src_clone = &src.to_string();
         let description = self.description.clone();
-        let changelog = self.changelog_url.clone().map(|change| vec![change]);
-
-        let sha256: String = task::block_in_place(move || {
-            Handle::current().block_on(async move {
-                get_hash(src_clone)
-                    .await
-                    .expect("Error: unable to get hash of vsix")
-            })
-        });
-
-        let (homepage, github, source) = task::block_in_place(move || {
-            Handle::current().block_on(async move {
-                let mut homepage_box = Box::new(String::from(""));
-                let mut github_box = Box::new(String::from(""));
-                let mut source_box = Box::new(String::from(""));
-
-                if let Ok(doc) = roxmltree::Document::parse(self.vsix_manifest_url.as_ref()) {
-                    for node in doc.descendants() {
-                        if node.is_element() && node.has_tag_name("Property") {
-                            if let Some(property_id) = node.attribute_node("Id") {
-                                match property_id.value() {
-                                    "Microsoft.VisualStudio.Services.Links.Learn" => {
-                                        if let Some(property_value) = node.attribute_node("Value") {
-                                            *homepage_box = property_value.value().to_string()
-                                        }
-                                    }
-                                    "Microsoft.VisualStudio.Services.Links.GitHub" => {
-                                        if let Some(property_value) = node.attribute_node("Value") {
-                                            *github_box = property_value.value().to_string()
-                                        }
-                                    }
-                                    "Microsoft.VisualStudio.Services.Links.Source" => {
-                                        if let Some(property_value) = node.attribute_node("Value") {
-                                            *source_box = property_value.value().to_string()
-                                        }
-                                    }
-                                    _ => (),
-                                }
-                            }
-                        }
-                    }
-
-                    let homepage: Option<String> = if *homepage_box != String::new() {
-                        Some(*homepage_box)
-                    } else {
-                        None
-                    };
+        let changelog_url = self.changelog_url.clone();
 
-                    let github: Option<String> = if *github_box != String::new() {
-                        Some(*github_box)
-                    } else {
-                        None
-                    };
+        let target_systems =
+            vscode_target_systems(self.target_platforms.iter().map(String::as_str));
 
-                    let source: Option<String> = if *source_box != String::new() {
-                        Some(*source_box)
-                    } else {
-                        None
-                    };
-                    (homepage, github, source)
-                } else {
-                    (None, None, None)
-                }
+        let name = Some(format!("{}-{}.zip", publisher, extension_name));
+        let (sha256, fetcher, platforms, native) = if target_systems.is_empty() {
+            let (sha256, native) = task::block_in_place(|| {
+                Handle::current().block_on(vsix::get_hash_and_native_binaries(src_clone))
             })
-        });
-
-        let long_description: Option<String> = if let Some(s) = source {
-            let desc = task::block_in_place(move || {
-                Handle::current().block_on(async move {
-                    // do something async
-                    get_long_description(s)
-                        .await
-                        .expect("Error: unable to get readme of extension")
+            .wrap_err_with(|| format!("Unable to get the hash of {}", src_clone))?;
+            (sha256, NixFetcher::FetchUrl { name }, None, native)
+        } else {
+            let urls = target_systems
+                .iter()
+                .map(|(system, target)| {
+                    (
+                        system.clone(),
+                        format!("{}?targetPlatform={}", src_clone, target),
+                    )
                 })
-            });
+                .collect();
+            let (sources, native) = task::block_in_place(|| {
+                Handle::current().block_on(vsix::get_system_sources_and_native_binaries(urls))
+            })
+            .wrap_err_with(|| {
+                format!(
+                    "Unable to get the hashes of {}.{}",
+                    publisher, extension_name
+                )
+            })?;
+            (
+                String::new(),
+                NixFetcher::FetchUrlBySystem { name, sources },
+                Some(NixPlatforms::Systems(target_systems.into_keys().collect())),
+                native,
+            )
+        };
 
-            if desc == String::new() {
-                None
-            } else {
-                Some(desc)
+        let manifest = task::block_in_place(|| {
+            Handle::current().block_on(async {
+                reqwest::get(&self.vsix_manifest_url)
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await
+            })
+        });
+        let (homepage, github, source) = match manifest {
+            Ok(manifest) => manifest_links(&manifest),
+            Err(e) => {
+                warn!(
+                    "Unable to get the VSIX manifest of {}.{}: {}",
+                    publisher, extension_name, e
+                );
+                (None, None, None)
             }
-        } else {
-            None
         };
 
-        let license = if let Some(github_url) = github {
-            task::block_in_place(move || {
-                Handle::current().block_on(async move {
-                    // do something async
-                    let github = github_url.trim_end_matches(".git");
-                    let github = github.trim_start_matches("https://github.com/");
-                    let split_github_url: Vec<&str> = github.split('/').collect();
-                    let github_author = split_github_url[0];
-                    let github_repo = split_github_url[1];
-                    if let Some(lic) = octocrab::instance()
-                        .repos(github_author, github_repo)
-                        .license()
-                        .await
-                        .expect("Error: getting repo information")
-                        .license
-                    {
-                        NixLicense::from_str(&lic.name)
-                    } else {
-                        None
-                    }
+        // the README as the marketplace renders it, or else the description in package.json
+        let long_description = readme_url
+            .and_then(|readme_url| {
+                task::block_in_place(|| {
+                    Handle::current().block_on(readme::get_long_description(&readme_url))
                 })
             })
-            .map(|lic| vec![*lic])
-        } else {
-            None
-        };
+            .or_else(|| description.clone());
+        let changelog_text = changelog_url.as_ref().and_then(|changelog_url| {
+            task::block_in_place(|| Handle::current().block_on(get_changelog(changelog_url)))
+        });
+
+        let repository = source.or_else(|| github.clone());
+
+        let (license, license_confidence) = self.license(github.as_deref(), &GitHubHost::default());
 
         let meta = NixPackageMeta {
             description,
             long_description,
-            homepage,
             license,
-            changelog,
-            ..Default::default()
+            license_confidence,
+            changelog: changelog_url.map(|changelog_url| vec![changelog_url]),
+            changelog_text,
+            status,
+            platforms,
+            ..NixPackageMeta::from_links(PackageLinks {
+                homepage,
+                repository,
+                store_page: Some(format!(
+                    "https://marketplace.visualstudio.com/items?itemName={}.{}",
+                    publisher, extension_name
+                )),
+            })
         };
 
-        NixPackage {
+        Ok(NixPackage {
             name: pname.clone(),
             pname,
             src,
             version,
             sha256,
             meta,
-        }
+            fetcher,
+            builder: NixBuilder::VSCodeExtension {
+                publisher,
+                name: extension_name,
+                dependencies: Vec::new(),
+                native,
+            },
+        })
     }
 }
 
+/// The homepage, GitHub repository and source repository an extension links to in its
+/// VSIX manifest.
+fn manifest_links(manifest: &str) -> (Option<String>, Option<String>, Option<String>) {
+    let doc = match roxmltree::Document::parse(manifest) {
+        Ok(doc) => doc,
+        Err(e) => {
+            warn!("Unable to parse VSIX manifest: {}", e);
+            return (None, None, None);
+        }
+    };
+    let property = |id: &str| {
+        doc.descendants()
+            .filter(|node| node.has_tag_name("Property"))
+            .find(|node| node.attribute("Id") == Some(id))
+            .and_then(|node| node.attribute("Value"))
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+    };
+    (
+        property("Microsoft.VisualStudio.Services.Links.Learn"),
+        property("Microsoft.VisualStudio.Services.Links.GitHub"),
+        property("Microsoft.VisualStudio.Services.Links.Source"),
+    )
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -586,10 +806,13 @@ mod tests {
             "readmeUrl": "https://cometeer.gallerycdn.vsassets.io/extensions/cometeer/spacemacs/1.1.1/1507198251877/Microsoft.VisualStudio.Services.Content.Details"
 	    })).unwrap();
 
-        let actual: VSMarketPlaceExtensionRefined =
-            VSMarketPlaceExtensionRefined::get(String::from("cometeer.spacemacs"))
-                .await
-                .unwrap();
+        let actual: VSMarketPlaceExtensionRefined = VSMarketPlaceExtensionRefined::get_compatible(
+            String::from("cometeer.spacemacs"),
+            None,
+            false,
+        )
+        .await
+        .unwrap();
         assert_eq!(actual.publisher, expected.publisher);
         assert_eq!(actual.extension_name, expected.extension_name);
         assert_eq!(actual.display_name, expected.display_name);
@@ -632,4 +855,192 @@ mod tests {
         assert_eq!(actual.changelog_url, expected.changelog_url);
         assert_eq!(actual.readme_url, expected.readme_url);
     }
+
+    #[test]
+    fn test_status() {
+        let version = |properties: serde_json::Value| -> VSMarketPlaceExtensionVersion {
+            serde_json::from_value(json!({
+                "version": "1.0.0",
+                "flags": "validated",
+                "lastUpdated": "2024-01-01T00:00:00.000Z",
+                "files": [],
+                "assetUri": "",
+                "fallbackAssetUri": "",
+                "properties": properties,
+            }))
+            .unwrap()
+        };
+
+        let maintained = version(json!([]));
+        assert_eq!(maintained.status("validated, public"), None);
+        assert_eq!(
+            maintained.status("validated, public, deprecated"),
+            Some(PackageStatus::Deprecated { replacement: None })
+        );
+        assert_eq!(
+            maintained.status("validated, unpublished"),
+            Some(PackageStatus::Unpublished)
+        );
+
+        let replaced = version(json!([{
+            "key": DEPRECATED_PROPERTY,
+            "value": "ms-python.black-formatter",
+        }]));
+        assert_eq!(
+            replaced.status("validated, public"),
+            Some(PackageStatus::Deprecated {
+                replacement: Some(String::from("ms-python.black-formatter"))
+            })
+        );
+        let not_deprecated = version(json!([{ "key": DEPRECATED_PROPERTY, "value": "false" }]));
+        assert_eq!(not_deprecated.status("validated, public"), None);
+    }
+
+    #[test]
+    fn test_accepts() {
+        let extension: VSMarketPlaceExtensionRefined = serde_json::from_value(json!({
+            "publisher": "cometeer",
+            "extensionName": "spacemacs",
+            "displayName": "Spacemacs",
+            "version": "1.1.1",
+            "vsixUrl": "",
+            "vsixManifestUrl": "",
+            "engine": "^1.80.0",
+        }))
+        .unwrap();
+
+        assert!(extension.accepts(&Version::new(1, 85, 0)));
+        assert!(!extension.accepts(&Version::new(1, 79, 0)));
+    }
+
+    /// The VSIX manifest of cometeer.spacemacs 1.1.1.
+    const SPACEMACS_MANIFEST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<PackageManifest Version="2.0.0" xmlns="http://schemas.microsoft.com/developer/vsx-schema/2011" xmlns:d="http://schemas.microsoft.com/developer/vsx-schema-design/2011">
+  <Metadata>
+    <Identity Language="en-US" Id="spacemacs" Version="1.1.1" Publisher="cometeer" />
+    <DisplayName>Spacemacs</DisplayName>
+    <Description xml:space="preserve">Spacemacs themes for Visual Studio Code</Description>
+    <Tags>theme,color-theme</Tags>
+    <Categories>Themes</Categories>
+    <GalleryFlags>Public</GalleryFlags>
+    <Properties>
+      <Property Id="Microsoft.VisualStudio.Code.Engine" Value="^1.15.0" />
+      <Property Id="Microsoft.VisualStudio.Services.Links.Source" Value="https://github.com/cometeer/spacemacs-vscode.git" />
+      <Property Id="Microsoft.VisualStudio.Services.Links.Getstarted" Value="https://github.com/cometeer/spacemacs-vscode.git" />
+      <Property Id="Microsoft.VisualStudio.Services.Links.GitHub" Value="https://github.com/cometeer/spacemacs-vscode.git" />
+      <Property Id="Microsoft.VisualStudio.Services.Links.Support" Value="https://github.com/cometeer/spacemacs-vscode/issues" />
+      <Property Id="Microsoft.VisualStudio.Services.Links.Learn" Value="https://github.com/cometeer/spacemacs-vscode#readme" />
+      <Property Id="Microsoft.VisualStudio.Services.GitHubFlavoredMarkdown" Value="true" />
+    </Properties>
+    <License>extension/LICENSE.txt</License>
+    <Icon>extension/images/spacemacs.png</Icon>
+  </Metadata>
+  <Installation>
+    <InstallationTarget Id="Microsoft.VisualStudio.Code" />
+  </Installation>
+  <Dependencies />
+  <Assets>
+    <Asset Type="Microsoft.VisualStudio.Code.Manifest" Path="extension/package.json" Addressable="true" />
+    <Asset Type="Microsoft.VisualStudio.Services.Content.Details" Path="extension/README.md" Addressable="true" />
+  </Assets>
+</PackageManifest>"#;
+
+    /// Answers the first request to the returned address with `body`.
+    fn serve_once(body: &'static str) -> std::net::SocketAddr {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+        address
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_license_of_microsoft_terms() {
+        // the license file of a Microsoft extension
+        let address = serve_once(
+            "MICROSOFT SOFTWARE LICENSE TERMS\n\nMICROSOFT C/C++ EXTENSION FOR VISUAL STUDIO CODE\n",
+        );
+
+        let extension: VSMarketPlaceExtensionRefined = serde_json::from_value(json!({
+            "publisher": "ms-vscode",
+            "extensionName": "cpptools",
+            "displayName": "C/C++",
+            "description": "C/C++ IntelliSense, debugging, and code browsing.",
+            "version": "1.18.5",
+            "vsixUrl": "https://ms-vscode.gallerycdn.vsassets.io/extensions/ms-vscode/cpptools/1.18.5/Microsoft.VisualStudio.Services.VSIXPackage",
+            "vsixManifestUrl": "https://ms-vscode.gallerycdn.vsassets.io/extensions/ms-vscode/cpptools/1.18.5/Microsoft.VisualStudio.Services.VsixManifest",
+            "changelogUrl": null,
+            "readmeUrl": null,
+            "licenseUrl": format!("http://{}/Microsoft.VisualStudio.Services.Content.License", address),
+        }))
+        .unwrap();
+
+        let (license, confidence) = extension.license(None, &GitHubHost::default());
+        assert_eq!(license, Some(vec![NixLicense::Unfree]));
+        assert!(confidence.is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_license_from_github() {
+        // GitHub's API, describing the license of the repository the manifest links to
+        let address = serve_once(
+            r#"{"name":"LICENSE","path":"LICENSE","license":{"key":"mit","name":"MIT License","spdx_id":"MIT","url":"https://api.github.com/licenses/mit","node_id":"MDc6TGljZW5zZTEz"}}"#,
+        );
+        let host =
+            GitHubHost::new(Some("github.com"), Some(&format!("http://{}", address))).unwrap();
+
+        let extension: VSMarketPlaceExtensionRefined = serde_json::from_value(json!({
+            "publisher": "cometeer",
+            "extensionName": "spacemacs",
+            "displayName": "Spacemacs",
+            "description": "Spacemacs themes for Visual Studio Code",
+            "version": "1.1.1",
+            "vsixUrl": "https://cometeer.gallerycdn.vsassets.io/extensions/cometeer/spacemacs/1.1.1/1507198251877/Microsoft.VisualStudio.Services.VSIXPackage",
+            "vsixManifestUrl": "https://cometeer.gallerycdn.vsassets.io/extensions/cometeer/spacemacs/1.1.1/1507198251877/Microsoft.VisualStudio.Services.VsixManifest",
+            "changelogUrl": null,
+            "readmeUrl": null,
+        }))
+        .unwrap();
+        let (_, github, _) = manifest_links(SPACEMACS_MANIFEST);
+
+        let (license, confidence) = extension.license(github.as_deref(), &host);
+        assert_eq!(license, Some(vec![NixLicense::Mit]));
+        assert_eq!(confidence, Some(LicenseConfidence::Spdx));
+    }
+
+    #[test]
+    fn test_manifest_links() {
+        assert_eq!(
+            manifest_links(SPACEMACS_MANIFEST),
+            (
+                Some(String::from(
+                    "https://github.com/cometeer/spacemacs-vscode#readme"
+                )),
+                Some(String::from(
+                    "https://github.com/cometeer/spacemacs-vscode.git"
+                )),
+                Some(String::from(
+                    "https://github.com/cometeer/spacemacs-vscode.git"
+                )),
+            )
+        );
+        // the URL of the manifest is not the manifest
+        assert_eq!(
+            manifest_links("https://cometeer.gallerycdn.vsassets.io/extensions/cometeer/spacemacs/1.1.1/1507198251877/Microsoft.VisualStudio.Services.VsixManifest"),
+            (None, None, None)
+        );
+    }
 }
@@ -0,0 +1,386 @@
+use color_eyre::eyre::{Result, WrapErr};
+use tracing::{debug, warn};
+
+use crate::nix::{ElfFile, NativeBinaries, SystemSource};
+
+use super::{download, nix_hash};
+
+use std::{
+    collections::BTreeMap,
+    convert::{TryFrom, TryInto},
+    fs::File,
+    io::Read,
+    path::Path,
+};
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+const PT_DYNAMIC: u32 = 2;
+const PT_LOAD: u32 = 1;
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_STRTAB: u64 = 5;
+
+/// The CPU an ELF `e_machine` is for, as in nix system names.
+fn elf_arch(machine: u16) -> String {
+    match machine {
+        3 => String::from("i686"),
+        40 => String::from("armv7l"),
+        62 => String::from("x86_64"),
+        183 => String::from("aarch64"),
+        243 => String::from("riscv64"),
+        machine => format!("unknown ({})", machine),
+    }
+}
+
+/// Reads the fields of an ELF file, in its class and byte order.
+struct Elf<'a> {
+    bytes: &'a [u8],
+    is_64: bool,
+    little_endian: bool,
+}
+
+impl Elf<'_> {
+    fn bytes<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+        self.bytes
+            .get(offset..offset.checked_add(N)?)?
+            .try_into()
+            .ok()
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn u64(&self, offset: usize) -> Option<u64> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.little_endian {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    }
+
+    /// An address or offset, 4 bytes in 32-bit files and 8 in 64-bit ones.
+    fn word(&self, offset: usize) -> Option<u64> {
+        if self.is_64 {
+            self.u64(offset)
+        } else {
+            self.u32(offset).map(u64::from)
+        }
+    }
+
+    fn string(&self, offset: usize) -> Option<String> {
+        let bytes = self.bytes.get(offset..)?;
+        let end = bytes.iter().position(|&byte| byte == 0)?;
+        String::from_utf8(bytes[..end].to_vec()).ok()
+    }
+}
+
+/// A program header: its type, and where its segment is in the file and in memory.
+struct Segment {
+    kind: u32,
+    offset: u64,
+    address: u64,
+    size: u64,
+}
+
+/// The architecture and needed libraries of the ELF file `bytes`, if it is one that is
+/// dynamically linked; statically linked executables run on NixOS as they are.
+fn elf_file(path: &str, bytes: &[u8]) -> Option<ElfFile> {
+    if !bytes.starts_with(ELF_MAGIC) {
+        return None;
+    }
+    let elf = Elf {
+        bytes,
+        is_64: *bytes.get(4)? == 2,
+        little_endian: *bytes.get(5)? == 1,
+    };
+
+    let machine = elf.u16(18)?;
+    let (phoff, phentsize, phnum) = if elf.is_64 {
+        (elf.u64(32)?, elf.u16(54)?, elf.u16(56)?)
+    } else {
+        (u64::from(elf.u32(28)?), elf.u16(42)?, elf.u16(44)?)
+    };
+
+    let segments: Vec<Segment> = (0..u64::from(phnum))
+        .filter_map(|i| {
+            // offsets come from the file, so they may point anywhere, or overflow
+            let header = i
+                .checked_mul(u64::from(phentsize))
+                .and_then(|offset| phoff.checked_add(offset))?;
+            let header = usize::try_from(header).ok()?;
+            let field = |offset: usize| header.checked_add(offset);
+            Some(if elf.is_64 {
+                Segment {
+                    kind: elf.u32(header)?,
+                    offset: elf.u64(field(8)?)?,
+                    address: elf.u64(field(16)?)?,
+                    size: elf.u64(field(32)?)?,
+                }
+            } else {
+                Segment {
+                    kind: elf.u32(header)?,
+                    offset: u64::from(elf.u32(field(4)?)?),
+                    address: u64::from(elf.u32(field(8)?)?),
+                    size: u64::from(elf.u32(field(16)?)?),
+                }
+            })
+        })
+        .collect();
+
+    let dynamic = segments.iter().find(|segment| segment.kind == PT_DYNAMIC)?;
+    let entry_size = if elf.is_64 { 16 } else { 8 };
+    let entries: Vec<(u64, u64)> = (0..dynamic.size / entry_size)
+        .map_while(|i| {
+            let entry = i
+                .checked_mul(entry_size)
+                .and_then(|offset| dynamic.offset.checked_add(offset))?;
+            let entry = usize::try_from(entry).ok()?;
+            let tag = elf.word(entry)?;
+            let value = elf.word(entry.checked_add(entry_size as usize / 2)?)?;
+            Some((tag, value)).filter(|(tag, _)| *tag != DT_NULL)
+        })
+        .collect();
+
+    // the string table is given by its address once loaded, found in the file through the
+    // segment it is loaded from
+    let strtab = entries
+        .iter()
+        .find(|(tag, _)| *tag == DT_STRTAB)
+        .and_then(|(_, address)| {
+            segments
+                .iter()
+                .filter(|segment| segment.kind == PT_LOAD)
+                .find(|segment| {
+                    segment
+                        .address
+                        .checked_add(segment.size)
+                        .is_some_and(|end| (segment.address..end).contains(address))
+                })
+                .and_then(|segment| {
+                    address
+                        .checked_sub(segment.address)?
+                        .checked_add(segment.offset)
+                })
+        });
+    let needed = entries
+        .iter()
+        .filter(|(tag, _)| *tag == DT_NEEDED)
+        .filter_map(|(_, name)| elf.string(usize::try_from(strtab?.checked_add(*name)?).ok()?))
+        .collect();
+
+    Some(ElfFile {
+        path: path.to_string(),
+        arch: elf_arch(machine),
+        needed,
+    })
+}
+
+/// The dynamically linked ELF files in the VSIX at `path`.
+pub fn native_binaries(path: &Path) -> Result<NativeBinaries> {
+    let mut vsix = zip::ZipArchive::new(File::open(path)?)
+        .wrap_err_with(|| format!("Unable to read {} as a VSIX", path.display()))?;
+
+    let mut files = Vec::new();
+    for i in 0..vsix.len() {
+        let mut entry = vsix.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+
+        // only read all of the files that start like ELF files
+        let mut magic = Vec::with_capacity(ELF_MAGIC.len());
+        (&mut entry)
+            .take(ELF_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        if magic != ELF_MAGIC {
+            continue;
+        }
+        let mut bytes = magic;
+        entry.read_to_end(&mut bytes)?;
+
+        match elf_file(&name, &bytes) {
+            Some(file) => files.push(file),
+            None => debug!("{} is not a dynamically linked ELF file", name),
+        }
+    }
+    Ok(NativeBinaries { files })
+}
+
+/// Hash the VSIX at `url` for `fetchurl` and look for native binaries in it, downloading it
+/// once. Failing to look inside it is only worth a warning.
+pub async fn get_hash_and_native_binaries(url: &str) -> Result<(String, NativeBinaries)> {
+    let (tmp_dir, dest_path) = download(url).await?;
+
+    let hash = nix_hash(&dest_path, true)?;
+    let native = native_binaries(&dest_path).unwrap_or_else(|e| {
+        warn!("Unable to look for native binaries in {}: {}", url, e);
+        NativeBinaries::default()
+    });
+
+    tmp_dir.close()?;
+
+    Ok((hash, native))
+}
+
+/// Hash the VSIX of every system, for a `fetchurl` by system, with the native binaries
+/// found in any of them.
+pub async fn get_system_sources_and_native_binaries(
+    urls: BTreeMap<String, String>,
+) -> Result<(BTreeMap<String, SystemSource>, NativeBinaries)> {
+    let mut sources = BTreeMap::new();
+    let mut native = NativeBinaries::default();
+    for (system, url) in urls {
+        let (sha256, system_native) = get_hash_and_native_binaries(&url).await?;
+        native.files.extend(system_native.files);
+        sources.insert(system, SystemSource { url, sha256 });
+    }
+    Ok((sources, native))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    /// A little-endian ELF64 shared library for x86_64, needing `needed`: the ELF header,
+    /// a PT_LOAD and a PT_DYNAMIC program header, the dynamic section and its strings.
+    fn elf64(needed: &[&str]) -> Vec<u8> {
+        let phoff = 64u64;
+        let dynamic_offset = phoff + 2 * 56;
+        let dynamic_size = (needed.len() as u64 + 2) * 16;
+        let strtab_offset = dynamic_offset + dynamic_size;
+        // loaded at a different address than its offset, as in real libraries
+        let base = 0x40_0000u64;
+
+        let mut strings = vec![0u8];
+        let mut names = Vec::new();
+        for soname in needed {
+            names.push(strings.len() as u64);
+            strings.extend_from_slice(soname.as_bytes());
+            strings.push(0);
+        }
+        let size = strtab_offset + strings.len() as u64;
+
+        let mut elf = Vec::new();
+        elf.extend_from_slice(b"\x7fELF\x02\x01\x01");
+        elf.resize(16, 0);
+        elf.extend_from_slice(&3u16.to_le_bytes()); // e_type: ET_DYN
+        elf.extend_from_slice(&62u16.to_le_bytes()); // e_machine: x86_64
+        elf.extend_from_slice(&1u32.to_le_bytes()); // e_version
+        elf.extend_from_slice(&0u64.to_le_bytes()); // e_entry
+        elf.extend_from_slice(&phoff.to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes()); // e_shoff
+        elf.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+        elf.extend_from_slice(&64u16.to_le_bytes()); // e_ehsize
+        elf.extend_from_slice(&56u16.to_le_bytes()); // e_phentsize
+        elf.extend_from_slice(&2u16.to_le_bytes()); // e_phnum
+        elf.extend_from_slice(&[0; 6]); // e_shentsize, e_shnum, e_shstrndx
+
+        for (kind, offset, size) in [
+            (PT_LOAD, 0, size),
+            (PT_DYNAMIC, dynamic_offset, dynamic_size),
+        ] {
+            elf.extend_from_slice(&kind.to_le_bytes());
+            elf.extend_from_slice(&0u32.to_le_bytes()); // p_flags
+            elf.extend_from_slice(&offset.to_le_bytes());
+            elf.extend_from_slice(&(base + offset).to_le_bytes()); // p_vaddr
+            elf.extend_from_slice(&(base + offset).to_le_bytes()); // p_paddr
+            elf.extend_from_slice(&size.to_le_bytes()); // p_filesz
+            elf.extend_from_slice(&size.to_le_bytes()); // p_memsz
+            elf.extend_from_slice(&0u64.to_le_bytes()); // p_align
+        }
+
+        for name in names {
+            elf.extend_from_slice(&DT_NEEDED.to_le_bytes());
+            elf.extend_from_slice(&name.to_le_bytes());
+        }
+        elf.extend_from_slice(&DT_STRTAB.to_le_bytes());
+        elf.extend_from_slice(&(base + strtab_offset).to_le_bytes());
+        elf.extend_from_slice(&[0; 16]); // DT_NULL
+        elf.extend_from_slice(&strings);
+        elf
+    }
+
+    #[test]
+    fn test_elf_file() {
+        let library = elf64(&["libstdc++.so.6", "libc.so.6"]);
+        assert_eq!(
+            elf_file("extension/bin/addon.node", &library),
+            Some(ElfFile {
+                path: String::from("extension/bin/addon.node"),
+                arch: String::from("x86_64"),
+                needed: vec![String::from("libstdc++.so.6"), String::from("libc.so.6")],
+            })
+        );
+
+        // statically linked, without a dynamic segment
+        let mut static_executable = library[..64].to_vec();
+        static_executable[56] = 0;
+        assert_eq!(elf_file("extension/bin/static", &static_executable), None);
+
+        assert_eq!(elf_file("extension/package.json", b"{}"), None);
+        assert_eq!(elf_file("extension/truncated", b"\x7fELF\x02\x01"), None);
+
+        // offsets and addresses that overflow are skipped rather than wrapped around
+        let mut program_headers_past_the_end = library.clone();
+        program_headers_past_the_end[32..40].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            elf_file("extension/bin/addon.node", &program_headers_past_the_end),
+            None
+        );
+
+        let mut load_past_the_end = library.clone();
+        load_past_the_end[80..88].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            elf_file("extension/bin/addon.node", &load_past_the_end).map(|file| file.needed),
+            Some(Vec::new())
+        );
+
+        let mut name_past_the_end = library;
+        name_past_the_end[184..192].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            elf_file("extension/bin/addon.node", &name_past_the_end).map(|file| file.needed),
+            Some(vec![String::from("libc.so.6")])
+        );
+    }
+
+    #[test]
+    fn test_native_binaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("extension.vsix");
+        let mut vsix = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options = zip::write::FileOptions::default();
+        vsix.start_file("extension/package.json", options).unwrap();
+        vsix.write_all(b"{}").unwrap();
+        vsix.start_file("extension/server/language-server", options)
+            .unwrap();
+        vsix.write_all(&elf64(&["libz.so.1"])).unwrap();
+        vsix.finish().unwrap();
+
+        let native = native_binaries(&path).unwrap();
+        assert_eq!(native.files.len(), 1);
+        assert_eq!(native.files[0].path, "extension/server/language-server");
+        assert_eq!(native.files[0].needed, ["libz.so.1"]);
+
+        assert!(native_binaries(&dir.path().join("missing.vsix")).is_err());
+    }
+}